@@ -0,0 +1,100 @@
+extern crate maillog;
+
+use std::env;
+use std::path::Path;
+use std::process;
+use maillog::parser::postfix::*;
+
+fn usage(name: &str) -> ! {
+	eprintln!("Usage: {} [--config FILE] DATABASE index LOG...", name);
+	eprintln!("       {} [--config FILE] DATABASE search [--queue-id ID] [--sender ADDRESS] [--recipient ADDRESS]", name);
+	eprintln!("           [--message-id ID] [--client-ip IP] [--sasl-username USER] [--after TIME] [--before TIME]");
+	eprintln!("TIME is in seconds since the UNIX epoch");
+	process::exit(1);
+}
+
+fn fail<T>(res: Result<T, StoreError>) -> T {
+	match res {
+		Err(x) => {
+			eprintln!("{}", x);
+			process::exit(1);
+		},
+		Ok(v) => v
+	}
+}
+
+#[cfg_attr(test, allow(dead_code))]
+fn print(transaction: &MailTransaction) {
+	for line in transaction.lines.iter() {
+		println!("{}", line);
+	}
+	for notification in transaction.notifications.iter() {
+		if let Some(ref child) = notification.transaction {
+			print(child);
+		}
+	}
+}
+
+#[cfg_attr(test, allow(dead_code))]
+fn main() {
+	let args: Vec<String> = env::args().collect();
+	let mut pos = 1;
+	let conf = match args.get(pos).map(|a| a.as_str()) {
+		Some("--config") if args.len() > pos + 1 => match ParserConfig::load(Path::new(&args[pos + 1])) {
+			Err(x) => {
+				eprintln!("Unable to load {}: {}", args[pos + 1], x);
+				process::exit(1);
+			},
+			Ok(conf) => {
+				pos += 2;
+				conf
+			}
+		},
+		_ => ParserConfig::new(vec![]),
+	};
+	if args.len() < pos + 2 {
+		usage(&args[0]);
+	}
+	let mut store = fail(Store::open(Path::new(&args[pos])));
+	match args[pos + 1].as_str() {
+		"index" => for log in args[pos + 2..].iter() {
+			let count = fail(store.index(Path::new(log), &conf, TransactionTracker::new(3600, 100000, Calendar::from_config(&conf))));
+			println!("{}: {} transactions", log, count);
+		},
+		"search" => {
+			let mut query = Query::new();
+			let mut options = args[pos + 2..].iter();
+			while let Some(option) = options.next() {
+				let value = match options.next() {
+					None => usage(&args[0]),
+					Some(v) => v.as_str()
+				};
+				query = match option.as_str() {
+					"--queue-id" => query.queue_id(value),
+					"--sender" => query.sender(value),
+					"--recipient" => query.recipient(value),
+					"--message-id" => query.message_id(value),
+					"--client-ip" => query.client_ip(value),
+					"--sasl-username" => query.sasl_username(value),
+					"--after" | "--before" => {
+						let time = match value.parse::<i64>() {
+							Err(_) => usage(&args[0]),
+							Ok(t) => Some(t)
+						};
+						let (after, before) = (query.after, query.before);
+						match option.as_str() {
+							"--after" => query.between(time, before),
+							_ => query.between(after, time),
+						}
+					},
+					_ => usage(&args[0]),
+				};
+			}
+			for transaction in fail(store.search(&query, &conf)) {
+				print(&transaction);
+				println!();
+			}
+		},
+		_ => usage(&args[0]),
+	}
+}
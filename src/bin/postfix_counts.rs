@@ -1,6 +1,11 @@
 extern crate maillog;
 
+use std::env;
+use std::fs::File;
 use std::io;
+use std::io::BufReader;
+use std::path::Path;
+use std::process;
 use std::io::prelude::*;
 use maillog::parser::postfix::*;
 use maillog::parser::postfix::messages::Message;
@@ -8,42 +13,185 @@ use maillog::parser::postfix::messages::Message;
 struct Counters {
 	all: u64,
 	ignored: u64,
+	errors: u64,
+	amavis: u64,
+	bogofilter: u64,
 	bounce: u64,
+	clamsmtpd: u64,
 	pickup: u64,
+	dovecotauthfailure: u64,
+	dovecotdelivery: u64,
 	forward: u64,
 	forwarderror: u64,
+	forwardverify: u64,
+	lookuperror: u64,
+	opendkim: u64,
+	opendmarc: u64,
+	policy: u64,
 	smtpd: u64,
+	smtpdconnect: u64,
+	smtpddisconnect: u64,
 	smtpdforward: u64,
 	smtpdlogin: u64,
 	cleanup: u64,
+	cleanupaction: u64,
+	custom: u64,
 	qmgr: u64,
 	qmgrremoved: u64,
 	qmgrexpired: u64,
 	rejects: u64,
+	rspamd: u64,
+	spamd: u64,
+	submission: u64,
 }
 
 #[cfg_attr(test, allow(dead_code))]
-fn print(counts: &Counters) {
+fn print(counts: &Counters, conf: &ParserConfig) {
 	println!("Read {} lines", counts.all);
 	println!("Ignored: {}", counts.ignored);
+	for rule in conf.noise() {
+		println!("  Noise ({}): {}", rule, rule.dropped());
+	}
+	println!("Errors: {}", counts.errors);
+	println!("Amavis: {}", counts.amavis);
+	println!("Bogofilter: {}", counts.bogofilter);
 	println!("Bounce: {}", counts.bounce);
+	println!("Clamsmtpd: {}", counts.clamsmtpd);
 	println!("Pickups: {}", counts.pickup);
+	println!("DovecotAuthFailures: {}", counts.dovecotauthfailure);
+	println!("DovecotDeliveries: {}", counts.dovecotdelivery);
 	println!("Forwards: {}", counts.forward);
 	println!("ForwardErrors: {}", counts.forwarderror);
+	println!("ForwardVerify: {}", counts.forwardverify);
+	println!("LookupErrors: {}", counts.lookuperror);
+	println!("Opendkim: {}", counts.opendkim);
+	println!("Opendmarc: {}", counts.opendmarc);
+	println!("Policies: {}", counts.policy);
 	println!("Smtpd: {}", counts.smtpd);
+	println!("SmtpdConnect: {}", counts.smtpdconnect);
+	println!("SmtpdDisconnect: {}", counts.smtpddisconnect);
 	println!("SmtpdForward: {}", counts.smtpdforward);
 	println!("SmtpdLogin: {}", counts.smtpdlogin);
 	println!("Cleanups: {}", counts.cleanup);
+	println!("CleanupActions: {}", counts.cleanupaction);
+	println!("Custom: {}", counts.custom);
 	println!("Qmgr: {}", counts.qmgr);
 	println!("QmgrRemoved: {}", counts.qmgrremoved);
 	println!("QmgrExpired: {}", counts.qmgrexpired);
 	println!("Rejects: {}", counts.rejects);
+	println!("Rspamd: {}", counts.rspamd);
+	println!("Spamd: {}", counts.spamd);
+	println!("Submissions: {}", counts.submission);
+}
+
+#[cfg_attr(test, allow(dead_code))]
+fn usage(name: &str) -> ! {
+	eprintln!("Usage: {} [--config FILE] [--window SECONDS] [[--timezone OFFSET] LOG...] (or < maillog)", name);
+	eprintln!("--timezone applies to the logs after it, instead of the configured one");
+	eprintln!("Without a configured year, logs are dated from the year they were last modified");
+	process::exit(1);
+}
+
+#[cfg_attr(test, allow(dead_code))]
+fn count(counts: &mut Counters, message: &Message) {
+	match message {
+		&Message::Amavis{m:_} => counts.amavis += 1,
+		&Message::Bogofilter{m:_} => counts.bogofilter += 1,
+		&Message::Bounce{m:_} => counts.bounce += 1,
+		&Message::Clamsmtpd{m:_} => counts.clamsmtpd += 1,
+		&Message::Cleanup{m:_} => counts.cleanup += 1,
+		&Message::CleanupAction{m:_} => counts.cleanupaction += 1,
+		&Message::Custom{m:_} => counts.custom += 1,
+		&Message::DovecotAuthFailure{m:_} => counts.dovecotauthfailure += 1,
+		&Message::DovecotDelivery{m:_} => counts.dovecotdelivery += 1,
+		&Message::Pickup{m:_} => counts.pickup += 1,
+		&Message::Forward{m:_}=> counts.forward += 1,
+		&Message::ForwardError{m:_}=> counts.forwarderror += 1,
+		&Message::ForwardVerify{m:_}=> counts.forwardverify += 1,
+		&Message::LookupError{m:_}=> counts.lookuperror += 1,
+		&Message::Opendkim{m:_}=> counts.opendkim += 1,
+		&Message::Opendmarc{m:_}=> counts.opendmarc += 1,
+		&Message::Policy{m:_}=> counts.policy += 1,
+		&Message::Qmgr{m:_} => counts.qmgr += 1,
+		&Message::QmgrRemoved{m:_} => counts.qmgrremoved += 1,
+		&Message::QmgrExpired{m:_} => counts.qmgrexpired += 1,
+		&Message::Smtpd{m:_}=> counts.smtpd += 1,
+		&Message::SmtpdConnect{m:_}=> counts.smtpdconnect += 1,
+		&Message::SmtpdDisconnect{m:_}=> counts.smtpddisconnect += 1,
+		&Message::SmtpdForward{m:_}=> counts.smtpdforward += 1,
+		&Message::SmtpdLogin{m:_}=> counts.smtpdlogin += 1,
+		&Message::Reject{m:_}=> counts.rejects += 1,
+		&Message::Rspamd{m:_}=> counts.rspamd += 1,
+		&Message::Spamd{m:_}=> counts.spamd += 1,
+		&Message::Submission{m:_}=> counts.submission += 1,
+	};
 }
 
 #[cfg_attr(test, allow(dead_code))]
 fn main() {
-	let mut counts = Counters { all: 0, ignored: 0, bounce: 0, pickup: 0, forward: 0, forwarderror: 0, smtpd: 0, smtpdforward: 0, smtpdlogin: 0, cleanup: 0, qmgr: 0, qmgrremoved: 0, qmgrexpired: 0, rejects: 0 };
-	let conf = ParserConfig { process_noise: vec!["clamsmtpd".to_string(), "postlicyd".to_string()] };
+	let mut counts = Counters { all: 0, ignored: 0, errors: 0, amavis: 0, bogofilter: 0, bounce: 0, clamsmtpd: 0, pickup: 0, dovecotauthfailure: 0, dovecotdelivery: 0, forward: 0, forwarderror: 0, forwardverify: 0, lookuperror: 0, opendkim: 0, opendmarc: 0, policy: 0, smtpd: 0, smtpdconnect: 0, smtpddisconnect: 0, smtpdforward: 0, smtpdlogin: 0, cleanup: 0, cleanupaction: 0, custom: 0, qmgr: 0, qmgrremoved: 0, qmgrexpired: 0, rejects: 0, rspamd: 0, spamd: 0, submission: 0 };
+	let args: Vec<String> = env::args().collect();
+	let mut conf = None;
+	let mut window = 0;
+	let mut timezone = None;
+	let mut logs = Vec::new();
+	let mut options = args[1..].iter();
+	while let Some(arg) = options.next() {
+		match (arg.as_str(), options.as_slice().first()) {
+			("--config", Some(file)) => match ParserConfig::load(Path::new(file)) {
+				Err(x) => {
+					eprintln!("Unable to load {}: {}", file, x);
+					process::exit(1);
+				},
+				Ok(c) => conf = Some(c)
+			},
+			("--window", Some(seconds)) => match seconds.parse::<u32>() {
+				Err(_) => usage(&args[0]),
+				Ok(w) => window = w
+			},
+			("--timezone", Some(offset)) => match parse_timezone(offset) {
+				None => usage(&args[0]),
+				Some(t) => timezone = Some(t)
+			},
+			(log, _) if !log.starts_with("--") => {
+				logs.push((log.to_string(), timezone));
+				continue;
+			},
+			_ => usage(&args[0]),
+		}
+		options.next();
+	}
+	let conf = conf.unwrap_or_else(|| ParserConfig::new(vec![]));
+
+	if !logs.is_empty() {
+		let mut reader = MergeReader::new(&conf, window);
+		for &(ref log, timezone) in logs.iter() {
+			let timezone = timezone.unwrap_or(conf.timezone.unwrap_or(0));
+			let (file, calendar) = match File::open(log).and_then(|mut f| Calendar::from_file(&mut f, &conf, timezone).map(|c| (f, c))) {
+				Err(x) => {
+					eprintln!("Unable to open {}: {}", log, x);
+					process::exit(1);
+				},
+				Ok(opened) => opened
+			};
+			reader.add_with_calendar(log, BufReader::new(file), calendar);
+		}
+		for res in reader.by_ref() {
+			match res {
+				Ok(m) => count(&mut counts, &m.message),
+				Err(x) => {
+					print(&counts, &conf);
+					eprintln!("Failure {}", x);
+					process::exit(1);
+				},
+			}
+		}
+		counts.all = reader.lines();
+		counts.ignored = reader.ignored();
+		counts.errors = reader.errors();
+		print(&counts, &conf);
+		return;
+	}
 
 	let stdin = io::stdin();
 	let mut buffer: Vec<u8> = Vec::new();
@@ -57,20 +205,14 @@ fn main() {
 		counts.all += 1;
 		match parse_line(line.clone(), &conf) {
 			Ok(None) => counts.ignored += 1,
-			Ok(Some(Message::Bounce{m:_})) => counts.bounce += 1,
-			Ok(Some(Message::Cleanup{m:_})) => counts.cleanup += 1,
-			Ok(Some(Message::Pickup{m:_})) => counts.pickup += 1,
-			Ok(Some(Message::Forward{m:_}))=> counts.forward += 1,
-			Ok(Some(Message::ForwardError{m:_}))=> counts.forwarderror += 1,
-			Ok(Some(Message::Qmgr{m:_})) => counts.qmgr += 1,
-			Ok(Some(Message::QmgrRemoved{m:_})) => counts.qmgrremoved += 1,
-			Ok(Some(Message::QmgrExpired{m:_})) => counts.qmgrexpired += 1,
-			Ok(Some(Message::Smtpd{m:_}))=> counts.smtpd += 1,
-			Ok(Some(Message::SmtpdForward{m:_}))=> counts.smtpdforward += 1,
-			Ok(Some(Message::SmtpdLogin{m:_}))=> counts.smtpdlogin += 1,
-			Ok(Some(Message::Reject{m:_}))=> counts.rejects += 1,
-			Err(x) => {print(&counts); panic!("Failure {} on {}", x, line)},
+			Ok(Some(message)) => count(&mut counts, &message),
+			Err(x) => if conf.strict {
+				print(&counts, &conf);
+				panic!("Failure {} on {}", x, line)
+			} else {
+				counts.errors += 1
+			},
 		};
 	};
-	print(&counts);
+	print(&counts, &conf);
 }
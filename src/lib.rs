@@ -1 +1,5 @@
+extern crate regex;
+#[cfg(feature = "store")]
+extern crate rusqlite;
+
 pub mod parser;
@@ -1,2 +1 @@
 pub mod postfix;
-mod bogofilter;
@@ -0,0 +1,145 @@
+use std::fs::File;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::time::UNIX_EPOCH;
+use super::config::ParserConfig;
+use super::messages::Inner;
+
+// Half a year: a timestamp going back further is the next year
+const ROLLOVER: u32 = 183 * 24 * 3600;
+
+// Average length of a year, in seconds
+const YEAR: i64 = 365 * 24 * 3600 + 6 * 3600;
+
+// Dates the lines of one log in seconds since the UNIX epoch. Syslog leaves
+// out the year: it starts at the given one, and moves to the next one when
+// the timestamps go back by more than half a year. A line late by more than
+// half a year belongs to the previous year.
+#[derive(Debug, Clone)]
+pub struct Calendar {
+	year: i32,
+	// UTC offset of the log, in seconds
+	timezone: i32,
+	// Seconds since the start of the year of the previous line
+	previous: Option<u32>,
+}
+
+impl Calendar {
+	pub fn new(year: i32, timezone: i32) -> Calendar {
+		Calendar { year: year, timezone: timezone, previous: None }
+	}
+
+	// Year and UTC offset of the configuration, 1970 and UTC when unset
+	pub fn from_config(conf: &ParserConfig) -> Calendar {
+		Calendar::new(conf.year.unwrap_or(1970), conf.timezone.unwrap_or(0))
+	}
+
+	// Year of the configuration, or else the latest year that does not date
+	// the first line of the file after its last modification, give or take
+	// a day: December lines in a file modified in January are from the
+	// previous year. The file is left at its start.
+	pub fn from_file(file: &mut File, conf: &ParserConfig, timezone: i32) -> io::Result<Calendar> {
+		if let Some(year) = conf.year {
+			return Ok(Calendar::new(year, timezone));
+		}
+		let modified = match file.metadata().and_then(|m| m.modified()) {
+			Err(x) => return Err(x),
+			Ok(m) => m.duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0)
+		};
+		let mut first = Vec::new();
+		if let Err(x) = BufReader::new(&*file).read_until(b'\n', &mut first) {
+			return Err(x);
+		}
+		if let Err(x) = file.seek(SeekFrom::Start(0)) {
+			return Err(x);
+		}
+		let first = String::from_utf8_lossy(&first);
+		let estimate = 1970 + ((modified + timezone as i64) / YEAR) as i32;
+		let year = (estimate - 1..estimate + 2).rev()
+			.find(|&y| Inner::epoch_of(&first, y, timezone).map_or(false, |t| t <= modified + 24 * 3600));
+		Ok(Calendar::new(year.unwrap_or(estimate), timezone))
+	}
+
+	// Goes on dating a log from a previous state
+	pub fn resume(year: i32, timezone: i32, previous: Option<u32>) -> Calendar {
+		Calendar { year: year, timezone: timezone, previous: previous }
+	}
+
+	// Current year, and seconds since its start of the previous line
+	pub fn state(&self) -> (i32, Option<u32>) {
+		(self.year, self.previous)
+	}
+
+	pub fn epoch(&mut self, inner: &Inner) -> Option<i64> {
+		let timestamp = match inner.timestamp() {
+			None => return None,
+			Some(t) => t
+		};
+		match self.previous {
+			Some(previous) if previous + ROLLOVER < timestamp => inner.epoch(self.year - 1, self.timezone),
+			previous => {
+				if previous.map_or(false, |previous| timestamp + ROLLOVER < previous) {
+					self.year += 1;
+				}
+				self.previous = Some(timestamp);
+				inner.epoch(self.year, self.timezone)
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::env;
+	use std::fs;
+	use std::io::Write;
+	use std::time::Duration;
+	use super::super::messages::Inner;
+
+	fn date(calendar: &mut Calendar, line: &str) -> Option<i64> {
+		let conf = ParserConfig::new(vec![]);
+		match Inner::parse(&conf, line.to_string()) {
+			Err(x) => panic!("Failed to parse: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some((inner, _))) => calendar.epoch(&inner)
+		}
+	}
+
+	#[test]
+	fn from_file() {
+		let path = env::temp_dir().join(format!("maillog-calendar-{}.log", ::std::process::id()));
+		let mut file = File::create(&path).unwrap();
+		writeln!(file, "Dec 31 23:59:59 yuuai postfix/qmgr[1236]: 84ED020916: removed").unwrap();
+		// 2016-01-01T00:10:00Z
+		file.set_modified(UNIX_EPOCH + Duration::from_secs(1451607000)).unwrap();
+		let mut file = File::open(&path).unwrap();
+		let conf = ParserConfig::new(vec![]);
+		let mut calendar = Calendar::from_file(&mut file, &conf, 0).unwrap();
+		assert_eq!(calendar.state(), (2015, None));
+		assert_eq!(date(&mut calendar, "Dec 31 23:59:59 yuuai postfix/qmgr[1236]: 84ED020916: removed"), Some(1451606399));
+		assert_eq!(date(&mut calendar, "Jan  1 00:00:01 yuuai postfix/qmgr[1236]: 84ED020916: removed"), Some(1451606401));
+		let mut line = String::new();
+		BufReader::new(&file).read_line(&mut line).unwrap();
+		assert!(line.starts_with("Dec 31"));
+		let mut conf = ParserConfig::new(vec![]);
+		conf.year = Some(2012);
+		assert_eq!(Calendar::from_file(&mut file, &conf, 0).unwrap().state(), (2012, None));
+		fs::remove_file(&path).unwrap();
+	}
+
+	#[test]
+	fn new_year() {
+		let mut calendar = Calendar::new(2015, 3600);
+		// 2015-12-31T22:59:59Z
+		assert_eq!(date(&mut calendar, "Dec 31 23:59:59 yuuai postfix/qmgr[1236]: 84ED020916: removed"), Some(1451602799));
+		assert_eq!(date(&mut calendar, "Jan  1 00:00:01 yuuai postfix/qmgr[1236]: 84ED020916: removed"), Some(1451602801));
+		assert_eq!(date(&mut calendar, "Dec 31 23:59:58 yuuai postfix/qmgr[1236]: 84ED020916: removed"), Some(1451602798));
+		// 2016 is a leap year
+		assert_eq!(date(&mut calendar, "Feb 29 00:00:00 yuuai postfix/qmgr[1236]: 84ED020916: removed"), Some(1456700400));
+		assert_eq!(date(&mut calendar, "Mar  1 00:00:00 yuuai postfix/qmgr[1236]: 84ED020916: removed"), Some(1456786800));
+	}
+}
@@ -1,4 +1,425 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use super::ConfigError;
+use super::ParseError;
+use super::messages::Inner;
+use super::messages::Message;
+use super::messages::MessageParser;
+use super::noise::NoiseRule;
+
+// MessageParser::parse of a built-in or registered parser
+pub type ParseFn = fn(Inner, usize) -> Result<Option<Message>, ParseError>;
+
 #[derive(Debug)]
 pub struct ParserConfig {
-	pub process_noise: Vec<String>
+	// Syslog identifiers dropped as a whole, without drop counts
+	#[deprecated(note = "use ParserConfig::new or add_noise(NoiseRule::identifier(...))")]
+	pub process_noise: Vec<String>,
+	noise: Vec<NoiseRule>,
+	parsers: Vec<(String, ParseFn)>,
+	aliases: Vec<(String, String)>,
+	instances: Vec<String>,
+	// UTC offset of the log timestamps, in seconds
+	pub timezone: Option<i32>,
+	// Year of the log timestamps, which syslog omits
+	pub year: Option<i32>,
+	// Lines from unknown processes or instances are errors instead of ignored
+	pub strict: bool,
+}
+
+// Noise rule of a [noise] section being read: kind, value and line of the
+// key, message and line of the section header. A section may only set
+// defaults.
+struct PendingNoise {
+	kind: Option<(String, String, usize)>,
+	message: Option<String>,
+	defaults: bool,
+	line: usize,
+}
+
+impl PendingNoise {
+	fn build(self) -> Result<Option<NoiseRule>, ConfigError> {
+		let rule = match self.kind {
+			None if self.defaults && self.message.is_none() => return Ok(None),
+			None => return Err(ConfigError::NoiseWithoutMatch(self.line)),
+			Some((ref kind, ref value, _)) if kind == "identifier" => NoiseRule::identifier(value),
+			Some((ref kind, ref value, _)) if kind == "process" => NoiseRule::process(value),
+			Some((_, ref value, line)) => match NoiseRule::regex(value) {
+				Err(_) => return Err(ConfigError::BadRegex(line)),
+				Ok(rule) => rule
+			},
+		};
+		match self.message {
+			None => Ok(Some(rule)),
+			Some(ref message) => Ok(Some(rule.message(message)))
+		}
+	}
+}
+
+// "UTC", "Z" or "+0200", as a UTC offset in seconds
+pub fn parse_timezone(value: &str) -> Option<i32> {
+	if value == "UTC" || value == "Z" {
+		return Some(0);
+	}
+	if value.len() != 5 || !value.is_ascii() || !value[1..].bytes().all(|b| b'0' <= b && b <= b'9') {
+		return None;
+	}
+	let hours = value[1..3].parse::<i32>().unwrap();
+	let minutes = value[3..5].parse::<i32>().unwrap();
+	if hours > 14 || minutes > 59 {
+		return None;
+	}
+	match &value[..1] {
+		"+" => Some(hours * 3600 + minutes * 60),
+		"-" => Some(-(hours * 3600 + minutes * 60)),
+		_ => None
+	}
+}
+
+impl ParserConfig {
+	// Each process_noise entry drops the lines logged under exactly that
+	// syslog identifier
+	pub fn new(process_noise: Vec<String>) -> ParserConfig {
+		let mut noise: Vec<NoiseRule> = process_noise.iter().map(|p| NoiseRule::identifier(p)).collect();
+		// Connection notices of the delivery agents parsed by Forward
+		for process in ["smtp", "local", "pipe", "error", "discard"].iter() {
+			for message in ["enabling PIX workarounds: ", "lost connection with", "conversation with ", "Cannot start TLS: "].iter() {
+				noise.push(NoiseRule::process(process).message(message));
+			}
+		}
+		#[allow(deprecated)]
+		ParserConfig { process_noise: Vec::new(), noise: noise, parsers: Vec::new(), aliases: Vec::new(), instances: Vec::new(),
+		               timezone: None, year: None, strict: true }
+	}
+
+	pub fn load(path: &Path) -> Result<ParserConfig, ConfigError> {
+		let mut content = String::new();
+		match File::open(path).and_then(|mut f| f.read_to_string(&mut content)) {
+			Err(x) => return Err(ConfigError::Io(x)),
+			Ok(_) => ()
+		};
+		ParserConfig::read(&content)
+	}
+
+	// INI-like format, '#' starts a comment at the start of a line or after
+	// a space, so values like regexes may contain it:
+	//   [parser]     mode = strict|lenient, year = 2016, timezone = +0200|UTC,
+	//                instances = postfix-in, postfix-out
+	//   [processes]  logged-name = built-in name, "smtp-amavis = smtp"
+	//   [noise]      one rule per section: identifier, process or regex,
+	//                optionally restricted by message; defaults = off drops
+	//                the built-in rules
+	pub fn read(content: &str) -> Result<ParserConfig, ConfigError> {
+		let mut config = ParserConfig::new(Vec::new());
+		let mut section = String::new();
+		let mut pending: Option<PendingNoise> = None;
+		let mut rules = Vec::new();
+		let mut defaults = true;
+		for (i, line) in content.lines().enumerate() {
+			let line_nb = i + 1;
+			let line = line.trim();
+			let line = if line.starts_with('#') {
+				""
+			} else {
+				match line.find(" #").or_else(|| line.find("\t#")) {
+					None => line,
+					Some(p) => line[..p].trim_end()
+				}
+			};
+			if line.is_empty() {
+				continue;
+			}
+			if line.starts_with('[') && line.ends_with(']') {
+				if let Some(noise) = pending.take() {
+					match noise.build() {
+						Err(x) => return Err(x),
+						Ok(rule) => rules.extend(rule)
+					}
+				}
+				section = line[1..line.len() - 1].trim().to_string();
+				match section.as_str() {
+					"parser" | "processes" => (),
+					"noise" => pending = Some(PendingNoise { kind: None, message: None, defaults: false, line: line_nb }),
+					_ => return Err(ConfigError::UnknownSection(line_nb)),
+				}
+				continue;
+			}
+			let (key, value) = match line.find('=') {
+				None => return Err(ConfigError::BadLine(line_nb)),
+				Some(p) => (line[..p].trim(), line[p + 1..].trim())
+			};
+			if key.is_empty() || value.is_empty() {
+				return Err(ConfigError::BadLine(line_nb));
+			}
+			match section.as_str() {
+				"parser" => match key {
+					"mode" => config.strict = match value {
+						"strict" => true,
+						"lenient" => false,
+						_ => return Err(ConfigError::BadValue(line_nb)),
+					},
+					"year" => config.year = match value.parse::<i32>() {
+						Err(_) => return Err(ConfigError::BadValue(line_nb)),
+						Ok(year) => Some(year)
+					},
+					"timezone" => config.timezone = match parse_timezone(value) {
+						None => return Err(ConfigError::BadValue(line_nb)),
+						Some(offset) => Some(offset)
+					},
+					"instances" => config.instances = value.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect(),
+					_ => return Err(ConfigError::UnknownKey(line_nb)),
+				},
+				"processes" => config.alias(key, value),
+				"noise" => {
+					let noise = pending.as_mut().unwrap();
+					match key {
+						"identifier" | "process" | "regex" if noise.kind.is_none() => noise.kind = Some((key.to_string(), value.to_string(), line_nb)),
+						"identifier" | "process" | "regex" => return Err(ConfigError::BadValue(line_nb)),
+						"message" => noise.message = Some(value.to_string()),
+						"defaults" => {
+							defaults = match value {
+								"on" => true,
+								"off" => false,
+								_ => return Err(ConfigError::BadValue(line_nb)),
+							};
+							noise.defaults = true;
+						},
+						_ => return Err(ConfigError::UnknownKey(line_nb)),
+					}
+				},
+				_ => return Err(ConfigError::UnknownKey(line_nb)),
+			}
+		}
+		if let Some(noise) = pending.take() {
+			match noise.build() {
+				Err(x) => return Err(x),
+				Ok(rule) => rules.extend(rule)
+			}
+		}
+		if !defaults {
+			config.clear_noise();
+		}
+		for rule in rules {
+			config.add_noise(rule);
+		}
+		Ok(config)
+	}
+
+	pub fn add_noise(&mut self, rule: NoiseRule) {
+		self.noise.push(rule);
+	}
+
+	// Drops the noise rules, the built-in ones included
+	pub fn clear_noise(&mut self) {
+		self.noise.clear();
+	}
+
+	pub fn noise(&self) -> &[NoiseRule] {
+		&self.noise
+	}
+
+	// rest is the line after the hostname, the first matching rule counts it.
+	// Process rules apply to the built-in process of aliases too.
+	pub fn is_noise(&self, rest: &str) -> bool {
+		let identifier = match rest.find(|c| c == '[' || c == ':') {
+			None => rest,
+			Some(p) => &rest[..p]
+		};
+		#[allow(deprecated)]
+		{
+			if self.process_noise.iter().any(|p| p == identifier) {
+				return true;
+			}
+		}
+		let logged = match identifier.rfind('/') {
+			None => identifier,
+			Some(p) => &identifier[p + 1..]
+		};
+		let process = self.process_name(logged);
+		match self.noise.iter().find(|rule| rule.matches_as(rest, Some(process))) {
+			None => false,
+			Some(rule) => {
+				rule.count();
+				true
+			}
+		}
+	}
+
+	// Lines from the given syslog identifier are handed to T, which takes
+	// precedence over any built-in parser for the same identifier.
+	pub fn register<T: MessageParser>(&mut self, process: &str) {
+		self.parsers.retain(|&(ref p, _)| p != process);
+		self.parsers.push((process.to_string(), T::parse));
+	}
+
+	pub fn parser(&self, process: &str) -> Option<ParseFn> {
+		self.parsers.iter().find(|&&(ref p, _)| p == process).map(|&(_, f)| f)
+	}
+
+	// Processes logging under another name, like master.cf services with
+	// their own syslog_name, are parsed as the built-in process
+	pub fn alias(&mut self, logged: &str, process: &str) {
+		self.aliases.retain(|&(ref l, _)| l != logged);
+		self.aliases.push((logged.to_string(), process.to_string()));
+	}
+
+	pub fn process_name<'a>(&'a self, logged: &'a str) -> &'a str {
+		match self.aliases.iter().find(|&&(ref l, _)| l == logged) {
+			None => logged,
+			Some(&(_, ref process)) => process
+		}
+	}
+
+	// Without any configured instance, all postfix instances are accepted
+	pub fn add_instance(&mut self, queue: &str) {
+		self.instances.push(queue.to_string());
+	}
+
+	pub fn known_instance(&self, queue: &str) -> bool {
+		self.instances.is_empty() || self.instances.iter().any(|i| i == queue)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::ConfigError;
+
+	#[test]
+	fn read() {
+		let conf = match ParserConfig::read("# Fleet configuration
+[parser]
+mode = lenient
+year = 2016
+timezone = +0130
+instances = postfix-in, postfix-out
+
+[processes]
+smtp-amavis = smtp # content filter return path
+
+[noise]
+identifier = clamsmtpd
+
+[noise]
+regex = ^postfix-out/smtpd\\[\\d+\\]: (dis)?connect
+") {
+			Err(x) => panic!("Config error: {}", x),
+			Ok(conf) => conf
+		};
+		assert_eq!(conf.strict, false);
+		assert_eq!(conf.year, Some(2016));
+		assert_eq!(conf.timezone, Some(5400));
+		assert!(conf.known_instance("postfix-out"));
+		assert!(!conf.known_instance("postfix"));
+		assert_eq!(conf.process_name("smtp-amavis"), "smtp");
+		assert_eq!(conf.process_name("smtpd"), "smtpd");
+		assert!(conf.is_noise("clamsmtpd[42]: started"));
+		assert!(conf.is_noise("postfix-out/smtpd[42]: connect from localhost[127.0.0.1]"));
+		assert!(!conf.is_noise("postfix-in/smtpd[42]: connect from localhost[127.0.0.1]"));
+		assert_eq!(conf.noise().iter().map(|r| r.dropped()).sum::<u64>(), 2);
+	}
+
+	#[test]
+	fn alias_noise() {
+		let mut conf = ParserConfig::new(vec![]);
+		conf.alias("smtp-amavis", "smtp");
+		assert!(conf.is_noise("postfix/smtp-amavis[42]: lost connection with 127.0.0.1[127.0.0.1]"));
+		assert!(!conf.is_noise("postfix/smtpd-amavis[42]: lost connection with 127.0.0.1[127.0.0.1]"));
+	}
+
+	#[test]
+	fn comments() {
+		let conf = match ParserConfig::read("[noise]\nregex = ^foo#bar\n\t# rule above keeps its '#'\n[noise]\nidentifier = clamsmtpd\t# not dropped") {
+			Err(x) => panic!("Config error: {}", x),
+			Ok(conf) => conf
+		};
+		assert!(conf.is_noise("foo#bar[42]: started"));
+		assert!(!conf.is_noise("foo[42]: started"));
+		assert!(conf.is_noise("clamsmtpd[42]: started"));
+	}
+
+	#[test]
+	fn forward_noise() {
+		let conf = ParserConfig::new(vec![]);
+		assert!(conf.is_noise("postfix/local[42]: lost connection with 127.0.0.1[127.0.0.1]"));
+		assert!(conf.is_noise("postfix/pipe[42]: conversation with 127.0.0.1[127.0.0.1] timed out"));
+		assert!(!conf.is_noise("postfix/qmgr[42]: lost connection with 127.0.0.1[127.0.0.1]"));
+	}
+
+	#[test]
+	fn no_defaults() {
+		let conf = match ParserConfig::read("[noise]\nidentifier = clamsmtpd\n[noise]\ndefaults = off") {
+			Err(x) => panic!("Config error: {}", x),
+			Ok(conf) => conf
+		};
+		assert_eq!(conf.noise().len(), 1);
+		assert!(conf.is_noise("clamsmtpd[42]: started"));
+		assert!(!conf.is_noise("postfix/smtp[42]: lost connection with 127.0.0.1[127.0.0.1]"));
+		let mut conf = ParserConfig::new(vec![]);
+		conf.clear_noise();
+		assert!(conf.noise().is_empty());
+	}
+
+	#[test]
+	#[allow(deprecated)]
+	fn process_noise() {
+		let mut conf = ParserConfig::new(vec![]);
+		conf.process_noise.push("clamsmtpd".to_string());
+		assert!(conf.is_noise("clamsmtpd[42]: started"));
+		assert!(!conf.is_noise("clamsmtpd-milter[42]: started"));
+	}
+
+	#[test]
+	fn defaults() {
+		let conf = match ParserConfig::read("") {
+			Err(x) => panic!("Config error: {}", x),
+			Ok(conf) => conf
+		};
+		assert_eq!(conf.strict, true);
+		assert_eq!(conf.year, None);
+		assert_eq!(conf.timezone, None);
+		assert!(conf.known_instance("postfix"));
+		assert_eq!(conf.noise().len(), ParserConfig::new(vec![]).noise().len());
+	}
+
+	#[test]
+	fn errors() {
+		match ParserConfig::read("[parser]\nmode = sloppy") {
+			Err(ConfigError::BadValue(2)) => (),
+			x => panic!("Should have been BadValue(2): {:?}", x.map(|_| ()))
+		}
+		match ParserConfig::read("[parser]\ntimezone = CEST") {
+			Err(ConfigError::BadValue(2)) => (),
+			x => panic!("Should have been BadValue(2): {:?}", x.map(|_| ()))
+		}
+		match ParserConfig::read("[parser]\ntimezone = é123") {
+			Err(ConfigError::BadValue(2)) => (),
+			x => panic!("Should have been BadValue(2): {:?}", x.map(|_| ()))
+		}
+		match ParserConfig::read("[filters]") {
+			Err(ConfigError::UnknownSection(1)) => (),
+			x => panic!("Should have been UnknownSection(1): {:?}", x.map(|_| ()))
+		}
+		match ParserConfig::read("[parser]\nverbose = yes") {
+			Err(ConfigError::UnknownKey(2)) => (),
+			x => panic!("Should have been UnknownKey(2): {:?}", x.map(|_| ()))
+		}
+		match ParserConfig::read("mode") {
+			Err(ConfigError::BadLine(1)) => (),
+			x => panic!("Should have been BadLine(1): {:?}", x.map(|_| ()))
+		}
+		match ParserConfig::read("[noise]\nmessage = warning:\nregex = (") {
+			Err(ConfigError::BadRegex(3)) => (),
+			x => panic!("Should have been BadRegex(3): {:?}", x.map(|_| ()))
+		}
+		match ParserConfig::read("[noise]\ndefaults = maybe") {
+			Err(ConfigError::BadValue(2)) => (),
+			x => panic!("Should have been BadValue(2): {:?}", x.map(|_| ()))
+		}
+		match ParserConfig::read("[noise]\nmessage = warning:\n[parser]") {
+			Err(ConfigError::NoiseWithoutMatch(1)) => (),
+			x => panic!("Should have been NoiseWithoutMatch(1): {:?}", x.map(|_| ()))
+		}
+	}
 }
@@ -1,4 +1,5 @@
 use std::fmt;
+use std::io;
 
 pub enum ParseError {
 	DateTooShort,
@@ -53,6 +54,35 @@ pub enum ParseError {
 	QmgrBadNrcpt,
 	QmgrNotActive,
 	QmgrNrcptNotInt,
+	SubmissionUnknownLevel,
+	SubmissionBadUID,
+	LookupBadKey,
+	BogofilterUnknownFormat,
+	BogofilterUnknownVerdict,
+	BogofilterBadSpamicity,
+	BogofilterBadRegistration,
+	ClamsmtpdNoTo,
+	ClamsmtpdNoStatus,
+	PolicyNoSender,
+	PolicyNoRecipient,
+	PolicyNoAction,
+	AmavisUnknownVerdict,
+	AmavisNoSender,
+	AmavisNoRecipient,
+	AmavisBadHits,
+	AmavisBadSize,
+	AmavisBadElapsed,
+	OpendkimBadSignature,
+	OpendmarcUnknownResult,
+	DovecotBadUser,
+	DovecotBadMessageID,
+	DovecotNoRemoteIP,
+	SpamdBadResult,
+	SpamdBadScore,
+	RspamdBadMetric,
+	RspamdUnknownAction,
+	UnknownInstance,
+	SmtpdBadClient,
 }
 
 impl fmt::Display for ParseError {
@@ -110,6 +140,35 @@ impl fmt::Display for ParseError {
 			&ParseError::QmgrBadNrcpt => "Qmgr non ending nrcpt",
 			&ParseError::QmgrNotActive => "Qmgr not in active queue",
 			&ParseError::QmgrNrcptNotInt => "Qmgr nrcpt is not and int",
+			&ParseError::SubmissionUnknownLevel => "Submission unknown log level",
+			&ParseError::SubmissionBadUID => "Submission uid is not an int",
+			&ParseError::LookupBadKey => "Lookup error non ending key",
+			&ParseError::BogofilterUnknownFormat => "Bogofilter unknown format",
+			&ParseError::BogofilterUnknownVerdict => "Bogofilter unknown verdict",
+			&ParseError::BogofilterBadSpamicity => "Bogofilter spamicity is not a float",
+			&ParseError::BogofilterBadRegistration => "Bogofilter bad registration",
+			&ParseError::ClamsmtpdNoTo => "Clamsmtpd no to",
+			&ParseError::ClamsmtpdNoStatus => "Clamsmtpd no status",
+			&ParseError::PolicyNoSender => "Policy no sender",
+			&ParseError::PolicyNoRecipient => "Policy no recipient",
+			&ParseError::PolicyNoAction => "Policy no action",
+			&ParseError::AmavisUnknownVerdict => "Amavis unknown verdict",
+			&ParseError::AmavisNoSender => "Amavis no sender",
+			&ParseError::AmavisNoRecipient => "Amavis no recipient",
+			&ParseError::AmavisBadHits => "Amavis hits is not a float",
+			&ParseError::AmavisBadSize => "Amavis size is not an integer",
+			&ParseError::AmavisBadElapsed => "Amavis elapsed time is not an integer",
+			&ParseError::OpendkimBadSignature => "Opendkim bad signature",
+			&ParseError::OpendmarcUnknownResult => "Opendmarc unknown result",
+			&ParseError::DovecotBadUser => "Dovecot bad user",
+			&ParseError::DovecotBadMessageID => "Dovecot bad message id",
+			&ParseError::DovecotNoRemoteIP => "Dovecot no remote ip",
+			&ParseError::SpamdBadResult => "Spamd bad result",
+			&ParseError::SpamdBadScore => "Spamd score is not a float",
+			&ParseError::RspamdBadMetric => "Rspamd bad metric",
+			&ParseError::RspamdUnknownAction => "Rspamd unknown action",
+			&ParseError::UnknownInstance => "Unknown postfix instance",
+			&ParseError::SmtpdBadClient => "Smtpd non ending client",
 		};
 		write!(fmt, "{}", error)
 	}
@@ -121,6 +180,86 @@ impl fmt::Debug for ParseError {
 	}
 }
 
+// Errors while loading a ParserConfig file, with the faulty line number
+pub enum ConfigError {
+	Io(io::Error),
+	BadLine(usize),
+	UnknownSection(usize),
+	UnknownKey(usize),
+	BadValue(usize),
+	BadRegex(usize),
+	NoiseWithoutMatch(usize),
+}
+
+impl fmt::Display for ConfigError {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			&ConfigError::Io(ref x) => write!(fmt, "Config read error: {}", x),
+			&ConfigError::BadLine(l) => write!(fmt, "Config line {}: not a section nor a key", l),
+			&ConfigError::UnknownSection(l) => write!(fmt, "Config line {}: unknown section", l),
+			&ConfigError::UnknownKey(l) => write!(fmt, "Config line {}: unknown key", l),
+			&ConfigError::BadValue(l) => write!(fmt, "Config line {}: bad value", l),
+			&ConfigError::BadRegex(l) => write!(fmt, "Config line {}: bad regex", l),
+			&ConfigError::NoiseWithoutMatch(l) => write!(fmt, "Config line {}: noise rule without identifier, process or regex", l),
+		}
+	}
+}
+
+impl fmt::Debug for ConfigError {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		fmt::Display::fmt(self, fmt)
+	}
+}
+
+// Errors of a MergeReader, with the name of the faulty source
+pub enum MergeError {
+	Io(String, io::Error),
+	// Line rejected by a strict ParserConfig
+	Parse(String, ParseError, String),
+}
+
+impl fmt::Display for MergeError {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			&MergeError::Io(ref source, ref x) => write!(fmt, "{}: read error: {}", source, x),
+			&MergeError::Parse(ref source, ref x, ref line) => write!(fmt, "{}: {} on {}", source, x, line),
+		}
+	}
+}
+
+impl fmt::Debug for MergeError {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		fmt::Display::fmt(self, fmt)
+	}
+}
+
+// Errors of the transaction Store
+#[cfg(feature = "store")]
+pub enum StoreError {
+	Sqlite(::rusqlite::Error),
+	Io(io::Error),
+	// Line rejected by a strict ParserConfig
+	Parse(ParseError, String),
+}
+
+#[cfg(feature = "store")]
+impl fmt::Display for StoreError {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			&StoreError::Sqlite(ref x) => write!(fmt, "Store database error: {}", x),
+			&StoreError::Io(ref x) => write!(fmt, "Store read error: {}", x),
+			&StoreError::Parse(ref x, ref line) => write!(fmt, "Store parse error: {} on {}", x, line),
+		}
+	}
+}
+
+#[cfg(feature = "store")]
+impl fmt::Debug for StoreError {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		fmt::Display::fmt(self, fmt)
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -183,5 +322,44 @@ mod tests {
 		assert_print_eq(ParseError::QmgrBadNrcpt, "Qmgr non ending nrcpt");
 		assert_print_eq(ParseError::QmgrNotActive, "Qmgr not in active queue");
 		assert_print_eq(ParseError::QmgrNrcptNotInt, "Qmgr nrcpt is not and int");
+		assert_print_eq(ParseError::SubmissionUnknownLevel, "Submission unknown log level");
+		assert_print_eq(ParseError::SubmissionBadUID, "Submission uid is not an int");
+		assert_print_eq(ParseError::LookupBadKey, "Lookup error non ending key");
+		assert_print_eq(ParseError::BogofilterUnknownFormat, "Bogofilter unknown format");
+		assert_print_eq(ParseError::BogofilterUnknownVerdict, "Bogofilter unknown verdict");
+		assert_print_eq(ParseError::BogofilterBadSpamicity, "Bogofilter spamicity is not a float");
+		assert_print_eq(ParseError::BogofilterBadRegistration, "Bogofilter bad registration");
+		assert_print_eq(ParseError::ClamsmtpdNoTo, "Clamsmtpd no to");
+		assert_print_eq(ParseError::ClamsmtpdNoStatus, "Clamsmtpd no status");
+		assert_print_eq(ParseError::PolicyNoSender, "Policy no sender");
+		assert_print_eq(ParseError::PolicyNoRecipient, "Policy no recipient");
+		assert_print_eq(ParseError::PolicyNoAction, "Policy no action");
+		assert_print_eq(ParseError::AmavisUnknownVerdict, "Amavis unknown verdict");
+		assert_print_eq(ParseError::AmavisNoSender, "Amavis no sender");
+		assert_print_eq(ParseError::AmavisNoRecipient, "Amavis no recipient");
+		assert_print_eq(ParseError::AmavisBadHits, "Amavis hits is not a float");
+		assert_print_eq(ParseError::AmavisBadSize, "Amavis size is not an integer");
+		assert_print_eq(ParseError::AmavisBadElapsed, "Amavis elapsed time is not an integer");
+		assert_print_eq(ParseError::OpendkimBadSignature, "Opendkim bad signature");
+		assert_print_eq(ParseError::OpendmarcUnknownResult, "Opendmarc unknown result");
+		assert_print_eq(ParseError::DovecotBadUser, "Dovecot bad user");
+		assert_print_eq(ParseError::DovecotBadMessageID, "Dovecot bad message id");
+		assert_print_eq(ParseError::DovecotNoRemoteIP, "Dovecot no remote ip");
+		assert_print_eq(ParseError::SpamdBadResult, "Spamd bad result");
+		assert_print_eq(ParseError::SpamdBadScore, "Spamd score is not a float");
+		assert_print_eq(ParseError::RspamdBadMetric, "Rspamd bad metric");
+		assert_print_eq(ParseError::RspamdUnknownAction, "Rspamd unknown action");
+		assert_print_eq(ParseError::UnknownInstance, "Unknown postfix instance");
+		assert_print_eq(ParseError::SmtpdBadClient, "Smtpd non ending client");
+	}
+
+	#[test]
+	fn config_formatting() {
+		assert_eq!(fmt::format(format_args!("{}", ConfigError::BadLine(3))), "Config line 3: not a section nor a key");
+		assert_eq!(fmt::format(format_args!("{:?}", ConfigError::UnknownSection(1))), "Config line 1: unknown section");
+		assert_eq!(fmt::format(format_args!("{}", ConfigError::UnknownKey(2))), "Config line 2: unknown key");
+		assert_eq!(fmt::format(format_args!("{}", ConfigError::BadValue(4))), "Config line 4: bad value");
+		assert_eq!(fmt::format(format_args!("{}", ConfigError::BadRegex(5))), "Config line 5: bad regex");
+		assert_eq!(fmt::format(format_args!("{}", ConfigError::NoiseWithoutMatch(6))), "Config line 6: noise rule without identifier, process or regex");
 	}	
 }
@@ -0,0 +1,271 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::io::BufRead;
+use super::calendar::Calendar;
+use super::config::ParserConfig;
+use super::errors::MergeError;
+use super::messages::Message;
+use super::parse::parse_line;
+
+// A message and the log it was read from
+#[derive(Debug)]
+pub struct SourcedMessage {
+	pub source: String,
+	// Seconds since the UNIX epoch, see Inner::epoch
+	pub time: i64,
+	pub message: Message,
+}
+
+struct Source<'a> {
+	name: String,
+	reader: Box<dyn BufRead + 'a>,
+	calendar: Calendar,
+	// Latest time read
+	high: Option<i64>,
+	done: bool,
+}
+
+struct Pending {
+	time: i64,
+	// Read order, for a stable sort
+	seq: u64,
+	message: SourcedMessage,
+}
+
+// BinaryHeap is a max-heap: the oldest message is the greatest
+impl Ord for Pending {
+	fn cmp(&self, other: &Pending) -> Ordering {
+		(other.time, other.seq).cmp(&(self.time, self.seq))
+	}
+}
+
+impl PartialOrd for Pending {
+	fn partial_cmp(&self, other: &Pending) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl PartialEq for Pending {
+	fn eq(&self, other: &Pending) -> bool {
+		self.time == other.time && self.seq == other.seq
+	}
+}
+
+impl Eq for Pending {}
+
+// Merges several logs, from different hosts or rotated files, into one
+// stream ordered by timestamp. Within a log, lines may be out of order by
+// up to window seconds. Years and UTC offsets come from the ParserConfig,
+// 1970 and UTC when unset.
+pub struct MergeReader<'a> {
+	conf: &'a ParserConfig,
+	window: i64,
+	sources: Vec<Source<'a>>,
+	pending: BinaryHeap<Pending>,
+	seq: u64,
+	lines: u64,
+	ignored: u64,
+	errors: u64,
+}
+
+impl<'a> MergeReader<'a> {
+	pub fn new(conf: &'a ParserConfig, window: u32) -> MergeReader<'a> {
+		MergeReader { conf: conf, window: window as i64, sources: Vec::new(), pending: BinaryHeap::new(),
+		              seq: 0, lines: 0, ignored: 0, errors: 0 }
+	}
+
+	pub fn add<R: BufRead + 'a>(&mut self, name: &str, reader: R) {
+		let timezone = self.conf.timezone.unwrap_or(0);
+		self.add_with_timezone(name, reader, timezone);
+	}
+
+	// For a log written with another UTC offset than the configured one
+	pub fn add_with_timezone<R: BufRead + 'a>(&mut self, name: &str, reader: R, timezone: i32) {
+		let calendar = Calendar::new(self.conf.year.unwrap_or(1970), timezone);
+		self.add_with_calendar(name, reader, calendar);
+	}
+
+	// For a log dated otherwise, see Calendar::from_file
+	pub fn add_with_calendar<R: BufRead + 'a>(&mut self, name: &str, reader: R, calendar: Calendar) {
+		self.sources.push(Source { name: name.to_string(), reader: Box::new(reader), calendar: calendar, high: None, done: false });
+	}
+
+	// Lines read, including ignored ones and errors
+	pub fn lines(&self) -> u64 {
+		self.lines
+	}
+
+	pub fn ignored(&self) -> u64 {
+		self.ignored
+	}
+
+	// Lines that failed to parse with a lenient ParserConfig
+	pub fn errors(&self) -> u64 {
+		self.errors
+	}
+
+	// Reads the next line of a source, queueing its message if any
+	fn read(&mut self, index: usize) -> Result<(), MergeError> {
+		let conf = self.conf;
+		let source = &mut self.sources[index];
+		let mut buffer: Vec<u8> = Vec::new();
+		let len = match source.reader.read_until(b'\n', &mut buffer) {
+			Err(x) => {
+				source.done = true;
+				return Err(MergeError::Io(source.name.clone(), x));
+			},
+			Ok(len) => len
+		};
+		if len == 0 {
+			source.done = true;
+			return Ok(());
+		}
+		self.lines += 1;
+		let end = if buffer[len - 1] == b'\n' { len - 1 } else { len };
+		let line = String::from_utf8_lossy(&buffer[..end]).into_owned();
+		let message = match parse_line(line.clone(), conf) {
+			Err(x) => {
+				if conf.strict {
+					return Err(MergeError::Parse(source.name.clone(), x, line));
+				}
+				self.errors += 1;
+				return Ok(());
+			},
+			Ok(None) => {
+				self.ignored += 1;
+				return Ok(());
+			},
+			Ok(Some(message)) => message
+		};
+		// Undated lines stay next to the previous one
+		let time = match source.calendar.epoch(message.inner()) {
+			None => source.high.unwrap_or(0),
+			Some(time) => time
+		};
+		if source.high.map_or(true, |high| time > high) {
+			source.high = Some(time);
+		}
+		self.seq += 1;
+		self.pending.push(Pending { time: time, seq: self.seq,
+		                            message: SourcedMessage { source: source.name.clone(), time: time, message: message } });
+		Ok(())
+	}
+}
+
+impl<'a> Iterator for MergeReader<'a> {
+	type Item = Result<SourcedMessage, MergeError>;
+
+	fn next(&mut self) -> Option<Result<SourcedMessage, MergeError>> {
+		loop {
+			// The source holding back the oldest pending message: every line
+			// still to come is at most window seconds older than its latest
+			let lagging = self.sources.iter().enumerate().filter(|&(_, s)| !s.done)
+				.min_by_key(|&(_, s)| s.high.unwrap_or(i64::min_value()))
+				.map(|(i, s)| (i, s.high));
+			let ready = match (self.pending.peek(), lagging) {
+				(None, None) => return None,
+				(Some(_), None) => true,
+				(Some(oldest), Some((_, Some(high)))) => oldest.time <= high - self.window,
+				(_, Some(_)) => false,
+			};
+			if ready {
+				return self.pending.pop().map(|p| Ok(p.message));
+			}
+			if let Err(x) = self.read(lagging.unwrap().0) {
+				return Some(Err(x));
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::io::Cursor;
+
+	fn merge(conf: &ParserConfig, window: u32, sources: &[(&str, &str)]) -> Vec<(String, i64, String)> {
+		let mut reader = MergeReader::new(conf, window);
+		for &(name, log) in sources {
+			reader.add(name, Cursor::new(log.as_bytes()));
+		}
+		reader.map(|m| match m {
+			Err(x) => panic!("Merge error: {}", x),
+			Ok(m) => (m.source, m.time, m.message.inner().queue_id().unwrap().to_string())
+		}).collect()
+	}
+
+	#[test]
+	fn hosts() {
+		let mut conf = ParserConfig::new(vec![]);
+		conf.year = Some(2015);
+		conf.timezone = Some(7200);
+		let merged = merge(&conf, 0, &[
+			("mx1", "Aug  4 00:00:01 mx1 postfix/cleanup[1235]: 84ED020916: message-id=<a@example.com>\n\
+			         Aug  4 00:00:03 mx1 postfix/cleanup[1235]: 0345620AE4: message-id=<b@example.com>\n"),
+			("mx2", "Aug  4 00:00:02 mx2 postfix/cleanup[1235]: C217620B0B: message-id=<c@example.com>\n\
+			         Aug  4 00:00:03 mx2 postfix/cleanup[1235]: 67D8720887: message-id=<d@example.com>"),
+		]);
+		assert_eq!(merged.iter().map(|m| (m.0.as_str(), m.2.as_str())).collect::<Vec<_>>(),
+		           vec![("mx1", "84ED020916"), ("mx2", "C217620B0B"), ("mx1", "0345620AE4"), ("mx2", "67D8720887")]);
+		// 2015-08-03T22:00:01Z
+		assert_eq!(merged[0].1, 1438639201);
+	}
+
+	#[test]
+	fn timezones() {
+		let conf = ParserConfig::new(vec![]);
+		let mut reader = MergeReader::new(&conf, 0);
+		reader.add_with_timezone("mx1", Cursor::new(&b"Aug  4 02:00:03 mx1 postfix/cleanup[1235]: 84ED020916: message-id=<a@example.com>\n"[..]), 7200);
+		reader.add("mx2", Cursor::new(&b"Aug  4 00:00:02 mx2 postfix/cleanup[1235]: C217620B0B: message-id=<c@example.com>\n"[..]));
+		let merged: Vec<(String, i64)> = reader.map(|m| m.map(|m| (m.source, m.time)).unwrap()).collect();
+		assert_eq!(merged, vec![("mx2".to_string(), 215 * 24 * 3600 + 2), ("mx1".to_string(), 215 * 24 * 3600 + 3)]);
+	}
+
+	#[test]
+	fn window() {
+		let conf = ParserConfig::new(vec![]);
+		let log = "Aug  4 00:00:05 yuuai postfix/cleanup[1235]: 84ED020916: message-id=<a@example.com>\n\
+		           Aug  4 00:00:02 yuuai postfix/cleanup[1235]: 0345620AE4: message-id=<b@example.com>\n\
+		           Aug  4 00:00:09 yuuai postfix/cleanup[1235]: C217620B0B: message-id=<c@example.com>\n";
+		let order = |window| merge(&conf, window, &[("log", log)]).into_iter().map(|m| m.2).collect::<Vec<_>>();
+		assert_eq!(order(0), vec!["84ED020916", "0345620AE4", "C217620B0B"]);
+		assert_eq!(order(3), vec!["0345620AE4", "84ED020916", "C217620B0B"]);
+	}
+
+	#[test]
+	fn new_year() {
+		let conf = ParserConfig::new(vec![]);
+		let merged = merge(&conf, 60, &[
+			("mail.log.1", "Dec 31 23:59:59 yuuai postfix/cleanup[1235]: 84ED020916: message-id=<a@example.com>\n"),
+			("mail.log", "Dec 31 23:59:58 yuuai postfix/cleanup[1235]: 0345620AE4: message-id=<b@example.com>\n\
+			              Jan  1 00:00:01 yuuai postfix/cleanup[1235]: C217620B0B: message-id=<c@example.com>\n\
+			              Dec 31 23:59:57 yuuai postfix/cleanup[1235]: 67D8720887: message-id=<d@example.com>\n\
+			              Jan  1 00:00:02 yuuai postfix/cleanup[1235]: 60F6120AF9: message-id=<e@example.com>\n"),
+		]);
+		assert_eq!(merged.iter().map(|m| m.2.as_str()).collect::<Vec<_>>(),
+		           vec!["67D8720887", "0345620AE4", "84ED020916", "C217620B0B", "60F6120AF9"]);
+		assert_eq!(merged[3].1, 365 * 24 * 3600 + 1);
+		assert_eq!(merged[4].1, 365 * 24 * 3600 + 2);
+	}
+
+	#[test]
+	fn errors() {
+		let log = "Aug  4 00:00:01 yuuai postfix/cleanup[1235]: 84ED020916: message-id=<a@example.com>\n\
+		           Aug  4 00:00:02 yuuai postfix/smtpd[1234]: 0345620AE4: hello\n\
+		           Aug  4 00:00:03 yuuai postfix/anvil[1236]: statistics: max connection rate 1/60s\n";
+		let conf = ParserConfig::new(vec![]);
+		let mut reader = MergeReader::new(&conf, 0);
+		reader.add("log", Cursor::new(log.as_bytes()));
+		assert!(reader.next().unwrap().is_ok());
+		match reader.next() {
+			Some(Err(MergeError::Parse(ref source, _, _))) => assert_eq!(source, "log"),
+			_ => panic!("Expected a parse error"),
+		}
+		let mut conf = ParserConfig::new(vec![]);
+		conf.strict = false;
+		let mut reader = MergeReader::new(&conf, 0);
+		reader.add("log", Cursor::new(log.as_bytes()));
+		assert_eq!(reader.by_ref().count(), 1);
+		assert_eq!((reader.lines(), reader.ignored(), reader.errors()), (3, 1, 1));
+	}
+}
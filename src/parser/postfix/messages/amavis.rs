@@ -0,0 +1,425 @@
+use std::fmt;
+use std::ops::Deref;
+use super::super::ParseError;
+use super::Inner;
+use super::Message;
+use super::MessageParser;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum AmavisAction {
+	Passed,
+	Blocked,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum AmavisVerdict {
+	Clean,
+	Spammy,
+	Spam,
+	Infected,
+	Banned,
+	BadHeader,
+	Unchecked,
+	Oversized,
+	MtaBlocked,
+	Other,
+}
+
+#[derive(Debug)]
+pub struct Amavis {
+	inner: Inner,
+	log_id_s: usize,
+	log_id_e: usize,
+	pub action: AmavisAction,
+	pub verdict: AmavisVerdict,
+	detail_s: usize,
+	detail_e: usize,
+	disposition_s: usize,
+	disposition_e: usize,
+	client_s: usize,
+	client_e: usize,
+	from_s: usize,
+	from_e: usize,
+	to: Vec<(usize, usize)>,
+	orig_queue_id_s: usize,
+	orig_queue_id_e: usize,
+	message_id_s: usize,
+	message_id_e: usize,
+	mail_id_s: usize,
+	mail_id_e: usize,
+	pub hits: Option<f64>,
+	pub size: Option<u64>,
+	queued_as_s: usize,
+	queued_as_e: usize,
+	pub elapsed: Option<u64>,
+}
+
+impl Deref for Amavis {
+	type Target = Inner;
+	fn deref(&self) -> &Inner {
+		&self.inner
+	}
+}
+
+impl fmt::Display for AmavisAction {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		let action = match self {
+			&AmavisAction::Passed => "Passed",
+			&AmavisAction::Blocked => "Blocked",
+		};
+		write!(fmt, "{}", action)
+	}
+}
+
+impl fmt::Debug for AmavisAction {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		fmt::Display::fmt(self, fmt)
+	}
+}
+
+impl fmt::Display for AmavisVerdict {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		let verdict = match self {
+			&AmavisVerdict::Clean => "CLEAN",
+			&AmavisVerdict::Spammy => "SPAMMY",
+			&AmavisVerdict::Spam => "SPAM",
+			&AmavisVerdict::Infected => "INFECTED",
+			&AmavisVerdict::Banned => "BANNED",
+			&AmavisVerdict::BadHeader => "BAD-HEADER",
+			&AmavisVerdict::Unchecked => "UNCHECKED",
+			&AmavisVerdict::Oversized => "OVERSIZED",
+			&AmavisVerdict::MtaBlocked => "MTA-BLOCKED",
+			&AmavisVerdict::Other => "OTHER",
+		};
+		write!(fmt, "{}", verdict)
+	}
+}
+
+impl fmt::Debug for AmavisVerdict {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		fmt::Display::fmt(self, fmt)
+	}
+}
+
+impl Amavis {
+	fn optional <'a>(&'a self, s: usize, e: usize) -> Option<&'a str> {
+		if e != 0 {
+			Some(&self.raw[s..e])
+		} else {
+			None
+		}
+	}
+
+	pub fn log_id <'a>(&'a self) -> &'a str {
+		&self.raw[self.log_id_s..self.log_id_e]
+	}
+
+	// Virus names for INFECTED, banned part description for BANNED
+	pub fn detail <'a>(&'a self) -> Option<&'a str> {
+		self.optional(self.detail_s, self.detail_e)
+	}
+
+	pub fn disposition <'a>(&'a self) -> Option<&'a str> {
+		self.optional(self.disposition_s, self.disposition_e)
+	}
+
+	pub fn client <'a>(&'a self) -> Option<&'a str> {
+		self.optional(self.client_s, self.client_e)
+	}
+
+	pub fn from <'a>(&'a self) -> &'a str {
+		&self.raw[self.from_s..self.from_e]
+	}
+
+	pub fn to <'a>(&'a self) -> Vec<&'a str> {
+		self.to.iter().map(|&(s, e)| &self.raw[s..e]).collect()
+	}
+
+	// Queue ID of the mail in the postfix instance feeding amavis
+	pub fn orig_queue_id <'a>(&'a self) -> Option<&'a str> {
+		self.optional(self.orig_queue_id_s, self.orig_queue_id_e)
+	}
+
+	pub fn message_id <'a>(&'a self) -> Option<&'a str> {
+		self.optional(self.message_id_s, self.message_id_e)
+	}
+
+	pub fn mail_id <'a>(&'a self) -> Option<&'a str> {
+		self.optional(self.mail_id_s, self.mail_id_e)
+	}
+
+	// Queue ID of the mail in the postfix instance amavis reinjected it into
+	pub fn queued_as <'a>(&'a self) -> Option<&'a str> {
+		self.optional(self.queued_as_s, self.queued_as_e)
+	}
+}
+
+impl MessageParser for Amavis {
+	fn parse(inner: Inner, start: usize) -> Result<Option<Message>, ParseError> {
+		let (log_id_s, log_id_e, action, verdict, detail_s, detail_e, disposition_s, disposition_e, client_s, client_e, from_s, from_e, to, fields_s) = {
+			let raw = &inner.raw;
+			if !raw[start..].starts_with(" (") {
+				return Ok(None);
+			}
+			let log_id_s = start + 2;
+			let log_id_e = match raw[log_id_s..].find(") ") {
+				None => return Ok(None),
+				Some(p) => log_id_s + p
+			};
+			let rest = &raw[log_id_e + 2..];
+			let (action, action_len) = if rest.starts_with("Passed ") {
+				(AmavisAction::Passed, 7)
+			} else if rest.starts_with("Blocked ") {
+				(AmavisAction::Blocked, 8)
+			} else {
+				return Ok(None);
+			};
+			let verdict_s = log_id_e + 2 + action_len;
+			let verdict_e = match raw[verdict_s..].find(|c| c == ' ' || c == ',') {
+				None => return Err(ParseError::AmavisNoSender),
+				Some(p) => verdict_s + p
+			};
+			let verdict = match &raw[verdict_s..verdict_e] {
+				"CLEAN" => AmavisVerdict::Clean,
+				"SPAMMY" => AmavisVerdict::Spammy,
+				"SPAM" => AmavisVerdict::Spam,
+				"INFECTED" => AmavisVerdict::Infected,
+				"BANNED" => AmavisVerdict::Banned,
+				"BAD-HEADER" => AmavisVerdict::BadHeader,
+				"OVERSIZED" => AmavisVerdict::Oversized,
+				"MTA-BLOCKED" => AmavisVerdict::MtaBlocked,
+				"OTHER" => AmavisVerdict::Other,
+				x if x.starts_with("UNCHECKED") => AmavisVerdict::Unchecked,
+				_ => return Err(ParseError::AmavisUnknownVerdict),
+			};
+			let mut pos = verdict_e;
+			let (mut detail_s, mut detail_e) = (0, 0);
+			if raw[pos..].starts_with(" (") {
+				if let Some(p) = raw[pos..].find(|c| c == '{' || c == '<').and_then(|p| raw[pos..pos + p].rfind(')')) {
+					detail_s = pos + 2;
+					detail_e = pos + p;
+					pos += p + 1;
+				}
+			}
+			let (mut disposition_s, mut disposition_e) = (0, 0);
+			if raw[pos..].starts_with(" {") {
+				if let Some(p) = raw[pos..].find('}') {
+					disposition_s = pos + 2;
+					disposition_e = pos + p;
+					pos += p + 1;
+				}
+			}
+			let from_s = match raw[pos..].find('<') {
+				None => return Err(ParseError::AmavisNoSender),
+				Some(p) => pos + p + 1
+			};
+			let (mut client_s, mut client_e) = (0, 0);
+			if let Some(p) = raw[pos..from_s].find('[') {
+				if let Some(l) = raw[pos + p..from_s].find(']') {
+					client_s = pos + p + 1;
+					client_e = pos + p + l;
+				}
+			}
+			let from_e = match raw[from_s..].find("> -> <") {
+				None => return Err(ParseError::AmavisNoRecipient),
+				Some(p) => from_s + p
+			};
+			let mut to = Vec::new();
+			pos = from_e + 6;
+			loop {
+				let to_e = match raw[pos..].find('>') {
+					None => return Err(ParseError::AmavisNoRecipient),
+					Some(p) => pos + p
+				};
+				to.push((pos, to_e));
+				if raw[to_e..].starts_with(">,<") {
+					pos = to_e + 3;
+				} else {
+					pos = to_e + 1;
+					break;
+				}
+			}
+			(log_id_s, log_id_e, action, verdict, detail_s, detail_e, disposition_s, disposition_e, client_s, client_e, from_s, from_e, to, pos)
+		};
+		let mut amavis = Amavis { inner: inner, log_id_s: log_id_s, log_id_e: log_id_e, action: action, verdict: verdict, detail_s: detail_s, detail_e: detail_e, disposition_s: disposition_s, disposition_e: disposition_e, client_s: client_s, client_e: client_e, from_s: from_s, from_e: from_e, to: to, orig_queue_id_s: 0, orig_queue_id_e: 0, message_id_s: 0, message_id_e: 0, mail_id_s: 0, mail_id_e: 0, hits: None, size: None, queued_as_s: 0, queued_as_e: 0, elapsed: None };
+		{
+			let raw = &amavis.inner.raw;
+			let mut pos = fields_s;
+			while raw[pos..].starts_with(", ") {
+				let field_s = pos + 2;
+				let field_e = match raw[field_s..].find(", ") {
+					None => raw.len(),
+					Some(p) => field_s + p
+				};
+				let field = &raw[field_s..field_e];
+				if field.starts_with("Queue-ID: ") {
+					amavis.orig_queue_id_s = field_s + 10;
+					amavis.orig_queue_id_e = field_e;
+				} else if field.starts_with("Message-ID: ") {
+					let bracketed = field.len() > 13 && field[12..].starts_with('<') && field.ends_with('>');
+					amavis.message_id_s = field_s + if bracketed { 13 } else { 12 };
+					amavis.message_id_e = field_e - if bracketed { 1 } else { 0 };
+				} else if field.starts_with("mail_id: ") {
+					amavis.mail_id_s = field_s + 9;
+					amavis.mail_id_e = field_e;
+				} else if field.starts_with("Hits: ") {
+					amavis.hits = match &field[6..] {
+						"-" => None,
+						hits => match hits.parse::<f64>() {
+							Err(_) => return Err(ParseError::AmavisBadHits),
+							Ok(h) => Some(h)
+						}
+					};
+				} else if field.starts_with("size: ") {
+					amavis.size = match field[6..].parse::<u64>() {
+						Err(_) => return Err(ParseError::AmavisBadSize),
+						Ok(s) => Some(s)
+					};
+				} else if field.starts_with("queued_as: ") {
+					amavis.queued_as_s = field_s + 11;
+					amavis.queued_as_e = field_e;
+				} else if field.ends_with(" ms") && field_e == raw.len() {
+					amavis.elapsed = match field[..field.len() - 3].parse::<u64>() {
+						Err(_) => return Err(ParseError::AmavisBadElapsed),
+						Ok(e) => Some(e)
+					};
+				}
+				pos = field_e;
+			}
+		}
+		Ok(Some(Message::Amavis { m: amavis }))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fmt;
+	use super::*;
+	use super::super::Inner;
+	use super::super::Message;
+	use super::super::MessageParser;
+	use super::super::super::ParserConfig;
+	use super::super::super::ParseError;
+
+	fn parse_amavis(s: String) -> Result<Option<Message>, ParseError> {
+		let conf = ParserConfig::new(vec!["clamsmtpd".to_string()]);
+		let (inner, start) = match Inner::parse(&conf, s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some((x,y))) => (x,y)
+		};
+		Amavis::parse(inner, start)
+	}
+
+	#[test]
+	fn ignored() {
+		let s = "Aug  4 00:00:08 yuuai amavis[2841]: (02841-01) ESMTP :10024 /var/lib/amavis/tmp/amavis-20160804T000008-02841-abcdefgh: <aaa@bbb.ccc> -> <xxx@yyy.zzz> SIZE=1234 Received: from mx1 ([127.0.0.1])".to_string();
+		match parse_amavis(s) {
+			Err(x) => panic!("Failed to parse {}", x),
+			Ok(None) => (),
+			Ok(_) => panic!("This should have been ignored"),
+		};
+		let s = "Aug  4 00:00:08 yuuai amavis[2841]: starting. /usr/sbin/amavisd-new at yuuai amavisd-new-2.10.1".to_string();
+		match parse_amavis(s) {
+			Err(x) => panic!("Failed to parse {}", x),
+			Ok(None) => (),
+			Ok(_) => panic!("This should have been ignored"),
+		};
+	}
+
+	#[test]
+	fn errors() {
+		let s = "Aug  4 00:00:08 yuuai amavis[2841]: (02841-01) Passed MAYBE {RelayedInbound}, [1.2.3.4]:5678 <aaa@bbb.ccc> -> <xxx@yyy.zzz>".to_string();
+		match parse_amavis(s) {
+			Err(ParseError::AmavisUnknownVerdict) => (),
+			Err(x) => panic!("Wrong error, should have been AmavisUnknownVerdict {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(_) => panic!("This should have failed")
+		};
+		let s = "Aug  4 00:00:08 yuuai amavis[2841]: (02841-01) Passed CLEAN {RelayedInbound}, [1.2.3.4]:5678".to_string();
+		match parse_amavis(s) {
+			Err(ParseError::AmavisNoSender) => (),
+			Err(x) => panic!("Wrong error, should have been AmavisNoSender {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(_) => panic!("This should have failed")
+		};
+		let s = "Aug  4 00:00:08 yuuai amavis[2841]: (02841-01) Passed CLEAN {RelayedInbound}, [1.2.3.4]:5678 <aaa@bbb.ccc>, Queue-ID: 84ED020916".to_string();
+		match parse_amavis(s) {
+			Err(ParseError::AmavisNoRecipient) => (),
+			Err(x) => panic!("Wrong error, should have been AmavisNoRecipient {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(_) => panic!("This should have failed")
+		};
+		let s = "Aug  4 00:00:08 yuuai amavis[2841]: (02841-01) Passed CLEAN {RelayedInbound}, [1.2.3.4]:5678 <aaa@bbb.ccc> -> <xxx@yyy.zzz>, Hits: lots".to_string();
+		match parse_amavis(s) {
+			Err(ParseError::AmavisBadHits) => (),
+			Err(x) => panic!("Wrong error, should have been AmavisBadHits {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(_) => panic!("This should have failed")
+		};
+		let s = "Aug  4 00:00:08 yuuai amavis[2841]: (02841-01) Passed CLEAN {RelayedInbound}, [1.2.3.4]:5678 <aaa@bbb.ccc> -> <xxx@yyy.zzz>, size: big".to_string();
+		match parse_amavis(s) {
+			Err(ParseError::AmavisBadSize) => (),
+			Err(x) => panic!("Wrong error, should have been AmavisBadSize {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(_) => panic!("This should have failed")
+		};
+		let s = "Aug  4 00:00:08 yuuai amavis[2841]: (02841-01) Passed CLEAN {RelayedInbound}, [1.2.3.4]:5678 <aaa@bbb.ccc> -> <xxx@yyy.zzz>, size: 1234, slow ms".to_string();
+		match parse_amavis(s) {
+			Err(ParseError::AmavisBadElapsed) => (),
+			Err(x) => panic!("Wrong error, should have been AmavisBadElapsed {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(_) => panic!("This should have failed")
+		};
+	}
+
+	#[test]
+	fn valid() {
+		let s = "Aug  4 00:00:08 yuuai amavis[2841]: (02841-01) Passed CLEAN {RelayedInbound}, [1.2.3.4]:5678 <aaa@bbb.ccc> -> <xxx@yyy.zzz>, Queue-ID: 84ED020916, Message-ID: <abc@bbb.ccc>, mail_id: Xc2Ja1Bc, Hits: -1.2, size: 1234, queued_as: 67D8720887, 1234 ms".to_string();
+		let amavis = match parse_amavis(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::Amavis{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		assert_eq!(amavis.queue_id(), None);
+		assert_eq!(amavis.log_id(), "02841-01");
+		assert_eq!(amavis.action, AmavisAction::Passed);
+		assert_eq!(amavis.verdict, AmavisVerdict::Clean);
+		assert_eq!(amavis.detail(), None);
+		assert_eq!(amavis.disposition(), Some("RelayedInbound"));
+		assert_eq!(amavis.client(), Some("1.2.3.4"));
+		assert_eq!(amavis.from(), "aaa@bbb.ccc");
+		assert_eq!(amavis.to(), vec!["xxx@yyy.zzz"]);
+		assert_eq!(amavis.orig_queue_id(), Some("84ED020916"));
+		assert_eq!(amavis.message_id(), Some("abc@bbb.ccc"));
+		assert_eq!(amavis.mail_id(), Some("Xc2Ja1Bc"));
+		assert_eq!(amavis.hits, Some(-1.2));
+		assert_eq!(amavis.size, Some(1234));
+		assert_eq!(amavis.queued_as(), Some("67D8720887"));
+		assert_eq!(amavis.elapsed, Some(1234));
+		assert_eq!(fmt::format(format_args!("{:?}", amavis)), "Amavis { inner: Inner { raw: \"Aug  4 00:00:08 yuuai amavis[2841]: (02841-01) Passed CLEAN {RelayedInbound}, [1.2.3.4]:5678 <aaa@bbb.ccc> -> <xxx@yyy.zzz>, Queue-ID: 84ED020916, Message-ID: <abc@bbb.ccc>, mail_id: Xc2Ja1Bc, Hits: -1.2, size: 1234, queued_as: 67D8720887, 1234 ms\", host_e: 21, queue_s: 22, queue_e: 22, process: Amavis, pid: 2841, queue_id_s: 0, queue_id_e: 0 }, log_id_s: 37, log_id_e: 45, action: Passed, verdict: CLEAN, detail_s: 0, detail_e: 0, disposition_s: 61, disposition_e: 75, client_s: 79, client_e: 86, from_s: 94, from_e: 105, to: [(111, 122)], orig_queue_id_s: 135, orig_queue_id_e: 145, message_id_s: 160, message_id_e: 171, mail_id_s: 183, mail_id_e: 191, hits: Some(-1.2), size: Some(1234), queued_as_s: 228, queued_as_e: 238, elapsed: Some(1234) }");
+	}
+
+	#[test]
+	fn valid_blocked() {
+		let s = "Aug  4 00:00:08 yuuai amavis[2841]: (02841-02) Blocked INFECTED (Eicar-Test-Signature) {DiscardedInbound,Quarantined}, LOCAL [1.2.3.4]:5678 [5.6.7.8] <> -> <xxx@yyy.zzz>,<ttt@yyy.zzz>, quarantine: virus-Xc2Ja1Bc, Queue-ID: 84ED020916, Message-ID: <abc@bbb.ccc>, mail_id: Xc2Ja1Bc, Hits: -, size: 1234, 57 ms".to_string();
+		let amavis = match parse_amavis(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::Amavis{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		assert_eq!(amavis.action, AmavisAction::Blocked);
+		assert_eq!(amavis.verdict, AmavisVerdict::Infected);
+		assert_eq!(amavis.detail(), Some("Eicar-Test-Signature"));
+		assert_eq!(amavis.disposition(), Some("DiscardedInbound,Quarantined"));
+		assert_eq!(amavis.client(), Some("1.2.3.4"));
+		assert_eq!(amavis.from(), "");
+		assert_eq!(amavis.to(), vec!["xxx@yyy.zzz", "ttt@yyy.zzz"]);
+		assert_eq!(amavis.orig_queue_id(), Some("84ED020916"));
+		assert_eq!(amavis.hits, None);
+		assert_eq!(amavis.queued_as(), None);
+		assert_eq!(amavis.elapsed, Some(57));
+	}
+}
@@ -0,0 +1,397 @@
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::fmt;
+use std::ops::Deref;
+use super::super::ParseError;
+use super::Inner;
+use super::Message;
+use super::MessageParser;
+use super::Process;
+use super::SpamAction;
+use super::SpamVerdict;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum BogofilterVerdict {
+	Spam,
+	Ham,
+	Unsure,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum BogofilterRegistration {
+	Spam,
+	Ham,
+	UnregisterSpam,
+	UnregisterHam,
+}
+
+#[derive(Debug)]
+pub struct Bogofilter {
+	inner: Inner,
+	pub verdict: Option<BogofilterVerdict>,
+	pub spamicity: Option<f64>,
+	version_s: usize,
+	version_e: usize,
+	pub registration: Option<BogofilterRegistration>,
+	pub words: Option<u32>,
+	pub messages: Option<u32>,
+}
+
+#[derive(Clone, Debug)]
+pub struct BogofilterLink {
+	pub queue_id: String,
+	pub verdict: BogofilterVerdict,
+	pub spamicity: Option<f64>,
+}
+
+// Verdicts waiting for their delivery, per host
+const MAX_PENDING: usize = 1000;
+
+// bogofilter does not know about postfix queue IDs and logs nothing shared
+// with the pipe delivery running it: its verdicts are matched, in order and
+// per host, with the pipe deliveries to the bogofilter service logged at most
+// timeout seconds later. Older verdicts are dropped, as well as the oldest
+// ones past MAX_PENDING. A pipe delivery to several recipients runs
+// bogofilter once, so its next recipients reuse the same verdict.
+pub struct BogofilterLinker {
+	service: String,
+	timeout: u32,
+	pending: HashMap<String, VecDeque<(u32, BogofilterVerdict, Option<f64>)>>,
+	// Last link of each host, with its time
+	linked: HashMap<String, (u32, BogofilterLink)>,
+}
+
+impl Deref for Bogofilter {
+	type Target = Inner;
+	fn deref(&self) -> &Inner {
+		&self.inner
+	}
+}
+
+impl fmt::Display for BogofilterVerdict {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		let verdict = match self {
+			&BogofilterVerdict::Spam => "Spam",
+			&BogofilterVerdict::Ham => "Ham",
+			&BogofilterVerdict::Unsure => "Unsure",
+		};
+		write!(fmt, "{}", verdict)
+	}
+}
+
+impl fmt::Debug for BogofilterVerdict {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		fmt::Display::fmt(self, fmt)
+	}
+}
+
+impl fmt::Display for BogofilterRegistration {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		let registration = match self {
+			&BogofilterRegistration::Spam => "register-s",
+			&BogofilterRegistration::Ham => "register-n",
+			&BogofilterRegistration::UnregisterSpam => "register-S",
+			&BogofilterRegistration::UnregisterHam => "register-N",
+		};
+		write!(fmt, "{}", registration)
+	}
+}
+
+impl fmt::Debug for BogofilterRegistration {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		fmt::Display::fmt(self, fmt)
+	}
+}
+
+impl Bogofilter {
+	pub fn version <'a>(&'a self) -> Option<&'a str> {
+		if self.version_e != 0 {
+			Some(&self.raw[self.version_s..self.version_e])
+		} else {
+			None
+		}
+	}
+
+	// bogofilter does not log its spam_cutoff, so the threshold is unknown
+	pub fn spam_verdict(&self) -> Option<SpamVerdict> {
+		let action = match self.verdict {
+			None => return None,
+			Some(BogofilterVerdict::Spam) => SpamAction::Spam,
+			Some(BogofilterVerdict::Ham) => SpamAction::Ham,
+			Some(BogofilterVerdict::Unsure) => SpamAction::Unsure,
+		};
+		Some(SpamVerdict { action: action, score: self.spamicity, threshold: None, symbols: Vec::new() })
+	}
+}
+
+impl BogofilterLinker {
+	pub fn new(service: &str, timeout: u32) -> BogofilterLinker {
+		BogofilterLinker { service: service.to_string(), timeout: timeout, pending: HashMap::new(), linked: HashMap::new() }
+	}
+
+	pub fn add(&mut self, message: &Message) -> Option<BogofilterLink> {
+		let timeout = self.timeout as i64;
+		match message {
+			&Message::Bogofilter { m: ref bogofilter } => {
+				let (verdict, time) = match (bogofilter.verdict, bogofilter.timestamp()) {
+					(Some(verdict), Some(time)) => (verdict, time),
+					_ => return None
+				};
+				let pending = self.pending.entry(bogofilter.host().to_string()).or_insert_with(VecDeque::new);
+				while pending.front().map_or(false, |p| bogofilter.elapsed(p.0).unwrap() > timeout) || pending.len() >= MAX_PENDING {
+					pending.pop_front();
+				}
+				pending.push_back((time, verdict, bogofilter.spamicity));
+				None
+			},
+			&Message::Forward { m: ref forward } => {
+				if forward.process != Process::Pipe || forward.relay() != self.service {
+					return None;
+				}
+				let (queue_id, time) = match (forward.queue_id(), forward.timestamp()) {
+					(Some(q), Some(t)) => (q, t),
+					_ => return None
+				};
+				if let Some(&(linked, ref link)) = self.linked.get(forward.host()) {
+					if link.queue_id == queue_id && forward.elapsed(linked).unwrap() <= timeout {
+						return Some(link.clone());
+					}
+				}
+				let (verdict, spamicity) = match self.pending.get_mut(forward.host()) {
+					None => return None,
+					Some(pending) => loop {
+						match pending.pop_front() {
+							None => return None,
+							Some((t, verdict, spamicity)) => if forward.elapsed(t).unwrap() <= timeout {
+								break (verdict, spamicity);
+							}
+						}
+					}
+				};
+				let link = BogofilterLink { queue_id: queue_id.to_string(), verdict: verdict, spamicity: spamicity };
+				self.linked.insert(forward.host().to_string(), (time, link.clone()));
+				Some(link)
+			},
+			_ => None
+		}
+	}
+}
+
+impl MessageParser for Bogofilter {
+	fn parse(inner: Inner, start: usize) -> Result<Option<Message>, ParseError> {
+		let (verdict, spamicity, version_s, version_e, registration, words, messages) = {
+			let rest = &inner.raw[start..];
+			let (verdict, mut pos) = {
+				if rest.starts_with(" X-Bogosity: ") {
+					let len = match rest[13..].find(',') {
+						None => rest.len() - 13,
+						Some(l) => l
+					};
+					let verdict = match &rest[13..13 + len] {
+						"Spam" => BogofilterVerdict::Spam,
+						"Ham" => BogofilterVerdict::Ham,
+						"Unsure" => BogofilterVerdict::Unsure,
+						_ => return Err(ParseError::BogofilterUnknownVerdict)
+					};
+					(Some(verdict), 13 + len)
+				} else if rest.starts_with(" register-") {
+					(None, 0)
+				} else {
+					return Err(ParseError::BogofilterUnknownFormat);
+				}
+			};
+			let mut spamicity = None;
+			let mut version_s = 0;
+			let mut version_e = 0;
+			let mut registration = None;
+			let mut words = None;
+			let mut messages = None;
+			while pos < rest.len() {
+				let field_s = pos + if pos == 0 { 1 } else { 2 };
+				let field_e = match rest[field_s..].find(", ") {
+					None => rest.len(),
+					Some(l) => field_s + l
+				};
+				let field = &rest[field_s..field_e];
+				if field.starts_with("spamicity=") {
+					spamicity = match field[10..].parse::<f64>() {
+						Err(_) => return Err(ParseError::BogofilterBadSpamicity),
+						Ok(val) => Some(val)
+					};
+				} else if field.starts_with("version=") {
+					version_s = start + field_s + 8;
+					version_e = start + field_e;
+				} else if field.starts_with("register-") {
+					registration = match &field[9..] {
+						"s" => Some(BogofilterRegistration::Spam),
+						"n" => Some(BogofilterRegistration::Ham),
+						"S" => Some(BogofilterRegistration::UnregisterSpam),
+						"N" => Some(BogofilterRegistration::UnregisterHam),
+						_ => return Err(ParseError::BogofilterBadRegistration)
+					};
+				} else if field.ends_with(" words") {
+					words = match field[..field.len() - 6].parse::<u32>() {
+						Err(_) => return Err(ParseError::BogofilterBadRegistration),
+						Ok(val) => Some(val)
+					};
+				} else if field.ends_with(" messages") {
+					messages = match field[..field.len() - 9].parse::<u32>() {
+						Err(_) => return Err(ParseError::BogofilterBadRegistration),
+						Ok(val) => Some(val)
+					};
+				}
+				pos = field_e;
+			}
+			(verdict, spamicity, version_s, version_e, registration, words, messages)
+		};
+		Ok(Some(Message::Bogofilter { m: Bogofilter { inner: inner, verdict: verdict, spamicity: spamicity, version_s: version_s, version_e: version_e, registration: registration, words: words, messages: messages } }))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fmt;
+	use super::*;
+	use super::super::Inner;
+	use super::super::Message;
+	use super::super::MessageParser;
+	use super::super::SpamAction;
+	use super::super::super::ParserConfig;
+	use super::super::super::ParseError;
+
+	fn parse_bogofilter(s: String) -> Result<Option<Message>, ParseError> {
+		let conf = ParserConfig::new(vec!["clamsmtpd".to_string()]);
+		let (inner, start) = match Inner::parse(&conf, s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some((x,y))) => (x,y)
+		};
+		Bogofilter::parse(inner, start)
+	}
+
+	#[test]
+	fn unknown_format() {
+		let s = "Aug  4 00:00:03 yuuai bogofilter[4242]: Can't open file 'wordlist.db'".to_string();
+		match parse_bogofilter(s) {
+			Err(ParseError::BogofilterUnknownFormat) => (),
+			Err(x) => panic!("Wrong error, should have been BogofilterUnknownFormat {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(_) => panic!("This should have failed")
+		};
+	}
+
+	#[test]
+	fn unknown_verdict() {
+		let s = "Aug  4 00:00:03 yuuai bogofilter[4242]: X-Bogosity: Maybe, tests=bogofilter, spamicity=0.500000, version=1.2.4".to_string();
+		match parse_bogofilter(s) {
+			Err(ParseError::BogofilterUnknownVerdict) => (),
+			Err(x) => panic!("Wrong error, should have been BogofilterUnknownVerdict {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(_) => panic!("This should have failed")
+		};
+	}
+
+	#[test]
+	fn bad_spamicity() {
+		let s = "Aug  4 00:00:03 yuuai bogofilter[4242]: X-Bogosity: Spam, tests=bogofilter, spamicity=high, version=1.2.4".to_string();
+		match parse_bogofilter(s) {
+			Err(ParseError::BogofilterBadSpamicity) => (),
+			Err(x) => panic!("Wrong error, should have been BogofilterBadSpamicity {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(_) => panic!("This should have failed")
+		};
+	}
+
+	#[test]
+	fn bad_registration() {
+		let s = "Aug  4 00:00:03 yuuai bogofilter[4242]: register-x, 120 words, 1 messages".to_string();
+		match parse_bogofilter(s) {
+			Err(ParseError::BogofilterBadRegistration) => (),
+			Err(x) => panic!("Wrong error, should have been BogofilterBadRegistration {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(_) => panic!("This should have failed")
+		};
+	}
+
+	#[test]
+	fn valid() {
+		let s = "Aug  4 00:00:03 yuuai bogofilter[4242]: X-Bogosity: Spam, tests=bogofilter, spamicity=0.999953, version=1.2.4".to_string();
+		let bogo = match parse_bogofilter(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::Bogofilter{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		assert_eq!(bogo.verdict, Some(BogofilterVerdict::Spam));
+		assert_eq!(bogo.spamicity, Some(0.999953));
+		assert_eq!(bogo.version(), Some("1.2.4"));
+		assert_eq!(bogo.registration, None);
+		let verdict = bogo.spam_verdict().unwrap();
+		assert_eq!(verdict.action, SpamAction::Spam);
+		assert_eq!(verdict.score, Some(0.999953));
+		assert!(verdict.is_spam());
+		assert_eq!(fmt::format(format_args!("{:?}", bogo)), "Bogofilter { inner: Inner { raw: \"Aug  4 00:00:03 yuuai bogofilter[4242]: X-Bogosity: Spam, tests=bogofilter, spamicity=0.999953, version=1.2.4\", host_e: 21, queue_s: 22, queue_e: 22, process: Bogofilter, pid: 4242, queue_id_s: 0, queue_id_e: 0 }, verdict: Some(Spam), spamicity: Some(0.999953), version_s: 104, version_e: 109, registration: None, words: None, messages: None }");
+	}
+
+	#[test]
+	fn valid_registration() {
+		let s = "Aug  4 00:00:03 yuuai bogofilter[4242]: X-Bogosity: Ham, tests=bogofilter, spamicity=0.000000, version=1.2.4, register-n, 215 words, 1 messages".to_string();
+		let bogo = match parse_bogofilter(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::Bogofilter{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		assert_eq!(bogo.verdict, Some(BogofilterVerdict::Ham));
+		assert_eq!(bogo.spamicity, Some(0.0));
+		assert_eq!(bogo.version(), Some("1.2.4"));
+		assert_eq!(bogo.registration, Some(BogofilterRegistration::Ham));
+		assert_eq!(bogo.words, Some(215));
+		assert_eq!(bogo.messages, Some(1));
+		let s = "Aug  4 00:00:03 yuuai bogofilter[4242]: register-S, 120 words, 1 messages".to_string();
+		let bogo = match parse_bogofilter(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::Bogofilter{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		assert_eq!(bogo.verdict, None);
+		assert_eq!(bogo.spam_verdict(), None);
+		assert_eq!(bogo.version(), None);
+		assert_eq!(bogo.registration, Some(BogofilterRegistration::UnregisterSpam));
+		assert_eq!(bogo.words, Some(120));
+		assert_eq!(bogo.messages, Some(1));
+	}
+
+	#[test]
+	fn linker() {
+		let conf = ParserConfig::new(vec!["clamsmtpd".to_string()]);
+		let mut linker = BogofilterLinker::new("bogofilter", 60);
+		let lines = vec![
+			"Aug  4 00:00:01 yuuai bogofilter[4241]: X-Bogosity: Unsure, tests=bogofilter, spamicity=0.520000, version=1.2.4",
+			"Aug  4 00:02:03 yuuai bogofilter[4242]: X-Bogosity: Spam, tests=bogofilter, spamicity=0.999953, version=1.2.4",
+			"Aug  4 00:02:03 ozgurluk bogofilter[4243]: X-Bogosity: Ham, tests=bogofilter, spamicity=0.000000, version=1.2.4",
+			"Aug  4 00:02:03 yuuai postfix/pipe[4240]: 0345620AE4: to=<xxxx@melix.net>, relay=bogofilter, delay=0.57, delays=0.4/0/0.04/0.13, dsn=2.0.0, status=sent (delivered via bogofilter service)",
+			"Aug  4 00:02:03 yuuai postfix/pipe[4240]: 0345620AE4: to=<zzzz@melix.net>, relay=bogofilter, delay=0.57, delays=0.4/0/0.04/0.13, dsn=2.0.0, status=sent (delivered via bogofilter service)",
+			"Aug  4 00:02:04 yuuai postfix/pipe[4240]: 60F6120AF9: to=<yyyy@melix.net>, relay=bogofilter, delay=0.57, delays=0.4/0/0.04/0.13, dsn=2.0.0, status=sent (delivered via bogofilter service)",
+		];
+		let mut links = Vec::new();
+		for line in lines {
+			match super::super::super::parse_line(line.to_string(), &conf) {
+				Err(x) => panic!("Parser Error: {}", x),
+				Ok(None) => panic!("This should not have been ignored"),
+				Ok(Some(m)) => match linker.add(&m) {
+					None => (),
+					Some(link) => links.push(link)
+				}
+			}
+		}
+		// The Unsure verdict expired before any delivery
+		assert_eq!(links.len(), 2);
+		for link in links.iter() {
+			assert_eq!(link.queue_id, "0345620AE4");
+			assert_eq!(link.verdict, BogofilterVerdict::Spam);
+			assert_eq!(link.spamicity, Some(0.999953));
+		}
+	}
+}
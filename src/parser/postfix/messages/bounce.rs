@@ -1,12 +1,23 @@
+use std::fmt;
 use std::ops::Deref;
 use super::super::ParseError;
 use super::Inner;
 use super::Message;
 use super::MessageParser;
 
+#[derive(Clone, Copy, PartialEq)]
+pub enum BounceKind {
+	SenderNonDelivery,
+	SenderDeliveryStatus,
+	SenderDelay,
+	PostmasterNonDelivery,
+	PostmasterDelay,
+}
+
 #[derive(Debug)]
 pub struct Bounce {
 	inner: Inner,
+	pub kind: BounceKind,
 	child_queue_id_s: usize,
 	child_queue_id_e: usize,
 }
@@ -18,6 +29,25 @@ impl Deref for Bounce {
 	}
 }
 
+impl fmt::Display for BounceKind {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		let kind = match self {
+			&BounceKind::SenderNonDelivery => "sender non-delivery notification",
+			&BounceKind::SenderDeliveryStatus => "sender delivery status notification",
+			&BounceKind::SenderDelay => "sender delay notification",
+			&BounceKind::PostmasterNonDelivery => "postmaster non-delivery notification",
+			&BounceKind::PostmasterDelay => "postmaster delay notification",
+		};
+		write!(fmt, "{}", kind)
+	}
+}
+
+impl fmt::Debug for BounceKind {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		fmt::Display::fmt(self, fmt)
+	}
+}
+
 impl Bounce {
 	pub fn child_queue_id <'a>(&'a self) -> &'a str {
 		&self.raw[self.child_queue_id_s..self.child_queue_id_e]
@@ -26,20 +56,32 @@ impl Bounce {
 }
 impl MessageParser for Bounce {
 	fn parse(inner: Inner, start: usize) -> Result<Option<Message>, ParseError> {
-		let (child_queue_id_s, child_queue_id_e) = {
+		let (kind, child_queue_id_s, child_queue_id_e) = {
 			let rest = &inner.raw[start..];
-			if !rest.starts_with(" sender non-delivery notification: ") {
-				return Err(ParseError::BounceBad);
-			}
-			let rest = &rest[35..];
-			let child_queue_id_s = start + 35;
+			let (kind, offset) = {
+				if rest.starts_with(" sender non-delivery notification: ") {
+					(BounceKind::SenderNonDelivery, 35)
+				} else if rest.starts_with(" sender delivery status notification: ") {
+					(BounceKind::SenderDeliveryStatus, 38)
+				} else if rest.starts_with(" sender delay notification: ") {
+					(BounceKind::SenderDelay, 28)
+				} else if rest.starts_with(" postmaster non-delivery notification: ") {
+					(BounceKind::PostmasterNonDelivery, 39)
+				} else if rest.starts_with(" postmaster delay notification: ") {
+					(BounceKind::PostmasterDelay, 32)
+				} else {
+					return Err(ParseError::BounceBad);
+				}
+			};
+			let rest = &rest[offset..];
+			let child_queue_id_s = start + offset;
 			if rest.bytes().any(|b| ('0' as u8 > b || b > '9' as u8) && ('A' as u8 > b || b > 'F' as u8)) {
 				return Err(ParseError::BounceBadQueueID);
 			}
 			let child_queue_id_e = inner.raw.len();
-			(child_queue_id_s, child_queue_id_e)
+			(kind, child_queue_id_s, child_queue_id_e)
 		};
-		Ok(Some(Message::Bounce { m: Bounce { inner: inner, child_queue_id_s: child_queue_id_s, child_queue_id_e: child_queue_id_e } }))
+		Ok(Some(Message::Bounce { m: Bounce { inner: inner, kind: kind, child_queue_id_s: child_queue_id_s, child_queue_id_e: child_queue_id_e } }))
 	}
 }
 
@@ -55,7 +97,7 @@ mod tests {
 	use super::super::super::ParseError;
 
 	fn parse_bounce(s: String) -> Result<Option<Message>, ParseError> {
-		let conf = ParserConfig { process_noise: vec!["clamsmtpd".to_string()] };
+		let conf = ParserConfig::new(vec!["clamsmtpd".to_string()]);
 		let (inner, start) = match Inner::parse(&conf, s) {
 			Err(x) => panic!("Parser Error: {}", x),
 			Ok(None) => panic!("This should not have been ignored"),
@@ -95,7 +137,63 @@ mod tests {
 			Ok(Some(Message::Bounce{m:x})) => x,
 			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
 		};
+		match bounce.kind {
+			BounceKind::SenderNonDelivery => (),
+			x => panic!("Parsed wrong kind: {}", x)
+		}
+		assert_eq!(bounce.child_queue_id(), "A270E20915");
+		assert_eq!(fmt::format(format_args!("{:?}", bounce)), "Bounce { inner: Inner { raw: \"Aug  4 00:03:15 yuuai postfix/bounce[24350]: 7C091208A3: sender non-delivery notification: A270E20915\", host_e: 21, queue_s: 22, queue_e: 29, process: Bounce, pid: 24350, queue_id_s: 45, queue_id_e: 55 }, kind: sender non-delivery notification, child_queue_id_s: 91, child_queue_id_e: 101 }");
+	}
+
+	#[test]
+	fn valid_kinds() {
+		let s = "Aug  4 00:03:15 yuuai postfix/bounce[24350]: 7C091208A3: sender delivery status notification: A270E20915".to_string();
+		let bounce = match parse_bounce(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::Bounce{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		match bounce.kind {
+			BounceKind::SenderDeliveryStatus => (),
+			x => panic!("Parsed wrong kind: {}", x)
+		}
 		assert_eq!(bounce.child_queue_id(), "A270E20915");
-		assert_eq!(fmt::format(format_args!("{:?}", bounce)), "Bounce { inner: Inner { raw: \"Aug  4 00:03:15 yuuai postfix/bounce[24350]: 7C091208A3: sender non-delivery notification: A270E20915\", host_e: 21, queue_s: 22, queue_e: 29, process: Bounce, pid: 24350, queue_id_s: 45, queue_id_e: 55 }, child_queue_id_s: 91, child_queue_id_e: 101 }");
+		let s = "Aug  4 00:03:15 yuuai postfix/bounce[24350]: 7C091208A3: sender delay notification: B381F31A26".to_string();
+		let bounce = match parse_bounce(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::Bounce{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		match bounce.kind {
+			BounceKind::SenderDelay => (),
+			x => panic!("Parsed wrong kind: {}", x)
+		}
+		assert_eq!(bounce.child_queue_id(), "B381F31A26");
+		let s = "Aug  4 00:03:15 yuuai postfix/bounce[24350]: 7C091208A3: postmaster non-delivery notification: C492042B37".to_string();
+		let bounce = match parse_bounce(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::Bounce{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		match bounce.kind {
+			BounceKind::PostmasterNonDelivery => (),
+			x => panic!("Parsed wrong kind: {}", x)
+		}
+		assert_eq!(bounce.child_queue_id(), "C492042B37");
+		let s = "Aug  4 00:03:15 yuuai postfix/bounce[24350]: 7C091208A3: postmaster delay notification: D5A3153C48".to_string();
+		let bounce = match parse_bounce(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::Bounce{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		match bounce.kind {
+			BounceKind::PostmasterDelay => (),
+			x => panic!("Parsed wrong kind: {}", x)
+		}
+		assert_eq!(bounce.child_queue_id(), "D5A3153C48");
 	}
 }
@@ -0,0 +1,366 @@
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::fmt;
+use std::ops::Deref;
+use super::super::ParseError;
+use super::Inner;
+use super::Message;
+use super::MessageParser;
+use super::Process;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ClamsmtpdStatus {
+	Clean,
+	Virus,
+	Error,
+}
+
+#[derive(Debug)]
+pub struct Clamsmtpd {
+	inner: Inner,
+	session_s: usize,
+	session_e: usize,
+	from_s: usize,
+	from_e: usize,
+	to: Vec<(usize, usize)>,
+	pub status: ClamsmtpdStatus,
+	status_s: usize,
+	status_e: usize,
+}
+
+#[derive(Debug)]
+pub struct ClamsmtpdLink {
+	// The scanned message
+	pub orig_queue_id: String,
+	// The clean message reinjected by clamsmtpd, when known
+	pub queue_id: Option<String>,
+	pub status: ClamsmtpdStatus,
+	pub virus: Option<String>,
+}
+
+// Results waiting for their delivery, per host
+const MAX_PENDING: usize = 1000;
+
+#[derive(Debug)]
+struct ScanResult {
+	time: u32,
+	to: Vec<String>,
+	status: ClamsmtpdStatus,
+	virus: Option<String>,
+}
+
+// clamsmtpd only logs its own session ID, with the envelope it scanned. A
+// result is matched with the next postfix delivery to the clamsmtpd relay on
+// the same host, for one of its recipients and at most timeout seconds later,
+// which gives the scanned queue ID. Clean messages are then reinjected: their
+// queue ID comes from the delivery reply or from the smtpd XFORWARD hop.
+// Results and hops older than timeout are dropped, as well as the oldest
+// results past MAX_PENDING.
+pub struct ClamsmtpdLinker {
+	relay: String,
+	timeout: u32,
+	pending: HashMap<String, VecDeque<ScanResult>>,
+	// Queue IDs of the reinjected messages, by host and scanned queue ID
+	reinjections: HashMap<(String, String), (u32, String)>,
+	// Scanned queue IDs already linked, for their next recipients
+	linked: HashMap<(String, String), u32>,
+}
+
+impl Deref for Clamsmtpd {
+	type Target = Inner;
+	fn deref(&self) -> &Inner {
+		&self.inner
+	}
+}
+
+impl fmt::Display for ClamsmtpdStatus {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		let status = match self {
+			&ClamsmtpdStatus::Clean => "Clean",
+			&ClamsmtpdStatus::Virus => "Virus",
+			&ClamsmtpdStatus::Error => "Error",
+		};
+		write!(fmt, "{}", status)
+	}
+}
+
+impl fmt::Debug for ClamsmtpdStatus {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		fmt::Display::fmt(self, fmt)
+	}
+}
+
+impl Clamsmtpd {
+	pub fn session <'a>(&'a self) -> &'a str {
+		&self.raw[self.session_s..self.session_e]
+	}
+
+	pub fn from <'a>(&'a self) -> &'a str {
+		&self.raw[self.from_s..self.from_e]
+	}
+
+	pub fn to <'a>(&'a self) -> Vec<&'a str> {
+		self.to.iter().map(|&(s, e)| &self.raw[s..e]).collect()
+	}
+
+	pub fn status_text <'a>(&'a self) -> &'a str {
+		&self.raw[self.status_s..self.status_e]
+	}
+
+	pub fn virus <'a>(&'a self) -> Option<&'a str> {
+		match self.status {
+			ClamsmtpdStatus::Virus => Some(&self.raw[self.status_s + 6..self.status_e]),
+			_ => None
+		}
+	}
+}
+
+impl ClamsmtpdLinker {
+	// relay is the host of the clamsmtpd listener, as in Forward::relay
+	pub fn new(relay: &str, timeout: u32) -> ClamsmtpdLinker {
+		ClamsmtpdLinker { relay: relay.to_string(), timeout: timeout, pending: HashMap::new(), reinjections: HashMap::new(), linked: HashMap::new() }
+	}
+
+	pub fn add(&mut self, message: &Message) -> Option<ClamsmtpdLink> {
+		let timeout = self.timeout as i64;
+		let time = match message.inner().timestamp() {
+			None => return None,
+			Some(t) => t
+		};
+		match message {
+			&Message::SmtpdForward { m: ref forward } => {
+				let queue_id = match forward.queue_id() {
+					None => return None,
+					Some(q) => q.to_string()
+				};
+				self.reinjections.retain(|_, &mut (t, _)| forward.elapsed(t).unwrap() <= timeout);
+				self.reinjections.insert((forward.host().to_string(), forward.orig_queue_id().to_string()), (time, queue_id));
+				None
+			},
+			&Message::Clamsmtpd { m: ref clamsmtpd } => {
+				let pending = self.pending.entry(clamsmtpd.host().to_string()).or_insert_with(VecDeque::new);
+				while pending.front().map_or(false, |p| clamsmtpd.elapsed(p.time).unwrap() > timeout) || pending.len() >= MAX_PENDING {
+					pending.pop_front();
+				}
+				pending.push_back(ScanResult { time: time, to: clamsmtpd.to().iter().map(|t| t.to_string()).collect(),
+				                               status: clamsmtpd.status, virus: clamsmtpd.virus().map(|v| v.to_string()) });
+				None
+			},
+			&Message::Forward { m: ref forward } => {
+				if forward.process != Process::Smtp || forward.relay() != self.relay {
+					return None;
+				}
+				let key = match forward.queue_id() {
+					None => return None,
+					Some(q) => (forward.host().to_string(), q.to_string())
+				};
+				self.linked.retain(|_, &mut t| forward.elapsed(t).unwrap() <= timeout);
+				if self.linked.contains_key(&key) {
+					return None;
+				}
+				let result = match self.pending.get_mut(forward.host()) {
+					None => return None,
+					Some(pending) => {
+						let to = forward.to();
+						let found = pending.iter().position(|p| forward.elapsed(p.time).unwrap() <= timeout
+						                                        && p.to.iter().any(|t| t.eq_ignore_ascii_case(to)));
+						match found.and_then(|i| pending.remove(i)) {
+							None => return None,
+							Some(p) => p
+						}
+					}
+				};
+				let queue_id = match result.status {
+					ClamsmtpdStatus::Clean => {
+						let reinjection = self.reinjections.remove(&key).map(|(_, q)| q);
+						forward.child_queue().map(|q| q.to_string()).or(reinjection)
+					},
+					_ => None
+				};
+				self.linked.insert(key.clone(), time);
+				Some(ClamsmtpdLink { orig_queue_id: key.1, queue_id: queue_id, status: result.status, virus: result.virus })
+			},
+			_ => None
+		}
+	}
+}
+
+impl MessageParser for Clamsmtpd {
+	fn parse(inner: Inner, start: usize) -> Result<Option<Message>, ParseError> {
+		let (session_s, session_e, from_s, from_e, to, status, status_s, status_e) = {
+			let rest = &inner.raw[start..];
+			let session_s = start + 1;
+			let session_e = match rest.find(": ") {
+				None => return Ok(None),
+				Some(p) => start + p
+			};
+			let rest = &inner.raw[session_e..];
+			if !rest.starts_with(": from=") {
+				return Ok(None);
+			}
+			let from_s = session_e + 7;
+			let from_e = match rest.find(", to=") {
+				None => return Err(ParseError::ClamsmtpdNoTo),
+				Some(p) => session_e + p
+			};
+			let mut to = Vec::new();
+			let mut pos = from_e;
+			while inner.raw[pos..].starts_with(", to=") {
+				let to_s = pos + 5;
+				let to_e = match inner.raw[to_s..].find(", ") {
+					None => return Err(ParseError::ClamsmtpdNoStatus),
+					Some(p) => to_s + p
+				};
+				to.push((to_s, to_e));
+				pos = to_e;
+			}
+			let rest = &inner.raw[pos..];
+			if !rest.starts_with(", status=") {
+				return Err(ParseError::ClamsmtpdNoStatus);
+			}
+			let status_s = pos + 9;
+			let status_e = inner.raw.len();
+			let status = {
+				let status = &inner.raw[status_s..status_e];
+				if status == "CLEAN" {
+					ClamsmtpdStatus::Clean
+				} else if status.starts_with("VIRUS:") {
+					ClamsmtpdStatus::Virus
+				} else {
+					ClamsmtpdStatus::Error
+				}
+			};
+			(session_s, session_e, from_s, from_e, to, status, status_s, status_e)
+		};
+		Ok(Some(Message::Clamsmtpd { m: Clamsmtpd { inner: inner, session_s: session_s, session_e: session_e, from_s: from_s, from_e: from_e, to: to, status: status, status_s: status_s, status_e: status_e } }))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fmt;
+	use super::*;
+	use super::super::Inner;
+	use super::super::Message;
+	use super::super::MessageParser;
+	use super::super::super::ParserConfig;
+	use super::super::super::ParseError;
+
+	fn conf() -> ParserConfig {
+		ParserConfig::new(vec!["clamav-milter".to_string()])
+	}
+
+	fn parse_clamsmtpd(s: String) -> Result<Option<Message>, ParseError> {
+		let (inner, start) = match Inner::parse(&conf(), s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some((x,y))) => (x,y)
+		};
+		Clamsmtpd::parse(inner, start)
+	}
+
+	#[test]
+	fn ignored() {
+		let s = "Aug  4 00:00:08 yuuai clamsmtpd[2841]: 100004: accepted connection from: 127.0.0.1".to_string();
+		match parse_clamsmtpd(s) {
+			Err(x) => panic!("Failed to parse {}", x),
+			Ok(None) => (),
+			Ok(_) => panic!("This should have been ignored"),
+		};
+		let s = "Aug  4 00:00:08 yuuai clamsmtpd[2841]: couldn't connect to clamd at /var/run/clamav/clamd.ctl".to_string();
+		match parse_clamsmtpd(s) {
+			Err(x) => panic!("Failed to parse {}", x),
+			Ok(None) => (),
+			Ok(_) => panic!("This should have been ignored"),
+		};
+	}
+
+	#[test]
+	fn no_to() {
+		let s = "Aug  4 00:00:08 yuuai clamsmtpd[2841]: 100004: from=aaa@bbb.ccc".to_string();
+		match parse_clamsmtpd(s) {
+			Err(ParseError::ClamsmtpdNoTo) => (),
+			Err(x) => panic!("Wrong error, should have been ClamsmtpdNoTo {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(_) => panic!("This should have failed")
+		};
+	}
+
+	#[test]
+	fn no_status() {
+		let s = "Aug  4 00:00:08 yuuai clamsmtpd[2841]: 100004: from=aaa@bbb.ccc, to=xxx@yyy.zzz".to_string();
+		match parse_clamsmtpd(s) {
+			Err(ParseError::ClamsmtpdNoStatus) => (),
+			Err(x) => panic!("Wrong error, should have been ClamsmtpdNoStatus {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(_) => panic!("This should have failed")
+		};
+	}
+
+	#[test]
+	fn valid() {
+		let s = "Aug  4 00:00:08 yuuai clamsmtpd[2841]: 100004: from=aaa@bbb.ccc, to=xxx@yyy.zzz, status=CLEAN".to_string();
+		let clam = match parse_clamsmtpd(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::Clamsmtpd{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		assert_eq!(clam.queue_id(), None);
+		assert_eq!(clam.session(), "100004");
+		assert_eq!(clam.from(), "aaa@bbb.ccc");
+		assert_eq!(clam.to(), vec!["xxx@yyy.zzz"]);
+		assert_eq!(clam.status, ClamsmtpdStatus::Clean);
+		assert_eq!(clam.virus(), None);
+		assert_eq!(fmt::format(format_args!("{:?}", clam)), "Clamsmtpd { inner: Inner { raw: \"Aug  4 00:00:08 yuuai clamsmtpd[2841]: 100004: from=aaa@bbb.ccc, to=xxx@yyy.zzz, status=CLEAN\", host_e: 21, queue_s: 22, queue_e: 22, process: Clamsmtpd, pid: 2841, queue_id_s: 0, queue_id_e: 0 }, session_s: 39, session_e: 45, from_s: 52, from_e: 63, to: [(68, 79)], status: Clean, status_s: 88, status_e: 93 }");
+		let s = "Aug  4 00:00:08 yuuai clamsmtpd[2841]: 100005: from=aaa@bbb.ccc, to=xxx@yyy.zzz, to=ttt@yyy.zzz, status=VIRUS:Eicar-Test-Signature".to_string();
+		let clam = match parse_clamsmtpd(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::Clamsmtpd{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		assert_eq!(clam.to(), vec!["xxx@yyy.zzz", "ttt@yyy.zzz"]);
+		assert_eq!(clam.status, ClamsmtpdStatus::Virus);
+		assert_eq!(clam.virus(), Some("Eicar-Test-Signature"));
+		assert_eq!(clam.status_text(), "VIRUS:Eicar-Test-Signature");
+	}
+
+	#[test]
+	fn linker() {
+		let mut linker = ClamsmtpdLinker::new("127.0.0.1", 60);
+		let lines = vec![
+			// Reinjection by amavis, not clamsmtpd
+			"Aug  4 00:00:07 yuuai postfix/smtpd.local[20038]: 0345620AE4: client=localhost[127.0.0.1], orig_queue_id=C217620B0B, orig_client=mx.example.com[198.51.100.1]",
+			"Aug  4 00:00:08 yuuai clamsmtpd[2841]: 100005: from=aaa@bbb.ccc, to=xxx@yyy.zzz, status=VIRUS:Eicar-Test-Signature",
+			"Aug  4 00:00:08 yuuai postfix/smtp[20037]: 67D8720887: to=<xxx@yyy.zzz>, relay=127.0.0.1[127.0.0.1]:10025, delay=0.5, delays=0.1/0/0.1/0.3, dsn=2.0.0, status=sent (250 Virus Detected; Discarded Email)",
+			"Aug  4 00:00:09 yuuai postfix/smtpd.local[20039]: 84ED020916: client=localhost[127.0.0.1], orig_queue_id=40A67208A3, orig_client=3.mo52.mail-out.ovh.net[178.33.254.192]",
+			"Aug  4 00:00:09 yuuai clamsmtpd[2841]: 100006: from=ddd@eee.fff, to=ttt@yyy.zzz, to=uuu@yyy.zzz, status=CLEAN",
+			"Aug  4 00:00:10 yuuai postfix/smtp[20040]: 40A67208A3: to=<uuu@yyy.zzz>, relay=127.0.0.1[127.0.0.1]:10025, delay=0.5, delays=0.1/0/0.1/0.3, dsn=2.0.0, status=sent (250 2.0.0 Ok)",
+			"Aug  4 00:00:10 yuuai postfix/smtp[20040]: 40A67208A3: to=<ttt@yyy.zzz>, relay=127.0.0.1[127.0.0.1]:10025, delay=0.5, delays=0.1/0/0.1/0.3, dsn=2.0.0, status=sent (250 2.0.0 Ok)",
+			// Delivered long after the scan
+			"Aug  4 00:01:00 yuuai clamsmtpd[2841]: 100007: from=ddd@eee.fff, to=vvv@yyy.zzz, status=CLEAN",
+			"Aug  4 00:03:00 yuuai postfix/smtp[20041]: 60F6120AF9: to=<vvv@yyy.zzz>, relay=127.0.0.1[127.0.0.1]:10025, delay=120, delays=0.1/0/0.1/120, dsn=2.0.0, status=sent (250 2.0.0 Ok)",
+		];
+		let mut links = Vec::new();
+		for line in lines {
+			match super::super::super::parse_line(line.to_string(), &conf()) {
+				Err(x) => panic!("Parser Error: {}", x),
+				Ok(None) => panic!("This should not have been ignored"),
+				Ok(Some(m)) => match linker.add(&m) {
+					None => (),
+					Some(link) => links.push(link)
+				}
+			}
+		}
+		assert_eq!(links.len(), 2);
+		assert_eq!(links[0].orig_queue_id, "67D8720887");
+		assert_eq!(links[0].queue_id, None);
+		assert_eq!(links[0].status, ClamsmtpdStatus::Virus);
+		assert_eq!(links[0].virus, Some("Eicar-Test-Signature".to_string()));
+		assert_eq!(links[1].orig_queue_id, "40A67208A3");
+		assert_eq!(links[1].queue_id, Some("84ED020916".to_string()));
+		assert_eq!(links[1].status, ClamsmtpdStatus::Clean);
+		assert_eq!(links[1].virus, None);
+	}
+}
@@ -1,17 +1,64 @@
+use std::fmt;
 use std::ops::Deref;
 use super::super::ParseError;
 use super::Inner;
 use super::Message;
 use super::MessageParser;
 use super::Reject;
+use super::RejectProto;
 use super::RejectReason;
+use super::reject::parse_context;
 
 #[derive(Debug)]
 pub struct Cleanup {
 	inner: Inner,
 	message_id_s: usize,
 	message_id_e: usize,
-	resent: bool,
+	pub resent: bool,
+}
+
+pub enum CleanupActionKind {
+	Discard,
+	Filter,
+	Hold,
+	Info,
+	Prepend,
+	Redirect,
+	Replace,
+	Strip,
+	Warning,
+	MilterDiscard,
+	MilterHold,
+	MilterReject,
+}
+
+#[derive(Debug)]
+pub struct CleanupAction {
+	inner: Inner,
+	pub action: CleanupActionKind,
+	text_s: usize,
+	text_e: usize,
+	client_s: usize,
+	client_e: usize,
+	from_s: usize,
+	from_e: usize,
+	to_s: usize,
+	to_e: usize,
+	pub proto: RejectProto,
+	helo_s: usize,
+	helo_e: usize,
+	explanation_s: usize,
+	explanation_e: usize,
+}
+
+#[derive(Debug)]
+pub struct CleanupHeaders {
+	pub queue_id: String,
+	pub message_id: Option<String>,
+	pub resent: bool,
+	pub subject: Option<String>,
+	pub from: Option<String>,
+	pub to: Option<String>,
 }
 
 impl Deref for Cleanup {
@@ -21,12 +68,189 @@ impl Deref for Cleanup {
 	}
 }
 
+impl Deref for CleanupAction {
+	type Target = Inner;
+	fn deref(&self) -> &Inner {
+		&self.inner
+	}
+}
+
+impl fmt::Display for CleanupActionKind {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		let action = match self {
+			&CleanupActionKind::Discard => "discard",
+			&CleanupActionKind::Filter => "filter",
+			&CleanupActionKind::Hold => "hold",
+			&CleanupActionKind::Info => "info",
+			&CleanupActionKind::Prepend => "prepend",
+			&CleanupActionKind::Redirect => "redirect",
+			&CleanupActionKind::Replace => "replace",
+			&CleanupActionKind::Strip => "strip",
+			&CleanupActionKind::Warning => "warning",
+			&CleanupActionKind::MilterDiscard => "milter-discard",
+			&CleanupActionKind::MilterHold => "milter-hold",
+			&CleanupActionKind::MilterReject => "milter-reject",
+		};
+		write!(fmt, "{}", action)
+	}
+}
+
+impl fmt::Debug for CleanupActionKind {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		fmt::Display::fmt(self, fmt)
+	}
+}
+
 impl Cleanup {
 	pub fn message_id <'a>(&'a self) -> &'a str {
 		&self.raw[self.message_id_s..self.message_id_e]
 	}
 
 }
+
+impl CleanupAction {
+	pub fn text <'a>(&'a self) -> &'a str {
+		&self.raw[self.text_s..self.text_e]
+	}
+	pub fn client <'a>(&'a self) -> Option<&'a str> {
+		if self.client_e != 0 {
+			Some(&self.raw[self.client_s..self.client_e])
+		} else {
+			None
+		}
+	}
+	pub fn from <'a>(&'a self) -> Option<&'a str> {
+		if self.from_e != 0 {
+			Some(&self.raw[self.from_s..self.from_e])
+		} else {
+			None
+		}
+	}
+	pub fn to <'a>(&'a self) -> Option<&'a str> {
+		if self.to_e != 0 {
+			Some(&self.raw[self.to_s..self.to_e])
+		} else {
+			None
+		}
+	}
+	pub fn helo <'a>(&'a self) -> Option<&'a str> {
+		if self.helo_e != 0 {
+			Some(&self.raw[self.helo_s..self.helo_e])
+		} else {
+			None
+		}
+	}
+	pub fn explanation<'a>(&'a self) -> Option<&'a str> {
+		if self.explanation_e != 0 {
+			Some(&self.raw[self.explanation_s..self.explanation_e])
+		} else {
+			None
+		}
+	}
+	pub fn header_name<'a>(&'a self) -> Option<&'a str> {
+		let text = self.text();
+		if !text.starts_with("header ") {
+			return None;
+		}
+		match text.find(':') {
+			None => None,
+			Some(p) => Some(&text[7..p])
+		}
+	}
+	pub fn header_value<'a>(&'a self) -> Option<&'a str> {
+		let text = self.text();
+		if !text.starts_with("header ") {
+			return None;
+		}
+		match text.find(':') {
+			None => None,
+			Some(p) => Some(text[p+1..].trim_start())
+		}
+	}
+
+	pub fn parse(inner: Inner, start: usize, action: CleanupActionKind) -> Result<Option<Message>, ParseError> {
+		let text_s = start + match action {
+			CleanupActionKind::Discard => 10,
+			CleanupActionKind::Filter => 9,
+			CleanupActionKind::Hold => 7,
+			CleanupActionKind::Info => 7,
+			CleanupActionKind::Prepend => 10,
+			CleanupActionKind::Redirect => 11,
+			CleanupActionKind::Replace => 10,
+			CleanupActionKind::Strip => 8,
+			CleanupActionKind::Warning => 10,
+			CleanupActionKind::MilterDiscard => 17,
+			CleanupActionKind::MilterHold => 14,
+			CleanupActionKind::MilterReject => 16,
+		};
+		let (message_e, from_s, from_e, to_s, to_e, proto, helo_s, helo_e, explanation_s, explanation_e) = match parse_context(&inner.raw, text_s) {
+			Err(x) => match action {
+				CleanupActionKind::Warning => return Ok(None),
+				_ => return Err(x)
+			},
+			Ok(context) => context
+		};
+		let (text_e, client_s, client_e, explanation_s, explanation_e) = {
+			let message = &inner.raw[text_s..message_e];
+			match action {
+				// "END-OF-MESSAGE from host[addr]: 5.7.1 reply"
+				CleanupActionKind::MilterDiscard | CleanupActionKind::MilterHold | CleanupActionKind::MilterReject => {
+					match message.find(" from ") {
+						None => (message_e, 0, 0, explanation_s, explanation_e),
+						Some(p) => match message[p..].find("]: ") {
+							None => (text_s + p, text_s + p + 6, message_e, explanation_s, explanation_e),
+							Some(l) => (text_s + p, text_s + p + 6, text_s + p + l + 1, text_s + p + l + 3, message_e)
+						}
+					}
+				},
+				// "header Subject: xxx from host[addr]"
+				_ => {
+					match message.rfind(" from ") {
+						Some(p) if message.ends_with(']') => (text_s + p, text_s + p + 6, message_e, explanation_s, explanation_e),
+						_ => (message_e, 0, 0, explanation_s, explanation_e)
+					}
+				}
+			}
+		};
+		Ok(Some(Message::CleanupAction { m: CleanupAction { inner: inner, action: action, text_s: text_s, text_e: text_e, client_s: client_s, client_e: client_e, from_s: from_s, from_e: from_e, to_s: to_s, to_e: to_e, proto: proto, helo_s: helo_s, helo_e: helo_e, explanation_s: explanation_s, explanation_e: explanation_e } }))
+	}
+}
+impl CleanupHeaders {
+	pub fn new(queue_id: &str) -> CleanupHeaders {
+		CleanupHeaders { queue_id: queue_id.to_string(), message_id: None, resent: false, subject: None, from: None, to: None }
+	}
+
+	// Records what a cleanup line tells about the message headers, returns
+	// false if the line carries no header information or is about another
+	// queue ID.
+	pub fn add(&mut self, message: &Message) -> bool {
+		if message.inner().queue_id() != Some(&self.queue_id[..]) {
+			return false;
+		}
+		match message {
+			&Message::Cleanup { m: ref cleanup } => {
+				self.message_id = Some(cleanup.message_id().to_string());
+				self.resent = cleanup.resent;
+				true
+			},
+			&Message::CleanupAction { m: ref action } => {
+				let (name, value) = match (action.header_name(), action.header_value()) {
+					(Some(name), Some(value)) => (name.to_lowercase(), value.to_string()),
+					_ => return false
+				};
+				match &name[..] {
+					"subject" => self.subject = Some(value),
+					"from" => self.from = Some(value),
+					"to" => self.to = Some(value),
+					_ => return false
+				};
+				true
+			},
+			_ => false
+		}
+	}
+}
+
 impl MessageParser for Cleanup {
 	fn parse(inner: Inner, start: usize) -> Result<Option<Message>, ParseError> {
 		match inner.queue_id() {
@@ -50,6 +274,46 @@ impl MessageParser for Cleanup {
 			None => (),
 			Some(reason) => return Reject::parse(inner, start, reason),
 		};
+		let action = {
+			let rest = &inner.raw[start..];
+			if rest.starts_with(" milter-reject: ") {
+				Some(CleanupActionKind::MilterReject)
+			} else if rest.starts_with(" milter-discard: ") {
+				Some(CleanupActionKind::MilterDiscard)
+			} else if rest.starts_with(" milter-hold: ") {
+				Some(CleanupActionKind::MilterHold)
+			} else if rest.starts_with(" discard: ") {
+				Some(CleanupActionKind::Discard)
+			} else if rest.starts_with(" filter: ") {
+				Some(CleanupActionKind::Filter)
+			} else if rest.starts_with(" hold: ") {
+				Some(CleanupActionKind::Hold)
+			} else if rest.starts_with(" info: ") {
+				Some(CleanupActionKind::Info)
+			} else if rest.starts_with(" prepend: ") {
+				Some(CleanupActionKind::Prepend)
+			} else if rest.starts_with(" redirect: ") {
+				Some(CleanupActionKind::Redirect)
+			} else if rest.starts_with(" replace: ") {
+				Some(CleanupActionKind::Replace)
+			} else if rest.starts_with(" strip: ") {
+				Some(CleanupActionKind::Strip)
+			} else if rest.starts_with(" warning: ") {
+				// Only header/body checks, milter and runtime warnings have
+				// no envelope context
+				let text = &rest[10..];
+				if !["header ", "body ", "mime-header ", "nested-header "].iter().any(|p| text.starts_with(p)) {
+					return Ok(None);
+				}
+				Some(CleanupActionKind::Warning)
+			} else {
+				None
+			}
+		};
+		match action {
+			None => (),
+			Some(action) => return CleanupAction::parse(inner, start, action),
+		};
 		let (message_id_s, message_id_e, resent) = {
 			let rest = &inner.raw[start..];
 			let (rest, message_id_s, resent) = {
@@ -95,7 +359,7 @@ mod tests {
 	use super::super::super::ParseError;
 
 	fn parse_cleanup(s: String) -> Result<Option<Message>, ParseError> {
-		let conf = ParserConfig { process_noise: vec!["clamsmtpd".to_string()] };
+		let conf = ParserConfig::new(vec!["clamsmtpd".to_string()]);
 		let (inner, start) = match Inner::parse(&conf, s) {
 			Err(x) => panic!("Parser Error: {}", x),
 			Ok(None) => panic!("This should not have been ignored"),
@@ -114,6 +378,24 @@ mod tests {
 		};
 	}
 
+	#[test]
+	fn ignored_warnings() {
+		for s in ["Aug  4 04:28:18 ozgurluk postfix-in/cleanup[24617]: 84ED020916: warning: milter inet:127.0.0.1:8891: can't read SMFIC_BODYEOB reply packet header: Connection reset by peer",
+		          "Aug  4 04:28:18 ozgurluk postfix-in/cleanup[24617]: 84ED020916: warning: header Subject: hello from localhost[127.0.0.1]"].iter() {
+			match parse_cleanup(s.to_string()) {
+				Err(x) => panic!("Failed to parse {}", x),
+				Ok(None) => (),
+				Ok(_) => panic!("This should have been ignored"),
+			};
+		}
+		let s = "Aug  4 04:28:18 ozgurluk postfix-in/cleanup[24617]: 84ED020916: warning: header Subject: hello from localhost[127.0.0.1]; from=<aaa@bbb.ccc> to=<xxx@yyy.zzz> proto=ESMTP helo=<localhost>".to_string();
+		match parse_cleanup(s) {
+			Ok(Some(Message::CleanupAction{m:_})) => (),
+			Err(x) => panic!("Failed to parse {}", x),
+			Ok(x) => panic!("Wrong message parsed: {:?}", x)
+		};
+	}
+
 	#[test]
 	fn rejected() {
 		let s = "Aug  4 09:07:20 yuuai postfix-in/cleanup[16854]: CAD22209F3: reject: header X-Mailer: XYZxyz from 1.mo53.mail-out.ovh.net[178.32.108.164]; from=<aaa@bbb.ccc> to=<xxx@yyy.zzz> proto=ESMTP helo=<1.mo53.mail-out.ovh.net>: 5.7.1 spam client software rule".to_string();
@@ -128,7 +410,7 @@ mod tests {
 			x => panic!("Parsed wrong reason: {}", x)
 		}
 		assert_eq!(cleanup.message(), "header X-Mailer: XYZxyz from 1.mo53.mail-out.ovh.net[178.32.108.164]");
-		assert_eq!(cleanup.from(), "aaa@bbb.ccc");
+		assert_eq!(cleanup.from(), Some("aaa@bbb.ccc"));
 		match cleanup.to() {
 			None => panic!("Failed to parse to"),
 			Some(s) => assert_eq!(s, "xxx@yyy.zzz")
@@ -137,7 +419,7 @@ mod tests {
 			RejectProto::ESMTP => (),
 			x => panic!("Parsed wrong proto: {}", x)
 		}
-		assert_eq!(cleanup.helo(), "1.mo53.mail-out.ovh.net");
+		assert_eq!(cleanup.helo(), Some("1.mo53.mail-out.ovh.net"));
 		match cleanup.explanation() {
 			None => panic!("Failed to parse explanation"),
 			Some(s) => assert_eq!(s, "5.7.1 spam client software rule"),
@@ -159,7 +441,7 @@ mod tests {
 			x => panic!("Parsed wrong reason: {}", x)
 		}
 		assert_eq!(cleanup.message(), "header Content-Type: image/jpeg;??name=image1.JPG;??x-apple-part-url=\"X.MA1.1438681780@aol.com\" from st11p01im-asmtp001.me.com[17.172.204.151]");
-		assert_eq!(cleanup.from(), "");
+		assert_eq!(cleanup.from(), Some(""));
 		match cleanup.to() {
 			None => panic!("Failed to parse to"),
 			Some(s) => assert_eq!(s, "xxx@yyy.zzz")
@@ -168,7 +450,7 @@ mod tests {
 			RejectProto::ESMTP => (),
 			x => panic!("Parsed wrong proto: {}", x)
 		}
-		assert_eq!(cleanup.helo(), "st11p01im-asmtp001.me.com");
+		assert_eq!(cleanup.helo(), Some("st11p01im-asmtp001.me.com"));
 		match cleanup.explanation() {
 			None => panic!("Failed to parse explanation"),
 			Some(s) => assert_eq!(s, "5.7.1 Files attached to emails that contain or end in \"com\" are prohibited on this server as they may contain viruses. The file named \"image1.JPG;??x-apple-part-url=\"X.MA1.1438681780@aol.com\" was rejected."),
@@ -227,4 +509,130 @@ mod tests {
 		assert_eq!(cleanup.resent, false);
 		assert_eq!(fmt::format(format_args!("{:?}", cleanup)), "Cleanup { inner: Inner { raw: \"Aug  4 00:00:01 yuuai postfix-in/cleanup[22502]: A071220883: message-id=<20150803220001.5E2AA52093C@mail2.les-moocs-gmf.fr>\", host_e: 21, queue_s: 22, queue_e: 32, process: Cleanup, pid: 22502, queue_id_s: 49, queue_id_e: 59 }, message_id_s: 73, message_id_e: 122, resent: false }");
 	}
+
+	#[test]
+	fn action_no_from() {
+		let s = "Aug  4 09:07:20 yuuai postfix-in/cleanup[16854]: CAD22209F3: hold: header Subject: hello from 1.mo53.mail-out.ovh.net[178.32.108.164]".to_string();
+		match parse_cleanup(s) {
+			Err(ParseError::RejectNoFrom) => (),
+			Err(x) => panic!("Wrong error, should have been RejectNoFrom {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(_) => panic!("This should have failed")
+		};
+	}
+
+	#[test]
+	fn valid_action() {
+		let s = "Aug  4 09:07:20 yuuai postfix-in/cleanup[16854]: CAD22209F3: hold: header Subject: Cheap pills from 1.mo53.mail-out.ovh.net[178.32.108.164]; from=<aaa@bbb.ccc> to=<xxx@yyy.zzz> proto=ESMTP helo=<1.mo53.mail-out.ovh.net>: held for review".to_string();
+		let action = match parse_cleanup(s) {
+			Err(x) => panic!("Failed to parse {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::CleanupAction{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		match action.action {
+			CleanupActionKind::Hold => (),
+			x => panic!("Parsed wrong action: {}", x)
+		}
+		assert_eq!(action.text(), "header Subject: Cheap pills");
+		assert_eq!(action.client(), Some("1.mo53.mail-out.ovh.net[178.32.108.164]"));
+		assert_eq!(action.from(), Some("aaa@bbb.ccc"));
+		assert_eq!(action.to(), Some("xxx@yyy.zzz"));
+		assert_eq!(action.helo(), Some("1.mo53.mail-out.ovh.net"));
+		assert_eq!(action.explanation(), Some("held for review"));
+		assert_eq!(fmt::format(format_args!("{:?}", action)), "CleanupAction { inner: Inner { raw: \"Aug  4 09:07:20 yuuai postfix-in/cleanup[16854]: CAD22209F3: hold: header Subject: Cheap pills from 1.mo53.mail-out.ovh.net[178.32.108.164]; from=<aaa@bbb.ccc> to=<xxx@yyy.zzz> proto=ESMTP helo=<1.mo53.mail-out.ovh.net>: held for review\", host_e: 21, queue_s: 22, queue_e: 32, process: Cleanup, pid: 16854, queue_id_s: 49, queue_id_e: 59 }, action: hold, text_s: 67, text_e: 94, client_s: 100, client_e: 139, from_s: 147, from_e: 158, to_s: 164, to_e: 175, proto: ESMTP, helo_s: 195, helo_e: 218, explanation_s: 221, explanation_e: 236 }");
+		let s = "Aug  4 09:07:20 yuuai postfix-in/cleanup[16854]: CAD22209F3: redirect: body Click here now; from=<root@yuuai> to=<xxx@yyy.zzz> proto=SMTP helo=<yuuai>: quarantine@yyy.zzz".to_string();
+		let action = match parse_cleanup(s) {
+			Err(x) => panic!("Failed to parse {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::CleanupAction{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		match action.action {
+			CleanupActionKind::Redirect => (),
+			x => panic!("Parsed wrong action: {}", x)
+		}
+		assert_eq!(action.text(), "body Click here now");
+		assert_eq!(action.client(), None);
+		assert_eq!(action.explanation(), Some("quarantine@yyy.zzz"));
+	}
+
+	#[test]
+	fn valid_milter() {
+		let s = "Aug  4 09:07:20 yuuai postfix-in/cleanup[16854]: CAD22209F3: milter-reject: END-OF-MESSAGE from 1.mo53.mail-out.ovh.net[178.32.108.164]: 5.7.1 Spam message rejected; from=<aaa@bbb.ccc> to=<xxx@yyy.zzz> proto=ESMTP helo=<1.mo53.mail-out.ovh.net>".to_string();
+		let action = match parse_cleanup(s) {
+			Err(x) => panic!("Failed to parse {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::CleanupAction{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		match action.action {
+			CleanupActionKind::MilterReject => (),
+			x => panic!("Parsed wrong action: {}", x)
+		}
+		assert_eq!(action.text(), "END-OF-MESSAGE");
+		assert_eq!(action.client(), Some("1.mo53.mail-out.ovh.net[178.32.108.164]"));
+		assert_eq!(action.from(), Some("aaa@bbb.ccc"));
+		assert_eq!(action.explanation(), Some("5.7.1 Spam message rejected"));
+		let s = "Aug  4 09:07:20 yuuai postfix-in/cleanup[16854]: CAD22209F3: milter-hold: END-OF-MESSAGE from 1.mo53.mail-out.ovh.net[178.32.108.164]: milter triggers HOLD action; from=<aaa@bbb.ccc> to=<xxx@yyy.zzz> proto=ESMTP helo=<1.mo53.mail-out.ovh.net>".to_string();
+		let action = match parse_cleanup(s) {
+			Err(x) => panic!("Failed to parse {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::CleanupAction{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		match action.action {
+			CleanupActionKind::MilterHold => (),
+			x => panic!("Parsed wrong action: {}", x)
+		}
+		assert_eq!(action.explanation(), Some("milter triggers HOLD action"));
+	}
+
+	#[test]
+	fn headers() {
+		let mut headers = CleanupHeaders::new("40A67208A3");
+		let s = "Aug  4 00:03:09 yuuai postfix-in/cleanup[22656]: 40A67208A3: resent-message-id=<PbhLmifNtVG.A.mh.ZU-vVB@bendel>".to_string();
+		match parse_cleanup(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(x)) => assert!(headers.add(&x))
+		};
+		let s = "Aug  4 00:03:09 yuuai postfix-in/cleanup[22656]: 40A67208A3: info: header Subject: Re: [bendel] Release from 1.mo53.mail-out.ovh.net[178.32.108.164]; from=<aaa@bbb.ccc> to=<xxx@yyy.zzz> proto=ESMTP helo=<1.mo53.mail-out.ovh.net>".to_string();
+		let action = match parse_cleanup(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(x)) => x
+		};
+		assert!(headers.add(&action));
+		match action {
+			Message::CleanupAction{m:x} => {
+				assert_eq!(x.header_name(), Some("Subject"));
+				assert_eq!(x.header_value(), Some("Re: [bendel] Release"));
+			},
+			x => panic!("Wrong message parsed: {:?}", x)
+		};
+		let s = "Aug  4 00:03:09 yuuai postfix-in/cleanup[22656]: 40A67208A3: info: header From: \"Bendel\" <aaa@bbb.ccc> from 1.mo53.mail-out.ovh.net[178.32.108.164]; from=<aaa@bbb.ccc> to=<xxx@yyy.zzz> proto=ESMTP helo=<1.mo53.mail-out.ovh.net>".to_string();
+		match parse_cleanup(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(x)) => assert!(headers.add(&x))
+		};
+		let s = "Aug  4 00:03:09 yuuai postfix-in/cleanup[22656]: 40A67208A3: info: body Unsubscribe from 1.mo53.mail-out.ovh.net[178.32.108.164]; from=<aaa@bbb.ccc> to=<xxx@yyy.zzz> proto=ESMTP helo=<1.mo53.mail-out.ovh.net>".to_string();
+		match parse_cleanup(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(x)) => assert!(!headers.add(&x))
+		};
+		let s = "Aug  4 00:03:10 yuuai postfix-in/cleanup[22656]: 4B1C5209E4: info: header To: <bbb@ccc.ddd> from 1.mo53.mail-out.ovh.net[178.32.108.164]; from=<aaa@bbb.ccc> to=<xxx@yyy.zzz> proto=ESMTP helo=<1.mo53.mail-out.ovh.net>".to_string();
+		match parse_cleanup(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(x)) => assert!(!headers.add(&x))
+		};
+		assert_eq!(headers.message_id, Some("PbhLmifNtVG.A.mh.ZU-vVB@bendel".to_string()));
+		assert_eq!(headers.resent, true);
+		assert_eq!(headers.subject, Some("Re: [bendel] Release".to_string()));
+		assert_eq!(headers.from, Some("\"Bendel\" <aaa@bbb.ccc>".to_string()));
+		assert_eq!(headers.to, None);
+	}
 }
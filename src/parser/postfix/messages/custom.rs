@@ -0,0 +1,24 @@
+use std::any::Any;
+use std::ops::Deref;
+use super::Inner;
+
+// Message produced by a parser registered on ParserConfig. The parser's own
+// result type is recovered with data.downcast_ref().
+#[derive(Debug)]
+pub struct Custom {
+	inner: Inner,
+	pub data: Box<dyn Any>,
+}
+
+impl Deref for Custom {
+	type Target = Inner;
+	fn deref(&self) -> &Inner {
+		&self.inner
+	}
+}
+
+impl Custom {
+	pub fn new(inner: Inner, data: Box<dyn Any>) -> Custom {
+		Custom { inner: inner, data: data }
+	}
+}
@@ -0,0 +1,390 @@
+use std::cmp;
+use std::ops::Deref;
+use super::super::ParseError;
+use super::Inner;
+use super::Message;
+use super::MessageParser;
+
+#[derive(Debug)]
+pub struct DovecotDelivery {
+	inner: Inner,
+	service_s: usize,
+	service_e: usize,
+	user_s: usize,
+	user_e: usize,
+	session_s: usize,
+	session_e: usize,
+	message_id_s: usize,
+	message_id_e: usize,
+	mailbox_s: usize,
+	mailbox_e: usize,
+}
+
+#[derive(Debug)]
+pub struct DovecotAuthFailure {
+	inner: Inner,
+	service_s: usize,
+	service_e: usize,
+	user_s: usize,
+	user_e: usize,
+	remote_ip_s: usize,
+	remote_ip_e: usize,
+	reason_s: usize,
+	reason_e: usize,
+}
+
+impl Deref for DovecotDelivery {
+	type Target = Inner;
+	fn deref(&self) -> &Inner {
+		&self.inner
+	}
+}
+
+impl Deref for DovecotAuthFailure {
+	type Target = Inner;
+	fn deref(&self) -> &Inner {
+		&self.inner
+	}
+}
+
+impl DovecotDelivery {
+	pub fn service <'a>(&'a self) -> &'a str {
+		&self.raw[self.service_s..self.service_e]
+	}
+
+	pub fn user <'a>(&'a self) -> &'a str {
+		&self.raw[self.user_s..self.user_e]
+	}
+
+	pub fn session <'a>(&'a self) -> Option<&'a str> {
+		if self.session_e != 0 {
+			Some(&self.raw[self.session_s..self.session_e])
+		} else {
+			None
+		}
+	}
+
+	// Without the angle brackets, as Cleanup::message_id
+	pub fn message_id <'a>(&'a self) -> &'a str {
+		&self.raw[self.message_id_s..self.message_id_e]
+	}
+
+	pub fn mailbox <'a>(&'a self) -> &'a str {
+		&self.raw[self.mailbox_s..self.mailbox_e]
+	}
+}
+
+impl DovecotAuthFailure {
+	pub fn service <'a>(&'a self) -> &'a str {
+		&self.raw[self.service_s..self.service_e]
+	}
+
+	pub fn user <'a>(&'a self) -> Option<&'a str> {
+		if self.user_e != 0 {
+			Some(&self.raw[self.user_s..self.user_e])
+		} else {
+			None
+		}
+	}
+
+	pub fn remote_ip <'a>(&'a self) -> &'a str {
+		&self.raw[self.remote_ip_s..self.remote_ip_e]
+	}
+
+	pub fn reason <'a>(&'a self) -> &'a str {
+		&self.raw[self.reason_s..self.reason_e]
+	}
+}
+
+// lmtp(user)<pid><session>: msgid=<id>: saved mail to INBOX
+// lmtp(pid, user): session: msgid=<id>: saved mail to INBOX
+fn parse_delivery(inner: Inner, start: usize, service_e: usize) -> Result<Option<Message>, ParseError> {
+	let (user_s, user_e, session_s, session_e, message_id_s, message_id_e, mailbox_s, mailbox_e) = {
+		let raw = &inner.raw;
+		// Whichever comes first, the rest of the line may hold either
+		let params_e = match (raw[service_e..].find("): "), raw[service_e..].find(")<")) {
+			(Some(p), Some(q)) => service_e + cmp::min(p, q),
+			(Some(p), None) | (None, Some(p)) => service_e + p,
+			(None, None) => return Err(ParseError::DovecotBadUser),
+		};
+		let user_s = match raw[service_e + 1..params_e].find(", ") {
+			None => service_e + 1,
+			Some(p) => service_e + 1 + p + 2
+		};
+		let mut pos = params_e + 1;
+		let (mut session_s, mut session_e) = (0, 0);
+		while raw[pos..].starts_with('<') {
+			let end = match raw[pos..].find('>') {
+				None => return Err(ParseError::DovecotBadUser),
+				Some(p) => pos + p
+			};
+			session_s = pos + 1;
+			session_e = end;
+			pos = end + 1;
+		}
+		if !raw[pos..].starts_with(": ") {
+			return Err(ParseError::DovecotBadUser);
+		}
+		pos += 2;
+		if !raw[pos..].starts_with("msgid=") {
+			match raw[pos..].find(": msgid=") {
+				Some(p) if !raw[pos..pos + p].contains(' ') => {
+					session_s = pos;
+					session_e = pos + p;
+					pos += p + 2;
+				},
+				_ => return Ok(None),
+			}
+		}
+		let rest = &raw[pos + 6..];
+		let (message_id_s, message_id_e) = if rest.starts_with('<') {
+			match rest.find(">: ") {
+				None => return Err(ParseError::DovecotBadMessageID),
+				Some(p) => (pos + 7, pos + 6 + p)
+			}
+		} else {
+			match rest.find(": ") {
+				None => return Err(ParseError::DovecotBadMessageID),
+				Some(p) => (pos + 6, pos + 6 + p)
+			}
+		};
+		let status_s = match raw[message_id_e..].find(": ") {
+			None => return Err(ParseError::DovecotBadMessageID),
+			Some(p) => message_id_e + p + 2
+		};
+		let status = &raw[status_s..];
+		let (mailbox_s, mailbox_e) = if status.starts_with("saved mail to ") {
+			(status_s + 14, raw.len())
+		} else if status.starts_with("stored mail into mailbox '") && status.ends_with('\'') {
+			(status_s + 26, raw.len() - 1)
+		} else {
+			return Ok(None);
+		};
+		(user_s, params_e, session_s, session_e, message_id_s, message_id_e, mailbox_s, mailbox_e)
+	};
+	Ok(Some(Message::DovecotDelivery { m: DovecotDelivery { inner: inner, service_s: start + 1, service_e: service_e, user_s: user_s, user_e: user_e, session_s: session_s, session_e: session_e, message_id_s: message_id_s, message_id_e: message_id_e, mailbox_s: mailbox_s, mailbox_e: mailbox_e } }))
+}
+
+// auth: passdb(user,ip[,<session>]): reason
+fn parse_auth(inner: Inner, start: usize, service_e: usize) -> Result<Option<Message>, ParseError> {
+	let (user_s, user_e, remote_ip_s, remote_ip_e, reason_s, reason_e) = {
+		let raw = &inner.raw;
+		let pos = match raw[service_e..].find(": ") {
+			None => return Ok(None),
+			Some(p) => service_e + p + 2
+		};
+		let params_s = match raw[pos..].find('(') {
+			None => return Ok(None),
+			Some(p) => pos + p + 1
+		};
+		if raw[pos..params_s].contains(' ') {
+			return Ok(None);
+		}
+		let params_e = match raw[params_s..].find("): ") {
+			None => return Ok(None),
+			Some(p) => params_s + p
+		};
+		let reason_s = params_e + 3;
+		let reason = &raw[reason_s..];
+		if !(reason.contains("failed") || reason.contains("mismatch") || reason == "unknown user") {
+			return Ok(None);
+		}
+		let user_e = match raw[params_s..params_e].find(',') {
+			None => return Err(ParseError::DovecotNoRemoteIP),
+			Some(p) => params_s + p
+		};
+		let remote_ip_e = match raw[user_e + 1..params_e].find(',') {
+			None => params_e,
+			Some(p) => user_e + 1 + p
+		};
+		(params_s, user_e, user_e + 1, remote_ip_e, reason_s, raw.len())
+	};
+	Ok(Some(Message::DovecotAuthFailure { m: DovecotAuthFailure { inner: inner, service_s: start + 1, service_e: service_e, user_s: user_s, user_e: user_e, remote_ip_s: remote_ip_s, remote_ip_e: remote_ip_e, reason_s: reason_s, reason_e: reason_e } }))
+}
+
+// imap-login: Disconnected (auth failed, 1 attempts in 2 secs): user=<user>, method=PLAIN, rip=ip, ...
+fn parse_login(inner: Inner, start: usize, service_e: usize) -> Result<Option<Message>, ParseError> {
+	let (user_s, user_e, remote_ip_s, remote_ip_e, reason_s, reason_e) = {
+		let raw = &inner.raw;
+		let reason_s = match raw[service_e..].find(" (auth failed") {
+			None => return Ok(None),
+			Some(p) => service_e + p + 2
+		};
+		let reason_e = match raw[reason_s..].find("): ") {
+			None => return Err(ParseError::DovecotNoRemoteIP),
+			Some(p) => reason_s + p
+		};
+		let (mut user_s, mut user_e) = (0, 0);
+		let (mut remote_ip_s, mut remote_ip_e) = (0, 0);
+		let mut pos = reason_e + 3;
+		while pos < raw.len() {
+			let end = match raw[pos..].find(", ") {
+				None => raw.len(),
+				Some(p) => pos + p
+			};
+			let field = &raw[pos..end];
+			if field.starts_with("user=<") && field.ends_with('>') {
+				user_s = pos + 6;
+				user_e = end - 1;
+			} else if field.starts_with("rip=") {
+				remote_ip_s = pos + 4;
+				remote_ip_e = end;
+			}
+			pos = end + 2;
+		}
+		if remote_ip_e == 0 {
+			return Err(ParseError::DovecotNoRemoteIP);
+		}
+		if user_e == user_s {
+			user_e = 0;
+		}
+		(user_s, user_e, remote_ip_s, remote_ip_e, reason_s, reason_e)
+	};
+	Ok(Some(Message::DovecotAuthFailure { m: DovecotAuthFailure { inner: inner, service_s: start + 1, service_e: service_e, user_s: user_s, user_e: user_e, remote_ip_s: remote_ip_s, remote_ip_e: remote_ip_e, reason_s: reason_s, reason_e: reason_e } }))
+}
+
+// Auth failures share the dovecot syslog identifier with deliveries
+impl MessageParser for DovecotDelivery {
+	fn parse(inner: Inner, start: usize) -> Result<Option<Message>, ParseError> {
+		let service_e = match inner.raw[start + 1..].find(|c| c == '(' || c == ':') {
+			None => return Ok(None),
+			Some(p) => start + 1 + p
+		};
+		let service = inner.raw[start + 1..service_e].to_string();
+		if service == "lmtp" || service == "lda" {
+			parse_delivery(inner, start, service_e)
+		} else if service == "auth" || service == "auth-worker" {
+			parse_auth(inner, start, service_e)
+		} else if service.ends_with("-login") {
+			parse_login(inner, start, service_e)
+		} else {
+			Ok(None)
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fmt;
+	use super::*;
+	use super::super::Inner;
+	use super::super::Message;
+	use super::super::MessageParser;
+	use super::super::super::ParserConfig;
+	use super::super::super::ParseError;
+
+	fn parse_dovecot(s: String) -> Result<Option<Message>, ParseError> {
+		let conf = ParserConfig::new(vec!["clamsmtpd".to_string()]);
+		let (inner, start) = match Inner::parse(&conf, s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some((x,y))) => (x,y)
+		};
+		DovecotDelivery::parse(inner, start)
+	}
+
+	fn parse_delivery(s: &str) -> DovecotDelivery {
+		match parse_dovecot(s.to_string()) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::DovecotDelivery{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		}
+	}
+
+	fn parse_auth_failure(s: &str) -> DovecotAuthFailure {
+		match parse_dovecot(s.to_string()) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::DovecotAuthFailure{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		}
+	}
+
+	#[test]
+	fn ignored() {
+		let lines = vec![
+			"Aug  4 00:00:08 yuuai dovecot: master: Dovecot v2.2.22 starting up for imap, lmtp",
+			"Aug  4 00:00:08 yuuai dovecot: lmtp(2841): Connect from local",
+			"Aug  4 00:00:08 yuuai dovecot: imap-login: Login: user=<aaa@bbb.ccc>, method=PLAIN, rip=1.2.3.4, lip=5.6.7.8, mpid=2842, TLS",
+			"Aug  4 00:00:08 yuuai dovecot: auth-worker(2841): pam(aaa@bbb.ccc,1.2.3.4): lookup service=dovecot",
+		];
+		for s in lines {
+			match parse_dovecot(s.to_string()) {
+				Err(x) => panic!("Failed to parse {}", x),
+				Ok(None) => (),
+				Ok(_) => panic!("This should have been ignored"),
+			};
+		}
+	}
+
+	#[test]
+	fn errors() {
+		let s = "Aug  4 00:00:08 yuuai dovecot: lmtp(aaa@bbb.ccc: msgid=<abc@bbb.ccc>: saved mail to INBOX".to_string();
+		match parse_dovecot(s) {
+			Err(ParseError::DovecotBadUser) => (),
+			Err(x) => panic!("Wrong error, should have been DovecotBadUser {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(_) => panic!("This should have failed")
+		};
+		let s = "Aug  4 00:00:08 yuuai dovecot: lmtp(aaa@bbb.ccc)<2841><GhPyMQ2C1FylcwAAS6SR4Q>: msgid=<abc@bbb.ccc saved mail to INBOX".to_string();
+		match parse_dovecot(s) {
+			Err(ParseError::DovecotBadMessageID) => (),
+			Err(x) => panic!("Wrong error, should have been DovecotBadMessageID {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(_) => panic!("This should have failed")
+		};
+		let s = "Aug  4 00:00:08 yuuai dovecot: imap-login: Disconnected (auth failed, 1 attempts in 2 secs): user=<aaa@bbb.ccc>, method=PLAIN".to_string();
+		match parse_dovecot(s) {
+			Err(ParseError::DovecotNoRemoteIP) => (),
+			Err(x) => panic!("Wrong error, should have been DovecotNoRemoteIP {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(_) => panic!("This should have failed")
+		};
+	}
+
+	#[test]
+	fn valid_delivery() {
+		let delivery = parse_delivery("Aug  4 00:00:08 yuuai dovecot: lmtp(aaa@bbb.ccc)<2841><GhPyMQ2C1FylcwAAS6SR4Q>: msgid=<abc@bbb.ccc>: saved mail to INBOX");
+		assert_eq!(delivery.service(), "lmtp");
+		assert_eq!(delivery.user(), "aaa@bbb.ccc");
+		assert_eq!(delivery.session(), Some("GhPyMQ2C1FylcwAAS6SR4Q"));
+		assert_eq!(delivery.message_id(), "abc@bbb.ccc");
+		assert_eq!(delivery.mailbox(), "INBOX");
+		assert_eq!(fmt::format(format_args!("{:?}", delivery)), "DovecotDelivery { inner: Inner { raw: \"Aug  4 00:00:08 yuuai dovecot: lmtp(aaa@bbb.ccc)<2841><GhPyMQ2C1FylcwAAS6SR4Q>: msgid=<abc@bbb.ccc>: saved mail to INBOX\", host_e: 21, queue_s: 22, queue_e: 22, process: Dovecot, pid: 0, queue_id_s: 0, queue_id_e: 0 }, service_s: 31, service_e: 35, user_s: 36, user_e: 47, session_s: 55, session_e: 77, message_id_s: 87, message_id_e: 98, mailbox_s: 115, mailbox_e: 120 }");
+		let delivery = parse_delivery("Aug  4 00:00:08 yuuai dovecot: lmtp(2841, aaa@bbb.ccc): dQ6xMGvYolcZCwAAS6SR4Q: msgid=<abc@bbb.ccc>: saved mail to INBOX");
+		assert_eq!(delivery.user(), "aaa@bbb.ccc");
+		assert_eq!(delivery.session(), Some("dQ6xMGvYolcZCwAAS6SR4Q"));
+		assert_eq!(delivery.message_id(), "abc@bbb.ccc");
+		let delivery = parse_delivery("Aug  4 00:00:08 yuuai dovecot: lda(aaa): msgid=<abc@bbb.ccc>: stored mail into mailbox 'Junk'");
+		assert_eq!(delivery.service(), "lda");
+		assert_eq!(delivery.user(), "aaa");
+		assert_eq!(delivery.session(), None);
+		assert_eq!(delivery.mailbox(), "Junk");
+		let delivery = parse_delivery("Aug  4 00:00:08 yuuai dovecot: lmtp(aaa@bbb.ccc)<2841><GhPyMQ2C1FylcwAAS6SR4Q>: msgid=<abc@bbb.ccc>: stored mail into mailbox 'Lists (dev): archive'");
+		assert_eq!(delivery.user(), "aaa@bbb.ccc");
+		assert_eq!(delivery.session(), Some("GhPyMQ2C1FylcwAAS6SR4Q"));
+		assert_eq!(delivery.message_id(), "abc@bbb.ccc");
+		assert_eq!(delivery.mailbox(), "Lists (dev): archive");
+	}
+
+	#[test]
+	fn valid_auth_failure() {
+		let failure = parse_auth_failure("Aug  4 00:00:08 yuuai dovecot: auth: passwd-file(aaa@bbb.ccc,1.2.3.4,<GhPyMQ2C1Fyl>): Password mismatch");
+		assert_eq!(failure.service(), "auth");
+		assert_eq!(failure.user(), Some("aaa@bbb.ccc"));
+		assert_eq!(failure.remote_ip(), "1.2.3.4");
+		assert_eq!(failure.reason(), "Password mismatch");
+		assert_eq!(fmt::format(format_args!("{:?}", failure)), "DovecotAuthFailure { inner: Inner { raw: \"Aug  4 00:00:08 yuuai dovecot: auth: passwd-file(aaa@bbb.ccc,1.2.3.4,<GhPyMQ2C1Fyl>): Password mismatch\", host_e: 21, queue_s: 22, queue_e: 22, process: Dovecot, pid: 0, queue_id_s: 0, queue_id_e: 0 }, service_s: 31, service_e: 35, user_s: 49, user_e: 60, remote_ip_s: 61, remote_ip_e: 68, reason_s: 86, reason_e: 103 }");
+		let failure = parse_auth_failure("Aug  4 00:00:08 yuuai dovecot: auth-worker(2841): pam(aaa@bbb.ccc,1.2.3.4): pam_authenticate() failed: Authentication failure (password mismatch?)");
+		assert_eq!(failure.service(), "auth-worker");
+		assert_eq!(failure.user(), Some("aaa@bbb.ccc"));
+		assert_eq!(failure.remote_ip(), "1.2.3.4");
+		assert_eq!(failure.reason(), "pam_authenticate() failed: Authentication failure (password mismatch?)");
+		let failure = parse_auth_failure("Aug  4 00:00:08 yuuai dovecot: imap-login: Disconnected (auth failed, 1 attempts in 2 secs): user=<aaa@bbb.ccc>, method=PLAIN, rip=1.2.3.4, lip=5.6.7.8, TLS, session=<GhPyMQ2C1Fyl>");
+		assert_eq!(failure.service(), "imap-login");
+		assert_eq!(failure.user(), Some("aaa@bbb.ccc"));
+		assert_eq!(failure.remote_ip(), "1.2.3.4");
+		assert_eq!(failure.reason(), "auth failed, 1 attempts in 2 secs");
+	}
+}
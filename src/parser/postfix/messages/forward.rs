@@ -1,3 +1,4 @@
+use std::fmt;
 use std::ops::Deref;
 use super::super::ParseError;
 use super::Inner;
@@ -30,6 +31,17 @@ pub struct ForwardError {
 	message_e: usize,
 }
 
+pub enum VerifyStatus {
+	Deliverable,
+	Undeliverable,
+}
+
+#[derive(Debug)]
+pub struct ForwardVerify {
+	forward: Forward,
+	pub result: VerifyStatus,
+}
+
 impl Deref for Forward {
 	type Target = Inner;
 	fn deref(&self) -> &Inner {
@@ -44,6 +56,29 @@ impl Deref for ForwardError {
 	}
 }
 
+impl Deref for ForwardVerify {
+	type Target = Forward;
+	fn deref(&self) -> &Forward {
+		&self.forward
+	}
+}
+
+impl fmt::Display for VerifyStatus {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		let status = match self {
+			&VerifyStatus::Deliverable => "deliverable",
+			&VerifyStatus::Undeliverable => "undeliverable",
+		};
+		write!(fmt, "{}", status)
+	}
+}
+
+impl fmt::Debug for VerifyStatus {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		fmt::Display::fmt(self, fmt)
+	}
+}
+
 impl Forward {
 	pub fn to <'a>(&'a self) -> &'a str {
 		&self.raw[self.to_s..self.to_e]
@@ -84,25 +119,10 @@ impl ForwardError {
 
 impl MessageParser for Forward {
 	fn parse(inner: Inner, start: usize) -> Result<Option<Message>, ParseError> {
-		{
-			match inner.queue_id() {
-				None => return Ok(None),
-				Some(_) => ()
-			};
-			let rest = &inner.raw[start..];
-			if rest.starts_with(" enabling PIX workarounds: ") {
-				return Ok(None);
-			}
-			if rest.starts_with(" lost connection with") {
-				return Ok(None);
-			}
-			if rest.starts_with(" conversation with ") {
-				return Ok(None);
-			}
-			if rest.starts_with(" Cannot start TLS: ") {
-				return Ok(None);
-			}
-		}
+		match inner.queue_id() {
+			None => return Ok(None),
+			Some(_) => ()
+		};
 		let (host_s, host_e, message_s, message_e) = {
 			let rest = &inner.raw[start..];
 			let error = rest.starts_with(" host ");
@@ -242,7 +262,21 @@ impl MessageParser for Forward {
 			};
 			(to_s, to_e, orig_to_s, orig_to_e, relay_s, relay_e, dsn, status_s, status_e, child_queue_id_s, child_queue_id_e)
 		};
-		Ok(Some(Message::Forward { m: Forward { inner: inner, to_s:to_s, to_e:to_e, orig_to_s:orig_to_s, orig_to_e:orig_to_e, relay_s:relay_s, relay_e:relay_e, dsn:dsn, status_s:status_s, status_e:status_e, child_queue_id_s:child_queue_id_s, child_queue_id_e:child_queue_id_e } }))
+		let result = {
+			let status = &inner.raw[status_s..status_e];
+			if status.starts_with("deliverable ") {
+				Some(VerifyStatus::Deliverable)
+			} else if status.starts_with("undeliverable ") {
+				Some(VerifyStatus::Undeliverable)
+			} else {
+				None
+			}
+		};
+		let forward = Forward { inner: inner, to_s:to_s, to_e:to_e, orig_to_s:orig_to_s, orig_to_e:orig_to_e, relay_s:relay_s, relay_e:relay_e, dsn:dsn, status_s:status_s, status_e:status_e, child_queue_id_s:child_queue_id_s, child_queue_id_e:child_queue_id_e };
+		match result {
+			None => Ok(Some(Message::Forward { m: forward })),
+			Some(result) => Ok(Some(Message::ForwardVerify { m: ForwardVerify { forward: forward, result: result } }))
+		}
 	}
 }
 
@@ -257,7 +291,7 @@ mod tests {
 	use super::super::super::ParseError;
 
 	fn parse_forward(s: String) -> Result<Option<Message>, ParseError> {
-		let conf = ParserConfig { process_noise: vec!["clamsmtpd".to_string()] };
+		let conf = ParserConfig::new(vec!["clamsmtpd".to_string()]);
 		let (inner, start) = match Inner::parse(&conf, s) {
 			Err(x) => panic!("Parser Error: {}", x),
 			Ok(None) => panic!("This should not have been ignored"),
@@ -266,18 +300,21 @@ mod tests {
 		Forward::parse(inner, start)
 	}
 
+	// Dropped by the default noise rules of the configuration
 	#[test]
 	fn ignored() {
-		match parse_forward("Aug  4 00:03:19 yuuai postfix/smtp[18086]: C5B28208A3: enabling PIX workarounds: disable_esmtp delay_dotcrlf for mrelay2.axa.com[171.18.34.18]:25".to_string()) {
+		let conf = ParserConfig::new(vec!["clamsmtpd".to_string()]);
+		match Inner::parse(&conf, "Aug  4 00:03:19 yuuai postfix/smtp[18086]: C5B28208A3: enabling PIX workarounds: disable_esmtp delay_dotcrlf for mrelay2.axa.com[171.18.34.18]:25".to_string()) {
 			Err(x) => panic!("Failed to parse: {}", x),
 			Ok(None) => (),
 			Ok(_) => panic!("Should have been ignored")
 		}
-		match parse_forward("Aug  4 00:04:40 svoboda postfix/smtp[337]: C06251400063D: lost connection with smtp-in.orange.fr[193.252.22.65] while receiving the initial server greeting".to_string()) {
+		match Inner::parse(&conf, "Aug  4 00:04:40 svoboda postfix/smtp[337]: C06251400063D: lost connection with smtp-in.orange.fr[193.252.22.65] while receiving the initial server greeting".to_string()) {
 			Err(x) => panic!("Failed to parse: {}", x),
 			Ok(None) => (),
 			Ok(_) => panic!("Should have been ignored")
 		}
+		assert_eq!(conf.noise().iter().map(|r| r.dropped()).sum::<u64>(), 2);
 	}
 
 	#[test]
@@ -474,4 +511,34 @@ mod tests {
 		assert_eq!(forward.child_queue(), None);
 		assert_eq!(fmt::format(format_args!("{:?}", forward)), "Forward { inner: Inner { raw: \"Jul 25 00:00:01 yuuai postfix/smtp[3703]: 0345620AE4: to=<xxxx@melix.net>, relay=bogofilter, delay=0.57, delays=0.4/0/0.04/0.13, dsn=2.0.0, status=sent (delivered via bogofilter service)\", host_e: 21, queue_s: 22, queue_e: 29, process: Smtp, pid: 3703, queue_id_s: 42, queue_id_e: 52 }, to_s: 58, to_e: 72, orig_to_s: 0, orig_to_e: 0, relay_s: 81, relay_e: 91, dsn: [2, 0, 0], status_s: 147, status_e: 186, child_queue_id_s: 0, child_queue_id_e: 0 }");
 	}
+
+	#[test]
+	fn valid_verify() {
+		let s = "Aug  4 00:02:11 yuuai postfix/smtp[10627]: C217620B0B: to=<xxxx@melix.net>, relay=mx.melix.net[129.104.30.40]:25, delay=0.12, delays=0.01/0/0.06/0.05, dsn=2.1.5, status=deliverable (250 2.1.5 Ok)".to_string();
+		let verify = match parse_forward(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::ForwardVerify{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		match verify.result {
+			VerifyStatus::Deliverable => (),
+			x => panic!("Parsed wrong result: {}", x)
+		}
+		assert_eq!(verify.to(), "xxxx@melix.net");
+		assert_eq!(verify.dsn, [2, 1, 5]);
+		let s = "Aug  4 00:02:11 yuuai postfix/smtp[10627]: C217620B0B: to=<yyyy@melix.net>, relay=mx.melix.net[129.104.30.40]:25, delay=0.12, delays=0.01/0/0.06/0.05, dsn=5.1.1, status=undeliverable (host mx.melix.net[129.104.30.40] said: 550 5.1.1 <yyyy@melix.net>: Recipient address rejected: User unknown (in reply to RCPT TO command))".to_string();
+		let verify = match parse_forward(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::ForwardVerify{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		match verify.result {
+			VerifyStatus::Undeliverable => (),
+			x => panic!("Parsed wrong result: {}", x)
+		}
+		assert_eq!(verify.to(), "yyyy@melix.net");
+		assert_eq!(fmt::format(format_args!("{:?}", verify.result)), "undeliverable");
+	}
 }
@@ -5,18 +5,35 @@ pub const DATE_LEN : usize = 15;
 
 #[derive(Debug, PartialEq)]
 pub enum Process {
+	Amavis,
 	Anvil,
+	Bogofilter,
 	Bounce,
+	Clamsmtpd,
 	Cleanup,
+	Custom(String),
 	Discard,
+	Dovecot,
 	Error,
 	Local,
+	Opendkim,
+	Opendmarc,
 	Pickup,
 	Pipe,
+	PolicydSpf,
+	Postdrop,
+	Postgrey,
+	Postlicyd,
+	Proxymap,
 	Qmgr,
+	Rspamd,
 	Scache,
+	Sendmail,
 	Smtp,
 	Smtpd,
+	Spamd,
+	TrivialRewrite,
+	Verify,
 }
 
 #[derive(Debug)]
@@ -36,6 +53,96 @@ impl Inner {
 		&self.raw[..DATE_LEN]
 	}
 
+	// Month (1 to 12), day and seconds since midnight of the syslog date
+	fn date_fields(line: &str) -> Option<(u32, u32, u32)> {
+		let date = match line.get(..DATE_LEN) {
+			Some(date) if date.is_ascii() => date,
+			_ => return None
+		};
+		let month = match &date[..4] {
+			"Jan " => 1,
+			"Feb " => 2,
+			"Mar " => 3,
+			"Apr " => 4,
+			"May " => 5,
+			"Jun " => 6,
+			"Jul " => 7,
+			"Aug " => 8,
+			"Sep " => 9,
+			"Oct " => 10,
+			"Nov " => 11,
+			"Dec " => 12,
+			_ => return None,
+		};
+		let day = match date[4..6].trim().parse::<u32>() {
+			Ok(d) if d >= 1 && d <= 31 => d,
+			_ => return None
+		};
+		if &date[9..10] != ":" || &date[12..13] != ":" {
+			return None;
+		}
+		match (date[7..9].parse::<u32>(), date[10..12].parse::<u32>(), date[13..15].parse::<u32>()) {
+			(Ok(h), Ok(m), Ok(s)) if h < 24 && m < 60 && s < 61 => Some((month, day, (h * 60 + m) * 60 + s)),
+			_ => None
+		}
+	}
+
+	// Seconds since the start of the year: syslog dates have no year, so
+	// February always has 28 days
+	pub fn timestamp(&self) -> Option<u32> {
+		const DAYS: [u32; 12] = [0, 31, 59, 90, 120, 151, 181, 212, 243, 273, 304, 334];
+		match Inner::date_fields(&self.raw) {
+			None => None,
+			Some((month, day, seconds)) => Some((DAYS[month as usize - 1] + day - 1) * 24 * 3600 + seconds)
+		}
+	}
+
+	// Seconds since the UNIX epoch, given the year and UTC offset (in
+	// seconds) that syslog leaves out
+	pub fn epoch(&self, year: i32, timezone: i32) -> Option<i64> {
+		Inner::epoch_of(&self.raw, year, timezone)
+	}
+
+	// Same as epoch, for a line not parsed yet
+	pub fn epoch_of(line: &str, year: i32, timezone: i32) -> Option<i64> {
+		let (month, day, seconds) = match Inner::date_fields(line) {
+			None => return None,
+			Some(fields) => fields
+		};
+		// Days since 1970-01-01, with years starting in March
+		let (y, m) = match month {
+			1 | 2 => (year as i64 - 1, month as i64 + 9),
+			_ => (year as i64, month as i64 - 3),
+		};
+		let era = if y >= 0 { y } else { y - 399 } / 400;
+		let year_of_era = y - era * 400;
+		let day_of_year = (153 * m + 2) / 5 + day as i64 - 1;
+		let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+		let days = era * 146097 + day_of_era - 719468;
+		Some(days * 24 * 3600 + seconds as i64 - timezone as i64)
+	}
+
+	// Seconds since the UNIX epoch in the year closest to time, for a line
+	// known to be less than half a year away from it
+	pub fn epoch_near(&self, time: i64, timezone: i32) -> Option<i64> {
+		let year = 1970 + ((time + timezone as i64) / (365 * 24 * 3600 + 6 * 3600)) as i32;
+		(year - 1..year + 2).filter_map(|y| self.epoch(y, timezone)).min_by_key(|t| (t - time).abs())
+	}
+
+	// Seconds from another timestamp to this line, negative if the line is
+	// older. Timestamps more than half a year apart are taken across the new
+	// year.
+	pub fn elapsed(&self, since: u32) -> Option<i64> {
+		const YEAR: i64 = 365 * 24 * 3600;
+		match self.timestamp() {
+			None => None,
+			Some(timestamp) => {
+				let diff = timestamp as i64 - since as i64;
+				Some(if diff < -YEAR / 2 { diff + YEAR } else if diff > YEAR / 2 { diff - YEAR } else { diff })
+			}
+		}
+	}
+
 	pub fn host<'a>(&'a self) -> &'a str {
 		&self.raw[DATE_LEN+1..self.host_e]
 	}
@@ -54,7 +161,8 @@ impl Inner {
 
 	pub fn parse(config: &ParserConfig, s: String) -> Result<Option<(Inner, usize)>, ParseError> {
 		let length = s.len();
-		if length < DATE_LEN + 2 {
+		// Multibyte characters would shorten the date
+		if length < DATE_LEN + 2 || !s.is_char_boundary(DATE_LEN) || !s.is_char_boundary(DATE_LEN + 1) {
 			return Err(ParseError::DateTooShort);
 		}
 		let (host_e, queue_s, queue_e, process, pid,
@@ -65,52 +173,98 @@ impl Inner {
 				Some(pos) => (DATE_LEN + 1 + pos, &rest[pos+1..])
 			};
 			let queue_s = host_e + 1;
-			for prog in config.process_noise.iter() {
-				if rest.starts_with(prog) {
-					return Ok(None);
-				}
+			if config.is_noise(rest) {
+				return Ok(None);
 			}
 			let pos = match rest.find(':') {
 				None => return Err(ParseError::MissingProcess),
 				Some(pos) => pos
 			};
-			let (queue_e, rest) = match rest.find('/') {
-				None => return Err(ParseError::NonEndingQueue),
-				Some(pos) => (queue_s + pos, &rest[pos+1..])
-			};
-			let process_len = match rest.find('[') {
-				None => return Err(ParseError::NonEndingProcess),
-				Some(len) => len
+			// Postfix logs as "queue/process[pid]:", other mail daemons only
+			// use their own syslog identifier, with or without a pid
+			let (standalone, queue_e, process_s, rest) = match rest[..pos].find('/') {
+				None => (true, queue_s, queue_s, rest),
+				Some(p) => (false, queue_s + p, queue_s + p + 1, &rest[p+1..])
 			};
-			let process = match &rest[..process_len] {
-				"anvil" => Process::Anvil,
-				"bounce" => Process::Bounce,
-				"cleanup" => Process::Cleanup,
-				"discard" => Process::Discard,
-				"error" => Process::Error,
-				"local" => Process::Local,
-				"pickup" => Process::Pickup,
-				"pipe" => Process::Pipe,
-				"qmgr" => Process::Qmgr,
-				"scache" => Process::Scache,
-				"smtp" => Process::Smtp,
-				"smtpd" => Process::Smtpd,
-				"smtpd.local" => Process::Smtpd,
-				_ => return Err(ParseError::UnknownProcess),
+			let (process_len, with_pid) = match rest.find('[') {
+				Some(len) if !standalone || process_s + len < queue_s + pos => (len, true),
+				_ if standalone => (queue_s + pos - process_s, false),
+				_ => return Err(ParseError::NonEndingProcess),
 			};
-			let rest = &rest[process_len+1..];
-			let process_end = queue_e + 1 + process_len;
-			let pid_e = pos - (process_end - queue_s) - 2;
-			if !rest[pid_e..].starts_with("]: ") {
-				return Err(ParseError::BadProcessID)
+			if !standalone && !config.known_instance(&s[queue_s..queue_e]) {
+				if config.strict {
+					return Err(ParseError::UnknownInstance);
+				}
+				return Ok(None);
 			}
-			let pid = match rest[..pid_e].parse::<u32>() {
-				Err(_) => return Err(ParseError::BadProcessID),
-				Ok(val) => val
+			let name = config.process_name(&rest[..process_len]);
+			let process = if standalone && config.parser(name).is_some() {
+				Process::Custom(name.to_string())
+			} else if standalone {
+				match name {
+					"amavis" => Process::Amavis,
+					"bogofilter" => Process::Bogofilter,
+					"clamsmtpd" => Process::Clamsmtpd,
+					"dovecot" => Process::Dovecot,
+					"opendkim" => Process::Opendkim,
+					"opendmarc" => Process::Opendmarc,
+					"policyd-spf" => Process::PolicydSpf,
+					"postgrey" => Process::Postgrey,
+					"postlicyd" => Process::Postlicyd,
+					"rspamd" => Process::Rspamd,
+					"spamd" => Process::Spamd,
+					_ if !config.strict => return Ok(None),
+					_ => return Err(ParseError::NonEndingQueue),
+				}
+			} else {
+				match name {
+					"anvil" => Process::Anvil,
+					"bounce" => Process::Bounce,
+					"cleanup" => Process::Cleanup,
+					"discard" => Process::Discard,
+					"error" => Process::Error,
+					"local" => Process::Local,
+					"pickup" => Process::Pickup,
+					"pipe" => Process::Pipe,
+					"postdrop" => Process::Postdrop,
+					"proxymap" => Process::Proxymap,
+					"qmgr" => Process::Qmgr,
+					"scache" => Process::Scache,
+					"sendmail" => Process::Sendmail,
+					"smtp" => Process::Smtp,
+					"smtpd" => Process::Smtpd,
+					"smtpd.local" => Process::Smtpd,
+					"trivial-rewrite" => Process::TrivialRewrite,
+					"verify" => Process::Verify,
+					_ if !config.strict => return Ok(None),
+					_ => return Err(ParseError::UnknownProcess),
+				}
+			};
+			let process_end = process_s + process_len;
+			let (pid, queue_id_s) = if with_pid {
+				let rest = &rest[process_len+1..];
+				if queue_s + pos < process_end + 2 {
+					return Err(ParseError::BadProcessID)
+				}
+				let pid_e = pos - (process_end - queue_s) - 2;
+				if !rest[pid_e..].starts_with("]: ") {
+					return Err(ParseError::BadProcessID)
+				}
+				let pid = match rest[..pid_e].parse::<u32>() {
+					Err(_) => return Err(ParseError::BadProcessID),
+					Ok(val) => val
+				};
+				(pid, process_end + 1 + pid_e + 3)
+			} else {
+				if !rest[process_len..].starts_with(": ") {
+					return Err(ParseError::BadProcessID)
+				}
+				(0, process_end + 2)
 			};
-			let queue_id_s = process_end + 1 + pid_e + 3;
-			let rest = &rest[pid_e + 3..];
+			let rest = &s[queue_id_s..];
 			let queue_id_e = match rest.find(':') {
+				// clamsmtpd prefixes its lines with its own session ID
+				_ if process == Process::Clamsmtpd => 0,
 				None => 0,
 				Some(pos) => {
 					let len = pos;
@@ -192,7 +346,7 @@ mod tests {
 	}
 
 	fn conf() -> ParserConfig {
-		ParserConfig { process_noise: vec!["clamsmtpd".to_string()] }
+		ParserConfig::new(vec!["clamsmtpd".to_string()])
 	}
 
 	#[test]
@@ -207,6 +361,11 @@ mod tests {
 			Err(x) => panic!("Wrong Error (should have been DateTooShort): {}", x),
 			_ => panic!("Should have failed")
 		}
+		match Inner::parse(&conf(), "Sep  3 00:00:0éyuuai postfix-in/cleanup[31247]: 12C172090B:".to_string()) {
+			Err(ParseError::DateTooShort) => (),
+			Err(x) => panic!("Wrong Error (should have been DateTooShort): {}", x),
+			_ => panic!("Should have failed")
+		}
 	}
 
 	#[test]
@@ -239,6 +398,19 @@ mod tests {
 			Err(x) => panic!("Wrong Error (Should have been ignored): {}", x),
 			_ => panic!("Should have been ignored")
 		}
+		let conf = ParserConfig::new(vec!["clam".to_string()]);
+		match Inner::parse(&conf, "Sep  3 00:00:03 yuuai clamav-milter[42]:".to_string()) {
+			Err(ParseError::NonEndingQueue) => (),
+			Err(x) => panic!("Wrong Error (should have been NonEndingQueue): {}", x),
+			_ => panic!("Should not have been ignored")
+		}
+		assert_eq!(conf.noise()[0].dropped(), 0);
+		match Inner::parse(&conf, "Sep  3 00:00:03 yuuai clam[42]:".to_string()) {
+			Ok(None) => (),
+			Err(x) => panic!("Wrong Error (Should have been ignored): {}", x),
+			_ => panic!("Should have been ignored")
+		}
+		assert_eq!(conf.noise()[0].dropped(), 1);
 	}
 	#[test]
 	fn non_ending_queue(){
@@ -267,6 +439,116 @@ mod tests {
 		}
 	}
 
+	#[test]
+	fn timestamp() {
+		assert_eq!(init().timestamp(), Some(((243 + 2) * 24) * 3600 + 3));
+		let (inner, _) = match Inner::parse(&conf(), "Jan 31 23:59:59 yuuai postfix-in/cleanup[31247]: 12C172090B:".to_string()) {
+			Err(x) => panic!("Failed to parse: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(inner)) => inner
+		};
+		assert_eq!(inner.timestamp(), Some(31 * 24 * 3600 - 1));
+		let (inner, _) = match Inner::parse(&conf(), "Foo 31 23:59:59 yuuai postfix-in/cleanup[31247]: 12C172090B:".to_string()) {
+			Err(x) => panic!("Failed to parse: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(inner)) => inner
+		};
+		assert_eq!(inner.timestamp(), None);
+	}
+
+	#[test]
+	fn non_ascii_date() {
+		let (inner, _) = match Inner::parse(&conf(), "Aug  4 0é0:011 yuuai postfix/cleanup[1]: 84ED020916: message-id=<a>".to_string()) {
+			Err(x) => panic!("Failed to parse: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(inner)) => inner
+		};
+		assert_eq!(inner.timestamp(), None);
+		assert_eq!(inner.epoch(2015, 0), None);
+	}
+
+	#[test]
+	fn elapsed() {
+		let sep3 = ((243 + 2) * 24) * 3600;
+		assert_eq!(init().elapsed(sep3), Some(3));
+		assert_eq!(init().elapsed(sep3 + 5), Some(-2));
+		let (inner, _) = match Inner::parse(&conf(), "Jan  1 00:00:01 yuuai postfix-in/cleanup[31247]: 12C172090B:".to_string()) {
+			Err(x) => panic!("Failed to parse: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(inner)) => inner
+		};
+		assert_eq!(inner.elapsed(365 * 24 * 3600 - 1), Some(2));
+	}
+
+	#[test]
+	fn epoch() {
+		// 2015-09-03T00:00:03+02:00
+		assert_eq!(init().epoch(2015, 7200), Some(1441231203));
+		let (inner, _) = match Inner::parse(&conf(), "Mar  1 12:00:00 yuuai postfix-in/cleanup[31247]: 12C172090B:".to_string()) {
+			Err(x) => panic!("Failed to parse: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(inner)) => inner
+		};
+		assert_eq!(inner.epoch(2016, 0), Some(1456833600));
+		assert_eq!(inner.epoch(2015, 0), Some(1456833600 - 366 * 24 * 3600));
+		assert_eq!(inner.epoch(1969, 0), Some(-306 * 24 * 3600 + 12 * 3600));
+	}
+
+	#[test]
+	fn lenient() {
+		let mut conf = conf();
+		conf.strict = false;
+		match Inner::parse(&conf, "Sep  3 00:00:03 yuuai postfix-in/xxx[42]: 12C172090B:".to_string()) {
+			Ok(None) => (),
+			Err(x) => panic!("Wrong Error (Should have been ignored): {}", x),
+			_ => panic!("Should have been ignored")
+		}
+		match Inner::parse(&conf, "Sep  3 00:00:03 yuuai xxx[42]: started".to_string()) {
+			Ok(None) => (),
+			Err(x) => panic!("Wrong Error (Should have been ignored): {}", x),
+			_ => panic!("Should have been ignored")
+		}
+	}
+
+	#[test]
+	fn instances() {
+		let mut conf = conf();
+		conf.add_instance("postfix-out");
+		match Inner::parse(&conf, "Sep  3 00:00:03 yuuai postfix-in/cleanup[31247]: 12C172090B:".to_string()) {
+			Err(ParseError::UnknownInstance) => (),
+			Err(x) => panic!("Wrong Error (should have been UnknownInstance): {}", x),
+			_ => panic!("Should have failed")
+		}
+		conf.strict = false;
+		match Inner::parse(&conf, "Sep  3 00:00:03 yuuai postfix-in/cleanup[31247]: 12C172090B:".to_string()) {
+			Ok(None) => (),
+			Err(x) => panic!("Wrong Error (Should have been ignored): {}", x),
+			_ => panic!("Should have been ignored")
+		}
+		match Inner::parse(&conf, "Sep  3 00:00:03 yuuai postfix-out/cleanup[31247]: 12C172090B:".to_string()) {
+			Ok(Some(_)) => (),
+			Err(x) => panic!("Failed to parse: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+		}
+	}
+
+	#[test]
+	fn aliases() {
+		let mut conf = conf();
+		conf.alias("smtp-amavis", "smtp");
+		conf.alias("policyd", "postlicyd");
+		match Inner::parse(&conf, "Sep  3 00:00:03 yuuai postfix-in/smtp-amavis[42]: 12C172090B:".to_string()) {
+			Ok(Some((inner, _))) => assert_eq!(inner.process, Process::Smtp),
+			Err(x) => panic!("Failed to parse: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+		}
+		match Inner::parse(&conf, "Sep  3 00:00:03 yuuai policyd[42]: request".to_string()) {
+			Ok(Some((inner, _))) => assert_eq!(inner.process, Process::Postlicyd),
+			Err(x) => panic!("Failed to parse: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+		}
+	}
+
 	#[test]
 	fn bad_pid(){
 		match Inner::parse(&conf(), "Sep  3 00:00:03 yuuai postfix-in/cleanup[31247:".to_string()) {
@@ -341,6 +623,34 @@ mod tests {
 		assert_eq!(fmt::format(format_args!("{:?}", parsed)), "Inner { raw: \"Sep  3 00:00:03 yuuai postfix-in/cleanup[31247]: 12C172090B:\", host_e: 21, queue_s: 22, queue_e: 32, process: Cleanup, pid: 31247, queue_id_s: 49, queue_id_e: 59 }");
 	}
 
+	#[test]
+	fn standalone() {
+		let (inner, end) = match Inner::parse(&conf(), "Sep  3 00:00:03 yuuai bogofilter[4242]: X-Bogosity: Ham".to_string()) {
+			Err(x) => panic!("Failed to parse: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(inner)) => inner
+		};
+		assert_eq!(inner.host(), "yuuai");
+		assert_eq!(inner.queue(), "");
+		assert_eq!(inner.process, Process::Bogofilter);
+		assert_eq!(inner.pid, 4242);
+		assert_eq!(inner.queue_id(), None);
+		assert_eq!(end, 39);
+		let (inner, end) = match Inner::parse(&conf(), "Sep  3 00:00:03 yuuai bogofilter: X-Bogosity: Ham".to_string()) {
+			Err(x) => panic!("Failed to parse: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(inner)) => inner
+		};
+		assert_eq!(inner.process, Process::Bogofilter);
+		assert_eq!(inner.pid, 0);
+		assert_eq!(end, 33);
+		match Inner::parse(&conf(), "Sep  3 00:00:03 yuuai bogofilter[:".to_string()) {
+			Err(ParseError::BadProcessID) => (),
+			Err(x) => panic!("Wrong Error (should have been BadProcessID): {}", x),
+			_ => panic!("Should have failed")
+		}
+	}
+
 	#[test]
 	fn ignore() {
 		match Inner::parse(&conf(), "Sep  3 00:00:03 yuuai clamsmtpd:".to_string()) {
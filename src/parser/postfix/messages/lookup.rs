@@ -0,0 +1,241 @@
+use std::fmt;
+use std::ops::Deref;
+use super::super::ParseError;
+use super::Inner;
+use super::Message;
+use super::MessageParser;
+
+pub enum LookupErrorKind {
+	LookupError,
+	TableProblem,
+	Unavailable,
+	Failure,
+}
+
+#[derive(Debug)]
+pub struct LookupError {
+	inner: Inner,
+	pub kind: LookupErrorKind,
+	parameter_s: usize,
+	parameter_e: usize,
+	table_s: usize,
+	table_e: usize,
+	key_s: usize,
+	key_e: usize,
+	message_s: usize,
+	message_e: usize,
+}
+
+impl Deref for LookupError {
+	type Target = Inner;
+	fn deref(&self) -> &Inner {
+		&self.inner
+	}
+}
+
+impl fmt::Display for LookupErrorKind {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		let kind = match self {
+			&LookupErrorKind::LookupError => "LookupError",
+			&LookupErrorKind::TableProblem => "TableProblem",
+			&LookupErrorKind::Unavailable => "Unavailable",
+			&LookupErrorKind::Failure => "Failure",
+		};
+		write!(fmt, "{}", kind)
+	}
+}
+
+impl fmt::Debug for LookupErrorKind {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		fmt::Display::fmt(self, fmt)
+	}
+}
+
+impl LookupError {
+	pub fn parameter <'a>(&'a self) -> Option<&'a str> {
+		if self.parameter_e != 0 {
+			Some(&self.raw[self.parameter_s..self.parameter_e])
+		} else {
+			None
+		}
+	}
+
+	pub fn table <'a>(&'a self) -> Option<&'a str> {
+		if self.table_e != 0 {
+			Some(&self.raw[self.table_s..self.table_e])
+		} else {
+			None
+		}
+	}
+
+	pub fn key <'a>(&'a self) -> Option<&'a str> {
+		if self.key_e != 0 {
+			Some(&self.raw[self.key_s..self.key_e])
+		} else {
+			None
+		}
+	}
+
+	pub fn message <'a>(&'a self) -> &'a str {
+		&self.raw[self.message_s..self.message_e]
+	}
+}
+
+impl MessageParser for LookupError {
+	fn parse(inner: Inner, start: usize) -> Result<Option<Message>, ParseError> {
+		let (kind, parameter_s, parameter_e, table_s, table_e, key_s, key_e, message_s, message_e) = {
+			let rest = &inner.raw[start..];
+			if !rest.starts_with(" warning: ") {
+				return Ok(None);
+			}
+			let rest = &rest[10..];
+			let start = start + 10;
+			let len = rest.len();
+			if let Some(p) = rest.find(" lookup error for \"") {
+				let key_s = p + 19;
+				let key_e = match rest[key_s..].rfind('"') {
+					None => return Err(ParseError::LookupBadKey),
+					Some(l) => key_s + l
+				};
+				(LookupErrorKind::LookupError, 0, 0, start, start + p, start + key_s, start + key_e, start, start + len)
+			} else if rest.ends_with(": table lookup problem") {
+				let table = &rest[..len - 22];
+				match table.find(": ") {
+					None => (LookupErrorKind::TableProblem, 0, 0, start, start + table.len(), 0, 0, start, start + len),
+					Some(p) => (LookupErrorKind::TableProblem, start, start + p, start + p + 2, start + table.len(), 0, 0, start, start + len)
+				}
+			} else if let Some(p) = rest.find(" is unavailable. ") {
+				(LookupErrorKind::Unavailable, 0, 0, start, start + p, 0, 0, start + p + 17, start + len)
+			} else if rest.ends_with(" lookup failure") && !rest[..len - 15].contains(' ') {
+				(LookupErrorKind::Failure, start, start + len - 15, 0, 0, 0, 0, start, start + len)
+			} else {
+				return Ok(None);
+			}
+		};
+		Ok(Some(Message::LookupError { m: LookupError { inner: inner, kind: kind, parameter_s: parameter_s, parameter_e: parameter_e, table_s: table_s, table_e: table_e, key_s: key_s, key_e: key_e, message_s: message_s, message_e: message_e } }))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fmt;
+	use super::*;
+	use super::super::Inner;
+	use super::super::Message;
+	use super::super::MessageParser;
+	use super::super::Process;
+	use super::super::super::ParserConfig;
+	use super::super::super::ParseError;
+
+	fn parse_lookup(s: String) -> Result<Option<Message>, ParseError> {
+		let conf = ParserConfig::new(vec!["clamsmtpd".to_string()]);
+		let (inner, start) = match Inner::parse(&conf, s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some((x,y))) => (x,y)
+		};
+		LookupError::parse(inner, start)
+	}
+
+	#[test]
+	fn ignored() {
+		let s = "Aug  4 00:00:03 yuuai postfix/proxymap[4242]: warning: request for unapproved table: \"hash:/etc/aliases\"".to_string();
+		match parse_lookup(s) {
+			Err(x) => panic!("Failed to parse {}", x),
+			Ok(None) => (),
+			Ok(_) => panic!("This should have been ignored"),
+		};
+		let s = "Aug  4 00:00:03 yuuai postfix/verify[4242]: cache btree:/var/lib/postfix/verify_cache full cleanup: retained=2 dropped=0 entries".to_string();
+		match parse_lookup(s) {
+			Err(x) => panic!("Failed to parse {}", x),
+			Ok(None) => (),
+			Ok(_) => panic!("This should have been ignored"),
+		};
+	}
+
+	#[test]
+	fn bad_key() {
+		let s = "Aug  4 00:00:03 yuuai postfix/trivial-rewrite[4242]: warning: hash:/etc/postfix/transport lookup error for \"example.com".to_string();
+		match parse_lookup(s) {
+			Err(ParseError::LookupBadKey) => (),
+			Err(x) => panic!("Wrong error, should have been LookupBadKey {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(_) => panic!("This should have failed")
+		};
+	}
+
+	#[test]
+	fn valid_lookup_error() {
+		let s = "Aug  4 00:00:03 yuuai postfix/trivial-rewrite[4242]: warning: hash:/etc/postfix/transport lookup error for \"example.com\"".to_string();
+		let lookup = match parse_lookup(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::LookupError{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		assert_eq!(lookup.process, Process::TrivialRewrite);
+		match lookup.kind {
+			LookupErrorKind::LookupError => (),
+			x => panic!("Parsed wrong kind: {}", x)
+		}
+		assert_eq!(lookup.parameter(), None);
+		assert_eq!(lookup.table(), Some("hash:/etc/postfix/transport"));
+		assert_eq!(lookup.key(), Some("example.com"));
+		assert_eq!(lookup.message(), "hash:/etc/postfix/transport lookup error for \"example.com\"");
+		assert_eq!(fmt::format(format_args!("{:?}", lookup)), "LookupError { inner: Inner { raw: \"Aug  4 00:00:03 yuuai postfix/trivial-rewrite[4242]: warning: hash:/etc/postfix/transport lookup error for \\\"example.com\\\"\", host_e: 21, queue_s: 22, queue_e: 29, process: TrivialRewrite, pid: 4242, queue_id_s: 0, queue_id_e: 0 }, kind: LookupError, parameter_s: 0, parameter_e: 0, table_s: 62, table_e: 89, key_s: 108, key_e: 119, message_s: 62, message_e: 120 }");
+	}
+
+	#[test]
+	fn valid_table_problem() {
+		let s = "Aug  4 00:00:03 yuuai postfix/proxymap[4242]: warning: virtual_alias_maps: proxy:mysql:/etc/postfix/mysql-virtual.cf: table lookup problem".to_string();
+		let lookup = match parse_lookup(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::LookupError{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		assert_eq!(lookup.process, Process::Proxymap);
+		match lookup.kind {
+			LookupErrorKind::TableProblem => (),
+			x => panic!("Parsed wrong kind: {}", x)
+		}
+		assert_eq!(lookup.parameter(), Some("virtual_alias_maps"));
+		assert_eq!(lookup.table(), Some("proxy:mysql:/etc/postfix/mysql-virtual.cf"));
+		assert_eq!(lookup.key(), None);
+	}
+
+	#[test]
+	fn valid_unavailable() {
+		let s = "Aug  4 00:00:03 yuuai postfix/verify[4242]: warning: btree:/var/lib/postfix/verify_cache is unavailable. open database /var/lib/postfix/verify_cache.db: Permission denied".to_string();
+		let lookup = match parse_lookup(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::LookupError{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		assert_eq!(lookup.process, Process::Verify);
+		match lookup.kind {
+			LookupErrorKind::Unavailable => (),
+			x => panic!("Parsed wrong kind: {}", x)
+		}
+		assert_eq!(lookup.table(), Some("btree:/var/lib/postfix/verify_cache"));
+		assert_eq!(lookup.message(), "open database /var/lib/postfix/verify_cache.db: Permission denied");
+	}
+
+	#[test]
+	fn valid_failure() {
+		let s = "Aug  4 00:00:03 yuuai postfix/trivial-rewrite[4242]: warning: transport_maps lookup failure".to_string();
+		let lookup = match parse_lookup(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::LookupError{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		match lookup.kind {
+			LookupErrorKind::Failure => (),
+			x => panic!("Parsed wrong kind: {}", x)
+		}
+		assert_eq!(lookup.parameter(), Some("transport_maps"));
+		assert_eq!(lookup.table(), None);
+	}
+}
@@ -1,45 +1,147 @@
 mod inner;
+mod amavis;
+mod bogofilter;
 mod bounce;
+mod clamsmtpd;
 mod cleanup;
+mod custom;
+mod dovecot;
 mod pickup;
 mod forward;
+mod lookup;
+mod opendkim;
+mod opendmarc;
+mod policy;
 mod qmgr;
 mod reject;
+mod rspamd;
+mod spam;
+mod spamd;
 mod smtpd;
+mod submission;
 
 pub use self::inner::Process;
 pub use self::inner::Inner;
+pub use self::amavis::Amavis;
+pub use self::amavis::AmavisAction;
+pub use self::amavis::AmavisVerdict;
+pub use self::bogofilter::Bogofilter;
+pub use self::bogofilter::BogofilterLink;
+pub use self::bogofilter::BogofilterLinker;
+pub use self::bogofilter::BogofilterRegistration;
+pub use self::bogofilter::BogofilterVerdict;
 pub use self::bounce::Bounce;
+pub use self::bounce::BounceKind;
+pub use self::clamsmtpd::Clamsmtpd;
+pub use self::clamsmtpd::ClamsmtpdLink;
+pub use self::clamsmtpd::ClamsmtpdLinker;
+pub use self::clamsmtpd::ClamsmtpdStatus;
 pub use self::cleanup::Cleanup;
+pub use self::cleanup::CleanupAction;
+pub use self::cleanup::CleanupActionKind;
+pub use self::cleanup::CleanupHeaders;
+pub use self::custom::Custom;
+pub use self::dovecot::DovecotAuthFailure;
+pub use self::dovecot::DovecotDelivery;
 pub use self::pickup::Pickup;
 pub use self::qmgr::Qmgr;
 pub use self::qmgr::QmgrRemoved;
 pub use self::qmgr::QmgrExpired;
 pub use self::forward::Forward;
 pub use self::forward::ForwardError;
+pub use self::forward::ForwardVerify;
+pub use self::forward::VerifyStatus;
+pub use self::lookup::LookupError;
+pub use self::lookup::LookupErrorKind;
+pub use self::opendkim::Opendkim;
+pub use self::opendkim::OpendkimResult;
+pub use self::opendmarc::Opendmarc;
+pub use self::opendmarc::OpendmarcResult;
+pub use self::policy::Policy;
 pub use self::reject::Reject;
 pub use self::reject::RejectReason;
 pub use self::reject::RejectProto;
+pub use self::rspamd::Rspamd;
+pub use self::spam::SpamAction;
+pub use self::spam::SpamVerdict;
+pub use self::spamd::Spamd;
 pub use self::smtpd::Smtpd;
+pub use self::smtpd::SmtpdConnect;
+pub use self::smtpd::SmtpdDisconnect;
 pub use self::smtpd::SmtpdForward;
 pub use self::smtpd::SmtpdLogin;
+pub use self::submission::Submission;
+pub use self::submission::SubmissionLevel;
 
 use super::ParseError;
 
 #[derive(Debug)]
 pub enum Message {
+	Amavis { m: Amavis },
+	Bogofilter { m: Bogofilter },
 	Bounce { m: Bounce },
+	Clamsmtpd { m: Clamsmtpd },
 	Cleanup { m: Cleanup },
+	CleanupAction { m: CleanupAction },
+	Custom { m: Custom },
+	DovecotAuthFailure { m: DovecotAuthFailure },
+	DovecotDelivery { m: DovecotDelivery },
         Pickup { m: Pickup },
 	Qmgr { m: Qmgr},
 	QmgrRemoved { m: QmgrRemoved },
 	QmgrExpired { m: QmgrExpired },
         Forward { m: Forward },
         ForwardError { m: ForwardError },
+	ForwardVerify { m: ForwardVerify },
+	LookupError { m: LookupError },
+	Opendkim { m: Opendkim },
+	Opendmarc { m: Opendmarc },
+	Policy { m: Policy },
 	Reject { m: Reject },
+	Rspamd { m: Rspamd },
+	Spamd { m: Spamd },
 	Smtpd { m: Smtpd },
+	SmtpdConnect { m: SmtpdConnect },
+	SmtpdDisconnect { m: SmtpdDisconnect },
 	SmtpdForward { m: SmtpdForward },
 	SmtpdLogin { m: SmtpdLogin },
+	Submission { m: Submission },
+}
+
+impl Message {
+	pub fn inner(&self) -> &Inner {
+		match self {
+			&Message::Amavis { ref m } => m,
+			&Message::Bogofilter { ref m } => m,
+			&Message::Bounce { ref m } => m,
+			&Message::Clamsmtpd { ref m } => m,
+			&Message::Cleanup { ref m } => m,
+			&Message::CleanupAction { ref m } => m,
+			&Message::Custom { ref m } => m,
+			&Message::DovecotAuthFailure { ref m } => m,
+			&Message::DovecotDelivery { ref m } => m,
+			&Message::Pickup { ref m } => m,
+			&Message::Qmgr { ref m } => m,
+			&Message::QmgrRemoved { ref m } => m,
+			&Message::QmgrExpired { ref m } => m,
+			&Message::Forward { ref m } => m,
+			&Message::ForwardError { ref m } => m,
+			&Message::ForwardVerify { ref m } => m,
+			&Message::LookupError { ref m } => m,
+			&Message::Opendkim { ref m } => m,
+			&Message::Opendmarc { ref m } => m,
+			&Message::Policy { ref m } => m,
+			&Message::Reject { ref m } => m,
+			&Message::Rspamd { ref m } => m,
+			&Message::Spamd { ref m } => m,
+			&Message::Smtpd { ref m } => m,
+			&Message::SmtpdConnect { ref m } => m,
+			&Message::SmtpdDisconnect { ref m } => m,
+			&Message::SmtpdForward { ref m } => m,
+			&Message::SmtpdLogin { ref m } => m,
+			&Message::Submission { ref m } => m,
+		}
+	}
 }
 
 pub trait MessageParser {
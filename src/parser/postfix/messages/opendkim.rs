@@ -0,0 +1,232 @@
+use std::fmt;
+use std::ops::Deref;
+use super::super::ParseError;
+use super::Inner;
+use super::Message;
+use super::MessageParser;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum OpendkimResult {
+	Signed,
+	Verified,
+	NoSignature,
+	BadSignature,
+	KeyRetrievalFailed,
+	Error,
+}
+
+#[derive(Debug)]
+pub struct Opendkim {
+	inner: Inner,
+	pub result: OpendkimResult,
+	selector_s: usize,
+	selector_e: usize,
+	domain_s: usize,
+	domain_e: usize,
+	message_s: usize,
+	message_e: usize,
+}
+
+impl Deref for Opendkim {
+	type Target = Inner;
+	fn deref(&self) -> &Inner {
+		&self.inner
+	}
+}
+
+impl fmt::Display for OpendkimResult {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		let result = match self {
+			&OpendkimResult::Signed => "Signed",
+			&OpendkimResult::Verified => "Verified",
+			&OpendkimResult::NoSignature => "NoSignature",
+			&OpendkimResult::BadSignature => "BadSignature",
+			&OpendkimResult::KeyRetrievalFailed => "KeyRetrievalFailed",
+			&OpendkimResult::Error => "Error",
+		};
+		write!(fmt, "{}", result)
+	}
+}
+
+impl fmt::Debug for OpendkimResult {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		fmt::Display::fmt(self, fmt)
+	}
+}
+
+impl Opendkim {
+	fn optional <'a>(&'a self, s: usize, e: usize) -> Option<&'a str> {
+		if e != 0 {
+			Some(&self.raw[s..e])
+		} else {
+			None
+		}
+	}
+
+	pub fn selector <'a>(&'a self) -> Option<&'a str> {
+		self.optional(self.selector_s, self.selector_e)
+	}
+
+	pub fn domain <'a>(&'a self) -> Option<&'a str> {
+		self.optional(self.domain_s, self.domain_e)
+	}
+
+	pub fn message <'a>(&'a self) -> Option<&'a str> {
+		self.optional(self.message_s, self.message_e)
+	}
+}
+
+// Parses "(s=selector, d=domain)", returning the selector and domain offsets
+fn parse_signature(raw: &str, start: usize) -> Result<(usize, usize, usize, usize, usize), ParseError> {
+	let rest = &raw[start..];
+	if !rest.starts_with("(s=") {
+		return Err(ParseError::OpendkimBadSignature);
+	}
+	let selector_e = match rest.find(", d=") {
+		None => return Err(ParseError::OpendkimBadSignature),
+		Some(p) => p
+	};
+	let domain_e = match rest.find(')') {
+		Some(p) if p > selector_e => p,
+		_ => return Err(ParseError::OpendkimBadSignature),
+	};
+	Ok((start + 3, start + selector_e, start + selector_e + 4, start + domain_e, start + domain_e + 1))
+}
+
+impl MessageParser for Opendkim {
+	fn parse(inner: Inner, start: usize) -> Result<Option<Message>, ParseError> {
+		if inner.queue_id().is_none() {
+			return Ok(None);
+		}
+		let (result, selector_s, selector_e, domain_s, domain_e, message_s, message_e) = {
+			let raw = &inner.raw;
+			let start = start + 1;
+			let rest = &raw[start..];
+			let len = raw.len();
+			if rest.starts_with("DKIM-Signature field added ") {
+				let (selector_s, selector_e, domain_s, domain_e, _) = match parse_signature(raw, start + 27) {
+					Err(x) => return Err(x),
+					Ok(x) => x
+				};
+				(OpendkimResult::Signed, selector_s, selector_e, domain_s, domain_e, 0, 0)
+			} else if rest == "DKIM verification successful" {
+				(OpendkimResult::Verified, 0, 0, 0, 0, 0, 0)
+			} else if rest == "no signature data" {
+				(OpendkimResult::NoSignature, 0, 0, 0, 0, 0, 0)
+			} else if rest == "bad signature data" {
+				(OpendkimResult::BadSignature, 0, 0, 0, 0, 0, 0)
+			} else if rest.starts_with("key retrieval failed ") {
+				let (selector_s, selector_e, domain_s, domain_e, end) = match parse_signature(raw, start + 21) {
+					Err(x) => return Err(x),
+					Ok(x) => x
+				};
+				if raw[end..].starts_with(": ") {
+					(OpendkimResult::KeyRetrievalFailed, selector_s, selector_e, domain_s, domain_e, end + 2, len)
+				} else {
+					(OpendkimResult::KeyRetrievalFailed, selector_s, selector_e, domain_s, domain_e, 0, 0)
+				}
+			} else if rest.starts_with("s=") {
+				// s=selector d=domain followed by the verification error
+				let selector_e = match rest.find(" d=") {
+					None => return Err(ParseError::OpendkimBadSignature),
+					Some(p) => start + p
+				};
+				let domain_e = match raw[selector_e + 3..].find(' ') {
+					None => return Err(ParseError::OpendkimBadSignature),
+					Some(p) => selector_e + 3 + p
+				};
+				(OpendkimResult::Error, start + 2, selector_e, selector_e + 3, domain_e, domain_e + 1, len)
+			} else {
+				return Ok(None);
+			}
+		};
+		Ok(Some(Message::Opendkim { m: Opendkim { inner: inner, result: result, selector_s: selector_s, selector_e: selector_e, domain_s: domain_s, domain_e: domain_e, message_s: message_s, message_e: message_e } }))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fmt;
+	use super::*;
+	use super::super::Inner;
+	use super::super::Message;
+	use super::super::MessageParser;
+	use super::super::super::ParserConfig;
+	use super::super::super::ParseError;
+
+	fn parse_opendkim(s: String) -> Result<Option<Message>, ParseError> {
+		let conf = ParserConfig::new(vec!["clamsmtpd".to_string()]);
+		let (inner, start) = match Inner::parse(&conf, s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some((x,y))) => (x,y)
+		};
+		Opendkim::parse(inner, start)
+	}
+
+	fn parse_valid(s: &str) -> Opendkim {
+		match parse_opendkim(s.to_string()) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::Opendkim{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		}
+	}
+
+	#[test]
+	fn ignored() {
+		let s = "Aug  4 00:00:08 yuuai opendkim[2841]: OpenDKIM Filter v2.10.3 starting (args: -x /etc/opendkim.conf)".to_string();
+		match parse_opendkim(s) {
+			Err(x) => panic!("Failed to parse {}", x),
+			Ok(None) => (),
+			Ok(_) => panic!("This should have been ignored"),
+		};
+		let s = "Aug  4 00:00:08 yuuai opendkim[2841]: 84ED020916: mx1.example.com [1.2.3.4] not internal".to_string();
+		match parse_opendkim(s) {
+			Err(x) => panic!("Failed to parse {}", x),
+			Ok(None) => (),
+			Ok(_) => panic!("This should have been ignored"),
+		};
+	}
+
+	#[test]
+	fn bad_signature() {
+		let s = "Aug  4 00:00:08 yuuai opendkim[2841]: 84ED020916: DKIM-Signature field added (s=sel)".to_string();
+		match parse_opendkim(s) {
+			Err(ParseError::OpendkimBadSignature) => (),
+			Err(x) => panic!("Wrong error, should have been OpendkimBadSignature {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(_) => panic!("This should have failed")
+		};
+	}
+
+	#[test]
+	fn valid_signed() {
+		let dkim = parse_valid("Aug  4 00:00:08 yuuai opendkim[2841]: 84ED020916: DKIM-Signature field added (s=mail, d=example.com)");
+		assert_eq!(dkim.queue_id(), Some("84ED020916"));
+		assert_eq!(dkim.result, OpendkimResult::Signed);
+		assert_eq!(dkim.selector(), Some("mail"));
+		assert_eq!(dkim.domain(), Some("example.com"));
+		assert_eq!(dkim.message(), None);
+		assert_eq!(fmt::format(format_args!("{:?}", dkim)), "Opendkim { inner: Inner { raw: \"Aug  4 00:00:08 yuuai opendkim[2841]: 84ED020916: DKIM-Signature field added (s=mail, d=example.com)\", host_e: 21, queue_s: 22, queue_e: 22, process: Opendkim, pid: 2841, queue_id_s: 38, queue_id_e: 48 }, result: Signed, selector_s: 80, selector_e: 84, domain_s: 88, domain_e: 99, message_s: 0, message_e: 0 }");
+	}
+
+	#[test]
+	fn valid_verification() {
+		let dkim = parse_valid("Aug  4 00:00:08 yuuai opendkim[2841]: 84ED020916: DKIM verification successful");
+		assert_eq!(dkim.result, OpendkimResult::Verified);
+		assert_eq!(dkim.domain(), None);
+		let dkim = parse_valid("Aug  4 00:00:08 yuuai opendkim[2841]: 84ED020916: bad signature data");
+		assert_eq!(dkim.result, OpendkimResult::BadSignature);
+		let dkim = parse_valid("Aug  4 00:00:08 yuuai opendkim[2841]: 84ED020916: s=mail d=example.com SSL error:04091068:rsa routines:INT_RSA_VERIFY:bad signature");
+		assert_eq!(dkim.result, OpendkimResult::Error);
+		assert_eq!(dkim.selector(), Some("mail"));
+		assert_eq!(dkim.domain(), Some("example.com"));
+		assert_eq!(dkim.message(), Some("SSL error:04091068:rsa routines:INT_RSA_VERIFY:bad signature"));
+		let dkim = parse_valid("Aug  4 00:00:08 yuuai opendkim[2841]: 84ED020916: key retrieval failed (s=mail, d=example.com): 'mail._domainkey.example.com' record not found");
+		assert_eq!(dkim.result, OpendkimResult::KeyRetrievalFailed);
+		assert_eq!(dkim.selector(), Some("mail"));
+		assert_eq!(dkim.domain(), Some("example.com"));
+		assert_eq!(dkim.message(), Some("'mail._domainkey.example.com' record not found"));
+	}
+}
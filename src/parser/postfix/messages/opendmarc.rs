@@ -0,0 +1,152 @@
+use std::fmt;
+use std::ops::Deref;
+use super::super::ParseError;
+use super::Inner;
+use super::Message;
+use super::MessageParser;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum OpendmarcResult {
+	Pass,
+	Fail,
+	None,
+	BestGuessPass,
+	TempError,
+	PermError,
+}
+
+#[derive(Debug)]
+pub struct Opendmarc {
+	inner: Inner,
+	domain_s: usize,
+	domain_e: usize,
+	pub result: OpendmarcResult,
+}
+
+impl Deref for Opendmarc {
+	type Target = Inner;
+	fn deref(&self) -> &Inner {
+		&self.inner
+	}
+}
+
+impl fmt::Display for OpendmarcResult {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		let result = match self {
+			&OpendmarcResult::Pass => "pass",
+			&OpendmarcResult::Fail => "fail",
+			&OpendmarcResult::None => "none",
+			&OpendmarcResult::BestGuessPass => "bestguesspass",
+			&OpendmarcResult::TempError => "temperror",
+			&OpendmarcResult::PermError => "permerror",
+		};
+		write!(fmt, "{}", result)
+	}
+}
+
+impl fmt::Debug for OpendmarcResult {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		fmt::Display::fmt(self, fmt)
+	}
+}
+
+impl Opendmarc {
+	pub fn domain <'a>(&'a self) -> &'a str {
+		&self.raw[self.domain_s..self.domain_e]
+	}
+}
+
+impl MessageParser for Opendmarc {
+	fn parse(inner: Inner, start: usize) -> Result<Option<Message>, ParseError> {
+		if inner.queue_id().is_none() {
+			return Ok(None);
+		}
+		let (domain_s, domain_e, result) = {
+			// The result line is the only one made of exactly two words
+			let domain_s = start + 1;
+			let rest = &inner.raw[domain_s..];
+			let len = match rest.find(' ') {
+				None => return Ok(None),
+				Some(l) => l
+			};
+			let result = &rest[len + 1..];
+			if result.contains(' ') {
+				return Ok(None);
+			}
+			let result = match result {
+				"pass" => OpendmarcResult::Pass,
+				"fail" => OpendmarcResult::Fail,
+				"none" => OpendmarcResult::None,
+				"bestguesspass" => OpendmarcResult::BestGuessPass,
+				"temperror" => OpendmarcResult::TempError,
+				"permerror" => OpendmarcResult::PermError,
+				_ => return Err(ParseError::OpendmarcUnknownResult),
+			};
+			(domain_s, domain_s + len, result)
+		};
+		Ok(Some(Message::Opendmarc { m: Opendmarc { inner: inner, domain_s: domain_s, domain_e: domain_e, result: result } }))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fmt;
+	use super::*;
+	use super::super::Inner;
+	use super::super::Message;
+	use super::super::MessageParser;
+	use super::super::super::ParserConfig;
+	use super::super::super::ParseError;
+
+	fn parse_opendmarc(s: String) -> Result<Option<Message>, ParseError> {
+		let conf = ParserConfig::new(vec!["clamsmtpd".to_string()]);
+		let (inner, start) = match Inner::parse(&conf, s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some((x,y))) => (x,y)
+		};
+		Opendmarc::parse(inner, start)
+	}
+
+	#[test]
+	fn ignored() {
+		let s = "Aug  4 00:00:08 yuuai opendmarc[2841]: OpenDMARC Filter v1.3.1 starting (args: -c /etc/opendmarc.conf)".to_string();
+		match parse_opendmarc(s) {
+			Err(x) => panic!("Failed to parse {}", x),
+			Ok(None) => (),
+			Ok(_) => panic!("This should have been ignored"),
+		};
+		let s = "Aug  4 00:00:08 yuuai opendmarc[2841]: 84ED020916: ignoring Authentication-Results at 18 from mx1.example.com".to_string();
+		match parse_opendmarc(s) {
+			Err(x) => panic!("Failed to parse {}", x),
+			Ok(None) => (),
+			Ok(_) => panic!("This should have been ignored"),
+		};
+	}
+
+	#[test]
+	fn unknown_result() {
+		let s = "Aug  4 00:00:08 yuuai opendmarc[2841]: 84ED020916: example.com maybe".to_string();
+		match parse_opendmarc(s) {
+			Err(ParseError::OpendmarcUnknownResult) => (),
+			Err(x) => panic!("Wrong error, should have been OpendmarcUnknownResult {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(_) => panic!("This should have failed")
+		};
+	}
+
+	#[test]
+	fn valid() {
+		let s = "Aug  4 00:00:08 yuuai opendmarc[2841]: 84ED020916: example.com pass".to_string();
+		let dmarc = match parse_opendmarc(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::Opendmarc{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		assert_eq!(dmarc.queue_id(), Some("84ED020916"));
+		assert_eq!(dmarc.domain(), "example.com");
+		assert_eq!(dmarc.result, OpendmarcResult::Pass);
+		assert_eq!(fmt::format(format_args!("{:?}", dmarc)), "Opendmarc { inner: Inner { raw: \"Aug  4 00:00:08 yuuai opendmarc[2841]: 84ED020916: example.com pass\", host_e: 21, queue_s: 22, queue_e: 22, process: Opendmarc, pid: 2841, queue_id_s: 39, queue_id_e: 49 }, domain_s: 51, domain_e: 62, result: pass }");
+	}
+}
@@ -68,7 +68,7 @@ mod tests {
 	use super::super::super::ParseError;
 
 	fn parse_pickup(s: String) -> Result<Option<Message>, ParseError> {
-		let conf = ParserConfig { process_noise: vec!["clamsmtpd".to_string()] };
+		let conf = ParserConfig::new(vec!["clamsmtpd".to_string()]);
 		let (inner, start) = match Inner::parse(&conf, s) {
 			Err(x) => panic!("Parser Error: {}", x),
 			Ok(None) => panic!("This should not have been ignored"),
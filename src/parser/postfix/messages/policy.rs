@@ -0,0 +1,441 @@
+use std::ops::Deref;
+use super::super::ParseError;
+use super::Inner;
+use super::Message;
+use super::MessageParser;
+use super::Process;
+
+#[derive(Debug)]
+pub struct Policy {
+	inner: Inner,
+	action_s: usize,
+	action_e: usize,
+	filter_s: usize,
+	filter_e: usize,
+	client_s: usize,
+	client_e: usize,
+	client_address_s: usize,
+	client_address_e: usize,
+	sender_s: usize,
+	sender_e: usize,
+	recipient_s: usize,
+	recipient_e: usize,
+	reason_s: usize,
+	reason_e: usize,
+	helo_s: usize,
+	helo_e: usize,
+	identity_s: usize,
+	identity_e: usize,
+}
+
+impl Deref for Policy {
+	type Target = Inner;
+	fn deref(&self) -> &Inner {
+		&self.inner
+	}
+}
+
+impl Policy {
+	fn optional <'a>(&'a self, s: usize, e: usize) -> Option<&'a str> {
+		if e != 0 {
+			Some(&self.raw[s..e])
+		} else {
+			None
+		}
+	}
+
+	pub fn action <'a>(&'a self) -> &'a str {
+		&self.raw[self.action_s..self.action_e]
+	}
+
+	pub fn filter <'a>(&'a self) -> Option<&'a str> {
+		self.optional(self.filter_s, self.filter_e)
+	}
+
+	pub fn client <'a>(&'a self) -> Option<&'a str> {
+		self.optional(self.client_s, self.client_e)
+	}
+
+	pub fn client_address <'a>(&'a self) -> Option<&'a str> {
+		self.optional(self.client_address_s, self.client_address_e)
+	}
+
+	pub fn sender <'a>(&'a self) -> Option<&'a str> {
+		self.optional(self.sender_s, self.sender_e)
+	}
+
+	pub fn recipient <'a>(&'a self) -> Option<&'a str> {
+		self.optional(self.recipient_s, self.recipient_e)
+	}
+
+	pub fn reason <'a>(&'a self) -> Option<&'a str> {
+		self.optional(self.reason_s, self.reason_e)
+	}
+
+	// HELO name checked by policyd-spf
+	pub fn helo <'a>(&'a self) -> Option<&'a str> {
+		self.optional(self.helo_s, self.helo_e)
+	}
+
+	// SPF identity checked by policyd-spf: mailfrom or helo
+	pub fn identity <'a>(&'a self) -> Option<&'a str> {
+		self.optional(self.identity_s, self.identity_e)
+	}
+
+	fn empty(inner: Inner) -> Policy {
+		Policy { inner: inner, action_s: 0, action_e: 0, filter_s: 0, filter_e: 0, client_s: 0, client_e: 0, client_address_s: 0, client_address_e: 0, sender_s: 0, sender_e: 0, recipient_s: 0, recipient_e: 0, reason_s: 0, reason_e: 0, helo_s: 0, helo_e: 0, identity_s: 0, identity_e: 0 }
+	}
+
+	// request client=name[addr], from=<sender>, to=<recipient>: awswer ACTION from filter NAME: "reason"
+	fn parse_postlicyd(inner: Inner, start: usize) -> Result<Option<Message>, ParseError> {
+		if !inner.raw[start..].starts_with(" request client=") {
+			return Ok(None);
+		}
+		let mut policy = Policy::empty(inner);
+		{
+			let raw = &policy.inner.raw;
+			policy.client_s = start + 16;
+			let pos = match raw[policy.client_s..].find(", from=<") {
+				None => return Err(ParseError::PolicyNoSender),
+				Some(p) => policy.client_s + p
+			};
+			policy.client_e = pos;
+			let client = &raw[policy.client_s..policy.client_e];
+			if let Some(p) = client.find('[') {
+				if client.ends_with(']') {
+					policy.client_e = policy.client_s + p;
+					policy.client_address_s = policy.client_s + p + 1;
+					policy.client_address_e = pos - 1;
+				}
+			}
+			policy.sender_s = pos + 8;
+			let pos = match raw[policy.sender_s..].find(">, to=<") {
+				None => return Err(ParseError::PolicyNoRecipient),
+				Some(p) => policy.sender_s + p
+			};
+			policy.sender_e = pos;
+			policy.recipient_s = pos + 7;
+			let pos = match raw[policy.recipient_s..].find(">: ") {
+				None => return Err(ParseError::PolicyNoRecipient),
+				Some(p) => policy.recipient_s + p
+			};
+			policy.recipient_e = pos;
+			let rest = &raw[pos + 3..];
+			let offset = if rest.starts_with("awswer ") || rest.starts_with("answer ") {
+				7
+			} else {
+				return Err(ParseError::PolicyNoAction);
+			};
+			policy.action_s = pos + 3 + offset;
+			let rest = &raw[policy.action_s..];
+			match rest.find(" from filter ") {
+				None => policy.action_e = raw.len(),
+				Some(p) => {
+					policy.action_e = policy.action_s + p;
+					policy.filter_s = policy.action_e + 13;
+					match raw[policy.filter_s..].find(": ") {
+						None => policy.filter_e = raw.len(),
+						Some(p) => {
+							policy.filter_e = policy.filter_s + p;
+							policy.reason_s = policy.filter_e + 2;
+							policy.reason_e = raw.len();
+							if raw[policy.reason_s..].starts_with('"') && raw.ends_with('"') && policy.reason_e > policy.reason_s + 1 {
+								policy.reason_s += 1;
+								policy.reason_e -= 1;
+							}
+						}
+					}
+				}
+			};
+		}
+		Ok(Some(Message::Policy { m: policy }))
+	}
+
+	// action=greylist, reason=new, client_name=name, client_address=addr, sender=sender, recipient=recipient
+	fn parse_postgrey(inner: Inner, start: usize) -> Result<Option<Message>, ParseError> {
+		if !inner.raw[start..].starts_with(" action=") {
+			return Ok(None);
+		}
+		let mut policy = Policy::empty(inner);
+		{
+			let raw = &policy.inner.raw;
+			let mut pos = start + 1;
+			while pos < raw.len() {
+				let end = match raw[pos..].find(", ") {
+					None => raw.len(),
+					Some(p) => pos + p
+				};
+				let field = &raw[pos..end];
+				let (s, e) = match field.find('=') {
+					None => (0, 0),
+					Some(p) => (pos + p + 1, end)
+				};
+				if field.starts_with("action=") {
+					policy.action_s = s;
+					policy.action_e = e;
+				} else if field.starts_with("reason=") {
+					policy.reason_s = s;
+					policy.reason_e = e;
+				} else if field.starts_with("client_name=") {
+					policy.client_s = s;
+					policy.client_e = e;
+				} else if field.starts_with("client_address=") {
+					policy.client_address_s = s;
+					policy.client_address_e = e;
+				} else if field.starts_with("sender=") {
+					policy.sender_s = s;
+					policy.sender_e = e;
+				} else if field.starts_with("recipient=") {
+					policy.recipient_s = s;
+					policy.recipient_e = e;
+				}
+				pos = end + 2;
+			}
+		}
+		Ok(Some(Message::Policy { m: policy }))
+	}
+
+	// Pass; identity=mailfrom; client-ip=addr; helo=name; envelope-from=sender; receiver=recipient
+	fn parse_policyd_spf(inner: Inner, start: usize) -> Result<Option<Message>, ParseError> {
+		let mut policy = Policy::empty(inner);
+		{
+			let raw = &policy.inner.raw;
+			let mut pos = start + 1;
+			if raw[pos..].starts_with("prepend Received-SPF: ") {
+				pos += 22;
+			}
+			let rest = &raw[pos..];
+			let len = match rest.find(|c| c == ';' || c == ' ') {
+				None => return Ok(None),
+				Some(l) => l
+			};
+			let numeric = len == 3 && rest[..3].bytes().all(|b| b'0' <= b && b <= b'9');
+			policy.action_s = pos;
+			policy.action_e = pos + if numeric {
+				match rest.find(';') {
+					None => rest.len(),
+					Some(l) => l
+				}
+			} else {
+				len
+			};
+			pos = policy.action_e;
+			if raw[pos..].starts_with(" (") {
+				if let Some(p) = raw[pos..].find(')') {
+					policy.reason_s = pos + 2;
+					policy.reason_e = pos + p;
+					pos += p + 1;
+				}
+			}
+			while pos < raw.len() {
+				let field_s = pos + raw[pos..].len() - raw[pos..].trim_start_matches(|c| c == ';' || c == ' ').len();
+				if field_s >= raw.len() {
+					break;
+				}
+				let field_e = match raw[field_s..].find(';') {
+					None => raw.len(),
+					Some(p) => field_s + p
+				};
+				let field = &raw[field_s..field_e];
+				let (s, e) = match field.find('=') {
+					None => (0, 0),
+					Some(p) => (field_s + p + 1, field_e)
+				};
+				if field.starts_with("identity=") {
+					policy.identity_s = s;
+					policy.identity_e = e;
+				} else if field.starts_with("client-ip=") {
+					policy.client_address_s = s;
+					policy.client_address_e = e;
+				} else if field.starts_with("helo=") {
+					policy.helo_s = s;
+					policy.helo_e = e;
+				} else if field.starts_with("envelope-from=") {
+					policy.sender_s = s;
+					policy.sender_e = e;
+				} else if field.starts_with("receiver=") {
+					policy.recipient_s = s;
+					policy.recipient_e = e;
+				}
+				pos = field_e;
+			}
+			if policy.identity_e == 0 && policy.client_address_e == 0 {
+				return Ok(None);
+			}
+		}
+		Ok(Some(Message::Policy { m: policy }))
+	}
+}
+
+impl MessageParser for Policy {
+	fn parse(inner: Inner, start: usize) -> Result<Option<Message>, ParseError> {
+		match inner.process {
+			Process::Postgrey => Policy::parse_postgrey(inner, start),
+			Process::PolicydSpf => Policy::parse_policyd_spf(inner, start),
+			_ => Policy::parse_postlicyd(inner, start),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fmt;
+	use super::*;
+	use super::super::Inner;
+	use super::super::Message;
+	use super::super::MessageParser;
+	use super::super::Process;
+	use super::super::super::ParserConfig;
+	use super::super::super::ParseError;
+
+	fn parse_policy(s: String) -> Result<Option<Message>, ParseError> {
+		let conf = ParserConfig::new(vec!["clamsmtpd".to_string()]);
+		let (inner, start) = match Inner::parse(&conf, s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some((x,y))) => (x,y)
+		};
+		Policy::parse(inner, start)
+	}
+
+	#[test]
+	fn ignored() {
+		let s = "Aug  4 00:00:08 yuuai postlicyd[2841]: reloading configuration".to_string();
+		match parse_policy(s) {
+			Err(x) => panic!("Failed to parse {}", x),
+			Ok(None) => (),
+			Ok(_) => panic!("This should have been ignored"),
+		};
+		let s = "Aug  4 00:00:08 yuuai postgrey[2841]: cleaning up old logs...".to_string();
+		match parse_policy(s) {
+			Err(x) => panic!("Failed to parse {}", x),
+			Ok(None) => (),
+			Ok(_) => panic!("This should have been ignored"),
+		};
+		let s = "Aug  4 00:00:08 yuuai policyd-spf[2841]: Starting".to_string();
+		match parse_policy(s) {
+			Err(x) => panic!("Failed to parse {}", x),
+			Ok(None) => (),
+			Ok(_) => panic!("This should have been ignored"),
+		};
+	}
+
+	#[test]
+	fn postlicyd_errors() {
+		let s = "Aug  4 00:00:08 yuuai postlicyd[2841]: 84ED020916: request client=mx1[129.104.30.34]".to_string();
+		match parse_policy(s) {
+			Err(ParseError::PolicyNoSender) => (),
+			Err(x) => panic!("Wrong error, should have been PolicyNoSender {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(_) => panic!("This should have failed")
+		};
+		let s = "Aug  4 00:00:08 yuuai postlicyd[2841]: 84ED020916: request client=mx1[129.104.30.34], from=<aaa@bbb.ccc>".to_string();
+		match parse_policy(s) {
+			Err(ParseError::PolicyNoRecipient) => (),
+			Err(x) => panic!("Wrong error, should have been PolicyNoRecipient {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(_) => panic!("This should have failed")
+		};
+		let s = "Aug  4 00:00:08 yuuai postlicyd[2841]: 84ED020916: request client=mx1[129.104.30.34], from=<aaa@bbb.ccc>, to=<xxx@yyy.zzz>: greylist".to_string();
+		match parse_policy(s) {
+			Err(ParseError::PolicyNoAction) => (),
+			Err(x) => panic!("Wrong error, should have been PolicyNoAction {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(_) => panic!("This should have failed")
+		};
+	}
+
+	#[test]
+	fn valid_postlicyd() {
+		let s = "Aug  4 00:00:08 yuuai postlicyd[2841]: 84ED020916: request client=mx1.example.com[129.104.30.34], from=<aaa@bbb.ccc>, to=<xxx@yyy.zzz>: awswer DEFER_IF_PERMIT from filter greylist: \"Greylisted, see http://postgrey.schweikert.ch/\"".to_string();
+		let policy = match parse_policy(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::Policy{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		assert_eq!(policy.process, Process::Postlicyd);
+		assert_eq!(policy.queue_id(), Some("84ED020916"));
+		assert_eq!(policy.action(), "DEFER_IF_PERMIT");
+		assert_eq!(policy.filter(), Some("greylist"));
+		assert_eq!(policy.client(), Some("mx1.example.com"));
+		assert_eq!(policy.client_address(), Some("129.104.30.34"));
+		assert_eq!(policy.sender(), Some("aaa@bbb.ccc"));
+		assert_eq!(policy.recipient(), Some("xxx@yyy.zzz"));
+		assert_eq!(policy.reason(), Some("Greylisted, see http://postgrey.schweikert.ch/"));
+		let s = "Aug  4 00:00:08 yuuai postlicyd[2841]: request client=unknown[190.62.150.179], from=<>, to=<xxx@yyy.zzz>: awswer DUNNO from filter spf".to_string();
+		let policy = match parse_policy(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::Policy{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		assert_eq!(policy.queue_id(), None);
+		assert_eq!(policy.action(), "DUNNO");
+		assert_eq!(policy.filter(), Some("spf"));
+		assert_eq!(policy.sender(), Some(""));
+		assert_eq!(policy.reason(), None);
+		assert_eq!(fmt::format(format_args!("{:?}", policy)), "Policy { inner: Inner { raw: \"Aug  4 00:00:08 yuuai postlicyd[2841]: request client=unknown[190.62.150.179], from=<>, to=<xxx@yyy.zzz>: awswer DUNNO from filter spf\", host_e: 21, queue_s: 22, queue_e: 22, process: Postlicyd, pid: 2841, queue_id_s: 0, queue_id_e: 0 }, action_s: 113, action_e: 118, filter_s: 131, filter_e: 134, client_s: 54, client_e: 61, client_address_s: 62, client_address_e: 76, sender_s: 85, sender_e: 85, recipient_s: 92, recipient_e: 103, reason_s: 0, reason_e: 0, helo_s: 0, helo_e: 0, identity_s: 0, identity_e: 0 }");
+	}
+
+	#[test]
+	fn valid_postgrey() {
+		let s = "Aug  4 00:00:08 yuuai postgrey[2841]: action=greylist, reason=new, client_name=mx1.example.com, client_address=129.104.30.34, sender=aaa@bbb.ccc, recipient=xxx@yyy.zzz".to_string();
+		let policy = match parse_policy(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::Policy{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		assert_eq!(policy.process, Process::Postgrey);
+		assert_eq!(policy.action(), "greylist");
+		assert_eq!(policy.filter(), None);
+		assert_eq!(policy.client(), Some("mx1.example.com"));
+		assert_eq!(policy.client_address(), Some("129.104.30.34"));
+		assert_eq!(policy.sender(), Some("aaa@bbb.ccc"));
+		assert_eq!(policy.recipient(), Some("xxx@yyy.zzz"));
+		assert_eq!(policy.reason(), Some("new"));
+	}
+
+	#[test]
+	fn valid_policyd_spf() {
+		let s = "Aug  4 00:00:08 yuuai policyd-spf[2841]: Pass; identity=mailfrom; client-ip=129.104.30.34; helo=mx1.example.com; envelope-from=aaa@bbb.ccc; receiver=xxx@yyy.zzz".to_string();
+		let policy = match parse_policy(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::Policy{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		assert_eq!(policy.process, Process::PolicydSpf);
+		assert_eq!(policy.action(), "Pass");
+		assert_eq!(policy.identity(), Some("mailfrom"));
+		assert_eq!(policy.helo(), Some("mx1.example.com"));
+		assert_eq!(policy.filter(), None);
+		assert_eq!(policy.client(), None);
+		assert_eq!(policy.client_address(), Some("129.104.30.34"));
+		assert_eq!(policy.sender(), Some("aaa@bbb.ccc"));
+		assert_eq!(policy.recipient(), Some("xxx@yyy.zzz"));
+		assert_eq!(policy.reason(), None);
+		let s = "Aug  4 00:00:08 yuuai policyd-spf[2841]: prepend Received-SPF: Softfail (mailfrom) identity=mailfrom; client-ip=129.104.30.34; helo=mx1.example.com; envelope-from=aaa@bbb.ccc; receiver=<UNKNOWN>".to_string();
+		let policy = match parse_policy(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::Policy{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		assert_eq!(policy.action(), "Softfail");
+		assert_eq!(policy.reason(), Some("mailfrom"));
+		assert_eq!(policy.identity(), Some("mailfrom"));
+		assert_eq!(policy.recipient(), Some("<UNKNOWN>"));
+		let s = "Aug  4 00:00:08 yuuai policyd-spf[2841]: 550 5.7.23 Message rejected due to: SPF fail - not authorized;identity=mailfrom;client-ip=129.104.30.34;helo=mx1.example.com;envelope-from=aaa@bbb.ccc;receiver=xxx@yyy.zzz".to_string();
+		let policy = match parse_policy(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::Policy{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		assert_eq!(policy.action(), "550 5.7.23 Message rejected due to: SPF fail - not authorized");
+		assert_eq!(policy.client_address(), Some("129.104.30.34"));
+		assert_eq!(policy.recipient(), Some("xxx@yyy.zzz"));
+	}
+}
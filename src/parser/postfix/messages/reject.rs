@@ -4,6 +4,7 @@ use super::super::ParseError;
 use super::Inner;
 use super::Message;
 
+#[derive(Clone, Copy, PartialEq)]
 pub enum RejectReason {
 	Reject,
 	Discard,
@@ -76,8 +77,12 @@ impl Reject {
 	pub fn message <'a>(&'a self) -> &'a str {
 		&self.raw[self.message_s..self.message_e]
 	}
-	pub fn from <'a>(&'a self) -> &'a str {
-		&self.raw[self.from_s..self.from_e]
+	pub fn from <'a>(&'a self) -> Option<&'a str> {
+		if self.from_e != 0 {
+			Some(&self.raw[self.from_s..self.from_e])
+		} else {
+			None
+		}
 	}
 	pub fn to <'a>(&'a self) -> Option<&'a str> {
 		if self.to_e != 0 {
@@ -86,8 +91,12 @@ impl Reject {
 			None
 		}
 	}
-	pub fn helo <'a>(&'a self) -> &'a str {
-		&self.raw[self.helo_s..self.helo_e]
+	pub fn helo <'a>(&'a self) -> Option<&'a str> {
+		if self.helo_e != 0 {
+			Some(&self.raw[self.helo_s..self.helo_e])
+		} else {
+			None
+		}
 	}
 	pub fn explanation<'a>(&'a self) -> Option<&'a str> {
 		if self.explanation_e != 0 {
@@ -100,31 +109,53 @@ impl Reject {
 
 impl Reject {
 	pub fn parse(inner: Inner, start: usize, reason: RejectReason) -> Result<Option<Message>, ParseError> {
-		let (message_s, message_e, from_s, from_e, to_s, to_e, proto, helo_s, helo_e, explanation_s, explanation_e) = {
-			let message_s = match reason {
-				RejectReason::Discard => start + 10,
-				RejectReason::Reject => start + 9,
-				RejectReason::Warn => start + 7,
-			};
-			let mut rest = &inner.raw[message_s..];
-			let mut message_e = message_s;
-			loop {
-				let pos = match rest.find(';') {
-					None => return Err(ParseError::RejectNoFrom),
-					Some(p) => p
-				};
-				message_e += pos;
-				rest = &rest[pos..];
-				if rest.starts_with("; from=<") {
-					break;
-				}
-				if rest.len() > 1 {
-					message_e += 1;
-					rest = &rest[1..];
-				} else {
-					return Err(ParseError::RejectNoFrom);
-				}
-			};
+		let message_s = match reason {
+			RejectReason::Discard => start + 10,
+			RejectReason::Reject => start + 9,
+			RejectReason::Warn => start + 7,
+		};
+		let (message_e, from_s, from_e, to_s, to_e, proto, helo_s, helo_e, explanation_s, explanation_e) = match parse_partial_context(&inner.raw, message_s) {
+			Err(x) => return Err(x),
+			Ok(context) => context
+		};
+		Ok(Some(Message::Reject { m: Reject { inner: inner, reason:reason, message_s:message_s, message_e:message_e, from_s:from_s, from_e:from_e, to_s:to_s, to_e:to_e, proto:proto, helo_s:helo_s, helo_e:helo_e, explanation_s:explanation_s, explanation_e:explanation_e } }))
+	}
+}
+
+// Parses the "<message>; from=<..> to=<..> proto=.. helo=<..>: <explanation>"
+// trailer shared by smtpd access actions and cleanup header/body actions.
+pub fn parse_context(raw: &str, message_s: usize) -> Result<(usize, usize, usize, usize, usize, RejectProto, usize, usize, usize, usize), ParseError> {
+	match parse_partial_context(raw, message_s) {
+		Ok((_, _, 0, _, _, _, _, _, _, _)) => Err(ParseError::RejectNoFrom),
+		Ok((_, _, _, _, _, _, _, 0, _, _)) => Err(ParseError::RejectNoHelo),
+		x => x
+	}
+}
+
+// Same as parse_context, but smtpd rejects before MAIL FROM or HELO leave out
+// from=<..> or helo=<..>, as in "<message>; proto=SMTP": their offsets are 0.
+fn parse_partial_context(raw: &str, message_s: usize) -> Result<(usize, usize, usize, usize, usize, RejectProto, usize, usize, usize, usize), ParseError> {
+	let mut rest = &raw[message_s..];
+	let mut message_e = message_s;
+	loop {
+		let pos = match rest.find(';') {
+			None => return Err(ParseError::RejectNoFrom),
+			Some(p) => p
+		};
+		message_e += pos;
+		rest = &rest[pos..];
+		if rest.starts_with("; from=<") || rest.starts_with("; proto=") {
+			break;
+		}
+		if rest.len() > 1 {
+			message_e += 1;
+			rest = &rest[1..];
+		} else {
+			return Err(ParseError::RejectNoFrom);
+		}
+	};
+	let (rest, from_s, from_e, to_s, to_e) = {
+		if rest.starts_with("; from=<") {
 			let from_s = message_e + 8;
 			let rest = &rest[8..];
 			let pos = match rest.find('>') {
@@ -133,56 +164,57 @@ impl Reject {
 			};
 			let rest = &rest[pos..];
 			let from_e = from_s + pos;
-			let (rest, end, to_s, to_e) = {
-				if rest.starts_with("> to=<") {
-					let rest = &rest[6..];
-					let to_s = from_e + 6;
-					let pos = match rest.find('>') {
-						None => return Err(ParseError::RejectBadTo),
-						Some(p) => p
-					};
-					let rest = &rest[pos..];
-					let to_e = to_s + pos;
-					(rest, to_e, to_s, to_e)
-				} else {
-					(rest, from_e, 0, 0)
-				}
-			};
-			if !rest.starts_with("> proto=") {
-				return Err(ParseError::RejectNoProto);
-			}
-			let rest = &rest[8..];
-			let pos = match rest.find(' ') {
-				None => return Err(ParseError::RejectBadProto),
-				Some(p) => p
-			};
-			let proto = match &rest[..pos] {
-				"SMTP" => RejectProto::SMTP,
-				"ESMTP" => RejectProto::ESMTP,
-				_ => return Err(ParseError::RejectUnknownProto)
-			};
-			let rest = &rest[pos..];
-			if !rest.starts_with(" helo=<") {
-				return Err(ParseError::RejectNoHelo);
+			if rest.starts_with("> to=<") {
+				let rest = &rest[6..];
+				let to_s = from_e + 6;
+				let pos = match rest.find('>') {
+					None => return Err(ParseError::RejectBadTo),
+					Some(p) => p
+				};
+				(&rest[pos + 1..], from_s, from_e, to_s, to_s + pos)
+			} else {
+				(&rest[1..], from_s, from_e, 0, 0)
 			}
-			let rest = &rest[7..];
-			let helo_s = end + 8 + pos + 7;
-			let pos = match rest.find('>') {
-				None => return Err(ParseError::RejectBadHelo),
-				Some(p) => p
-			};
-			let helo_e = helo_s + pos;
-			let (explanation_s, explanation_e) = {
-				if rest[pos..].starts_with(">: ") {
-					(helo_e + 3, helo_s + rest.len())
-				} else {
-					(0, 0)
-				}
-			};
-			(message_s, message_e, from_s, from_e, to_s, to_e, proto, helo_s, helo_e, explanation_s, explanation_e)
-		};
-		Ok(Some(Message::Reject { m: Reject { inner: inner, reason:reason, message_s:message_s, message_e:message_e, from_s:from_s, from_e:from_e, to_s:to_s, to_e:to_e, proto:proto, helo_s:helo_s, helo_e:helo_e, explanation_s:explanation_s, explanation_e:explanation_e } }))
+		} else {
+			(&rest[1..], 0, 0, 0, 0)
+		}
+	};
+	if !rest.starts_with(" proto=") {
+		return Err(ParseError::RejectNoProto);
+	}
+	let rest = &rest[7..];
+	let pos = match rest.find(' ') {
+		None => rest.len(),
+		Some(p) => p
+	};
+	let proto = match &rest[..pos] {
+		"" => return Err(ParseError::RejectBadProto),
+		"SMTP" => RejectProto::SMTP,
+		"ESMTP" => RejectProto::ESMTP,
+		_ => return Err(ParseError::RejectUnknownProto)
+	};
+	let rest = &rest[pos..];
+	if rest.is_empty() {
+		return Ok((message_e, from_s, from_e, to_s, to_e, proto, 0, 0, 0, 0));
+	}
+	if !rest.starts_with(" helo=<") {
+		return Err(ParseError::RejectNoHelo);
 	}
+	let helo_s = raw.len() - rest.len() + 7;
+	let rest = &rest[7..];
+	let pos = match rest.find('>') {
+		None => return Err(ParseError::RejectBadHelo),
+		Some(p) => p
+	};
+	let helo_e = helo_s + pos;
+	let (explanation_s, explanation_e) = {
+		if rest[pos..].starts_with(">: ") {
+			(helo_e + 3, raw.len())
+		} else {
+			(0, 0)
+		}
+	};
+	Ok((message_e, from_s, from_e, to_s, to_e, proto, helo_s, helo_e, explanation_s, explanation_e))
 }
 
 
@@ -196,7 +228,7 @@ mod tests {
 	use super::super::super::ParseError;
 
 	fn parse_reject(s: String, reason: RejectReason) -> Result<Option<Message>, ParseError> {
-		let conf = ParserConfig { process_noise: vec!["clamsmtpd".to_string()] };
+		let conf = ParserConfig::new(vec!["clamsmtpd".to_string()]);
 		let (inner, start) = match Inner::parse(&conf, s) {
 			Err(x) => panic!("Parser Error: {}", x),
 			Ok(None) => panic!("This should not have been ignored"),
@@ -0,0 +1,261 @@
+use std::ops::Deref;
+use super::super::ParseError;
+use super::Inner;
+use super::Message;
+use super::MessageParser;
+use super::SpamAction;
+use super::SpamVerdict;
+
+#[derive(Debug)]
+pub struct Rspamd {
+	inner: Inner,
+	message_id_s: usize,
+	message_id_e: usize,
+	qid_s: usize,
+	qid_e: usize,
+	ip_s: usize,
+	ip_e: usize,
+	from_s: usize,
+	from_e: usize,
+	pub verdict: SpamVerdict,
+	pub size: Option<u64>,
+}
+
+impl Deref for Rspamd {
+	type Target = Inner;
+	fn deref(&self) -> &Inner {
+		&self.inner
+	}
+}
+
+impl Rspamd {
+	fn optional <'a>(&'a self, s: usize, e: usize) -> Option<&'a str> {
+		if e != 0 {
+			Some(&self.raw[s..e])
+		} else {
+			None
+		}
+	}
+
+	pub fn message_id <'a>(&'a self) -> Option<&'a str> {
+		self.optional(self.message_id_s, self.message_id_e)
+	}
+
+	// Queue ID of the mail in the postfix instance calling rspamd as a milter
+	pub fn qid <'a>(&'a self) -> Option<&'a str> {
+		self.optional(self.qid_s, self.qid_e)
+	}
+
+	pub fn ip <'a>(&'a self) -> Option<&'a str> {
+		self.optional(self.ip_s, self.ip_e)
+	}
+
+	pub fn from <'a>(&'a self) -> Option<&'a str> {
+		self.optional(self.from_s, self.from_e)
+	}
+}
+
+fn parse_float(s: &str) -> Result<f64, ParseError> {
+	match s.parse::<f64>() {
+		Err(_) => Err(ParseError::RspamdBadMetric),
+		Ok(val) => Ok(val)
+	}
+}
+
+// default: F (no action): [1.20/15.00] [BAYES_HAM(-3.00){99.99%;},R_SPF_ALLOW(-0.20){+ip4:1.2.3.4;}]
+fn parse_metric(metric: &str) -> Result<SpamVerdict, ParseError> {
+	let action_s = match metric.find(" (") {
+		None => return Err(ParseError::RspamdBadMetric),
+		Some(p) => p + 2
+	};
+	let action_e = match metric[action_s..].find("): [") {
+		None => return Err(ParseError::RspamdBadMetric),
+		Some(p) => action_s + p
+	};
+	let action = match &metric[action_s..action_e] {
+		"no action" => SpamAction::Ham,
+		"greylist" => SpamAction::Greylist,
+		"add header" => SpamAction::Spam,
+		"rewrite subject" => SpamAction::Spam,
+		"soft reject" => SpamAction::SoftReject,
+		"reject" => SpamAction::Reject,
+		_ => return Err(ParseError::RspamdUnknownAction),
+	};
+	let score_s = action_e + 4;
+	let score_e = match metric[score_s..].find('/') {
+		None => return Err(ParseError::RspamdBadMetric),
+		Some(p) => score_s + p
+	};
+	let threshold_e = match metric[score_e..].find("] [") {
+		None => return Err(ParseError::RspamdBadMetric),
+		Some(p) => score_e + p
+	};
+	let score = match parse_float(&metric[score_s..score_e]) {
+		Err(x) => return Err(x),
+		Ok(val) => val
+	};
+	let threshold = match parse_float(&metric[score_e + 1..threshold_e]) {
+		Err(x) => return Err(x),
+		Ok(val) => val
+	};
+	let mut symbols = Vec::new();
+	let mut depth = 0;
+	let mut symbol_s = threshold_e + 3;
+	let mut in_name = true;
+	for (i, c) in metric[symbol_s..].char_indices() {
+		let i = threshold_e + 3 + i;
+		match c {
+			'(' | '{' => {
+				if depth == 0 && in_name {
+					symbols.push(metric[symbol_s..i].to_string());
+					in_name = false;
+				}
+				depth += 1;
+			},
+			')' | '}' => depth -= 1,
+			',' | ']' if depth == 0 => {
+				if in_name && i > symbol_s {
+					symbols.push(metric[symbol_s..i].to_string());
+				}
+				symbol_s = i + 1;
+				in_name = true;
+			},
+			_ => (),
+		}
+	}
+	Ok(SpamVerdict { action: action, score: Some(score), threshold: Some(threshold), symbols: symbols })
+}
+
+impl MessageParser for Rspamd {
+	fn parse(inner: Inner, start: usize) -> Result<Option<Message>, ParseError> {
+		let (message_id_s, message_id_e, qid_s, qid_e, ip_s, ip_e, from_s, from_e, verdict, size) = {
+			let raw = &inner.raw;
+			let mut pos = match raw[start..].find("; rspamd_task_write_log: ") {
+				None => return Ok(None),
+				Some(p) => start + p + 25
+			};
+			let (mut message_id_s, mut message_id_e, mut qid_s, mut qid_e) = (0, 0, 0, 0);
+			let (mut ip_s, mut ip_e, mut from_s, mut from_e) = (0, 0, 0, 0);
+			let mut verdict = None;
+			let mut size = None;
+			while pos < raw.len() {
+				let end = if raw[pos..].starts_with('(') {
+					match raw[pos..].find("])") {
+						None => return Err(ParseError::RspamdBadMetric),
+						Some(p) => pos + p + 2
+					}
+				} else {
+					match raw[pos..].find(", ") {
+						None => raw.len(),
+						Some(p) => pos + p
+					}
+				};
+				let field = &raw[pos..end];
+				// Values between angle brackets are stored without them
+				let (s, e) = match field.find(": ") {
+					None => (0, 0),
+					Some(p) if field[p + 2..].starts_with('<') && field.ends_with('>') => (pos + p + 3, end - 1),
+					Some(p) => (pos + p + 2, end)
+				};
+				if field.starts_with('(') {
+					verdict = match parse_metric(&field[1..field.len() - 1]) {
+						Err(x) => return Err(x),
+						Ok(v) => Some(v)
+					};
+				} else if field.starts_with("id: ") {
+					message_id_s = s;
+					message_id_e = e;
+				} else if field.starts_with("qid: ") {
+					qid_s = s;
+					qid_e = e;
+				} else if field.starts_with("ip: ") {
+					ip_s = s;
+					ip_e = e;
+				} else if field.starts_with("from: ") {
+					from_s = s;
+					from_e = e;
+				} else if field.starts_with("len: ") {
+					size = field[5..].parse::<u64>().ok();
+				}
+				pos = end + 2;
+			}
+			let verdict = match verdict {
+				None => return Err(ParseError::RspamdBadMetric),
+				Some(v) => v
+			};
+			(message_id_s, message_id_e, qid_s, qid_e, ip_s, ip_e, from_s, from_e, verdict, size)
+		};
+		Ok(Some(Message::Rspamd { m: Rspamd { inner: inner, message_id_s: message_id_s, message_id_e: message_id_e, qid_s: qid_s, qid_e: qid_e, ip_s: ip_s, ip_e: ip_e, from_s: from_s, from_e: from_e, verdict: verdict, size: size } }))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fmt;
+	use super::*;
+	use super::super::Inner;
+	use super::super::Message;
+	use super::super::MessageParser;
+	use super::super::super::ParserConfig;
+	use super::super::super::ParseError;
+
+	fn parse_rspamd(s: String) -> Result<Option<Message>, ParseError> {
+		let conf = ParserConfig::new(vec!["clamsmtpd".to_string()]);
+		let (inner, start) = match Inner::parse(&conf, s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some((x,y))) => (x,y)
+		};
+		Rspamd::parse(inner, start)
+	}
+
+	#[test]
+	fn ignored() {
+		let s = "Aug  4 00:00:08 yuuai rspamd[2841]: <4d2b7e>; lua; dkim_signing.lua:100: skip signing".to_string();
+		match parse_rspamd(s) {
+			Err(x) => panic!("Failed to parse {}", x),
+			Ok(None) => (),
+			Ok(_) => panic!("This should have been ignored"),
+		};
+	}
+
+	#[test]
+	fn errors() {
+		let s = "Aug  4 00:00:08 yuuai rspamd[2841]: <4d2b7e>; task; rspamd_task_write_log: id: <abc@bbb.ccc>, qid: <84ED020916>, ip: 1.2.3.4, from: <aaa@bbb.ccc>, (default: F (maybe): [1.20/15.00] [BAYES_HAM(-3.00)]), len: 1234".to_string();
+		match parse_rspamd(s) {
+			Err(ParseError::RspamdUnknownAction) => (),
+			Err(x) => panic!("Wrong error, should have been RspamdUnknownAction {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(_) => panic!("This should have failed")
+		};
+		let s = "Aug  4 00:00:08 yuuai rspamd[2841]: <4d2b7e>; task; rspamd_task_write_log: id: <abc@bbb.ccc>, qid: <84ED020916>, ip: 1.2.3.4, len: 1234".to_string();
+		match parse_rspamd(s) {
+			Err(ParseError::RspamdBadMetric) => (),
+			Err(x) => panic!("Wrong error, should have been RspamdBadMetric {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(_) => panic!("This should have failed")
+		};
+	}
+
+	#[test]
+	fn valid() {
+		let s = "Aug  4 00:00:08 yuuai rspamd[2841]: <4d2b7e>; task; rspamd_task_write_log: id: <abc@bbb.ccc>, qid: <84ED020916>, ip: 1.2.3.4, from: <aaa@bbb.ccc>, (default: T (add header): [7.20/15.00] [BAYES_SPAM(5.10){99.99%;},R_SPF_ALLOW(-0.20){+ip4:1.2.3.4;},MIME_GOOD(-0.10){text/plain;}]), len: 1234, time: 123.4ms, dns req: 12, digest: <0123456789abcdef>, rcpts: <xxx@yyy.zzz>".to_string();
+		let rspamd = match parse_rspamd(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::Rspamd{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		assert_eq!(rspamd.queue_id(), None);
+		assert_eq!(rspamd.message_id(), Some("abc@bbb.ccc"));
+		assert_eq!(rspamd.qid(), Some("84ED020916"));
+		assert_eq!(rspamd.ip(), Some("1.2.3.4"));
+		assert_eq!(rspamd.from(), Some("aaa@bbb.ccc"));
+		assert_eq!(rspamd.size, Some(1234));
+		assert_eq!(rspamd.verdict.action, SpamAction::Spam);
+		assert_eq!(rspamd.verdict.score, Some(7.2));
+		assert_eq!(rspamd.verdict.threshold, Some(15.0));
+		assert_eq!(rspamd.verdict.symbols, vec!["BAYES_SPAM".to_string(), "R_SPF_ALLOW".to_string(), "MIME_GOOD".to_string()]);
+		assert_eq!(fmt::format(format_args!("{:?}", rspamd)), "Rspamd { inner: Inner { raw: \"Aug  4 00:00:08 yuuai rspamd[2841]: <4d2b7e>; task; rspamd_task_write_log: id: <abc@bbb.ccc>, qid: <84ED020916>, ip: 1.2.3.4, from: <aaa@bbb.ccc>, (default: T (add header): [7.20/15.00] [BAYES_SPAM(5.10){99.99%;},R_SPF_ALLOW(-0.20){+ip4:1.2.3.4;},MIME_GOOD(-0.10){text/plain;}]), len: 1234, time: 123.4ms, dns req: 12, digest: <0123456789abcdef>, rcpts: <xxx@yyy.zzz>\", host_e: 21, queue_s: 22, queue_e: 22, process: Rspamd, pid: 2841, queue_id_s: 0, queue_id_e: 0 }, message_id_s: 80, message_id_e: 91, qid_s: 100, qid_e: 110, ip_s: 117, ip_e: 124, from_s: 133, from_e: 144, verdict: SpamVerdict { action: Spam, score: Some(7.2), threshold: Some(15.0), symbols: [\"BAYES_SPAM\", \"R_SPF_ALLOW\", \"MIME_GOOD\"] }, size: Some(1234) }");
+	}
+}
@@ -23,6 +23,22 @@ pub struct SmtpdForward {
 	orig_client_e: usize,
 }
 
+#[derive(Debug)]
+pub struct SmtpdConnect {
+	inner: Inner,
+	client_s: usize,
+	client_e: usize,
+}
+
+#[derive(Debug)]
+pub struct SmtpdDisconnect {
+	inner: Inner,
+	client_s: usize,
+	client_e: usize,
+	stats_s: usize,
+	stats_e: usize,
+}
+
 pub enum SmtpdMethod {
 	Plain,
 	Login,
@@ -43,6 +59,20 @@ impl Deref for Smtpd {
 	}
 }
 
+impl Deref for SmtpdConnect {
+	type Target = Inner;
+	fn deref(&self) -> &Inner {
+		&self.inner
+	}
+}
+
+impl Deref for SmtpdDisconnect {
+	type Target = Inner;
+	fn deref(&self) -> &Inner {
+		&self.inner
+	}
+}
+
 impl Deref for SmtpdForward {
 	type Target = Smtpd;
 	fn deref(&self) -> &Smtpd {
@@ -78,6 +108,27 @@ impl Smtpd {
 	}
 }
 
+impl SmtpdConnect {
+	pub fn client <'a>(&'a self) -> &'a str {
+		&self.raw[self.client_s..self.client_e]
+	}
+}
+
+impl SmtpdDisconnect {
+	pub fn client <'a>(&'a self) -> &'a str {
+		&self.raw[self.client_s..self.client_e]
+	}
+
+	// Command counters of postfix >= 3.0: "ehlo=1 mail=1 rcpt=1 data=1 quit=1 commands=5"
+	pub fn stats <'a>(&'a self) -> Option<&'a str> {
+		if self.stats_e != 0 {
+			Some(&self.raw[self.stats_s..self.stats_e])
+		} else {
+			None
+		}
+	}
+}
+
 impl SmtpdForward {
 	pub fn orig_queue_id <'a>(&'a self) -> &'a str {
 		&self.raw[self.orig_queue_id_s..self.orig_queue_id_e]
@@ -96,7 +147,7 @@ impl SmtpdLogin {
 impl MessageParser for Smtpd {
 	fn parse(inner: Inner, start: usize) -> Result<Option<Message>, ParseError> {
 		match inner.queue_id() {
-			None => return Ok(None),
+			None => return parse_no_queue(inner, start),
 			Some(_) => ()
 		};
 		let bad = {
@@ -178,6 +229,45 @@ impl MessageParser for Smtpd {
 	}
 }
 
+fn parse_no_queue(inner: Inner, start: usize) -> Result<Option<Message>, ParseError> {
+	let (connect, client_s, reason) = {
+		let rest = &inner.raw[start..];
+		if rest.starts_with(" connect from ") {
+			(true, start + 14, None)
+		} else if rest.starts_with(" disconnect from ") {
+			(false, start + 17, None)
+		} else if rest.starts_with(" NOQUEUE: reject: ") {
+			(false, 0, Some(RejectReason::Reject))
+		} else if rest.starts_with(" NOQUEUE: discard: ") {
+			(false, 0, Some(RejectReason::Discard))
+		} else if rest.starts_with(" NOQUEUE: warn: ") {
+			(false, 0, Some(RejectReason::Warn))
+		} else {
+			return Ok(None);
+		}
+	};
+	match reason {
+		None => (),
+		Some(reason) => return Reject::parse(inner, start + 9, reason),
+	};
+	let (client_e, stats_s, stats_e) = {
+		let rest = &inner.raw[client_s..];
+		let client_e = match rest.find(']') {
+			None => return Err(ParseError::SmtpdBadClient),
+			Some(p) => client_s + p + 1
+		};
+		if inner.raw[client_e..].starts_with(' ') {
+			(client_e, client_e + 1, inner.raw.len())
+		} else {
+			(client_e, 0, 0)
+		}
+	};
+	if connect {
+		Ok(Some(Message::SmtpdConnect { m: SmtpdConnect { inner: inner, client_s: client_s, client_e: client_e } }))
+	} else {
+		Ok(Some(Message::SmtpdDisconnect { m: SmtpdDisconnect { inner: inner, client_s: client_s, client_e: client_e, stats_s: stats_s, stats_e: stats_e } }))
+	}
+}
 
 #[cfg(test)]
 mod tests {
@@ -192,7 +282,7 @@ mod tests {
 	use super::super::super::ParseError;
 
 	fn parse_smtpd(s: String) -> Result<Option<Message>, ParseError> {
-		let conf = ParserConfig { process_noise: vec!["clamsmtpd".to_string()] };
+		let conf = ParserConfig::new(vec!["clamsmtpd".to_string()]);
 		let (inner, start) = match Inner::parse(&conf, s) {
 			Err(x) => panic!("Parser Error: {}", x),
 			Ok(None) => panic!("This should not have been ignored"),
@@ -203,7 +293,7 @@ mod tests {
 
 	#[test]
 	fn no_queue_id() {
-		let s = "Aug  4 00:00:09 yuuai postfix/smtpd[20518]: disconnect from mx1[129.104.30.34]".to_string();
+		let s = "Aug  4 00:00:09 yuuai postfix/smtpd[20518]: lost connection after DATA from mx1[129.104.30.34]".to_string();
 		match parse_smtpd(s) {
 			Err(x) => panic!("Parser Error: {}", x),
 			Ok(None) => (),
@@ -217,6 +307,95 @@ mod tests {
 		};
 	}
 
+	#[test]
+	fn bad_client() {
+		let s = "Aug  4 00:00:09 yuuai postfix/smtpd[20518]: connect from mx1[129.104.30.34".to_string();
+		match parse_smtpd(s) {
+			Err(ParseError::SmtpdBadClient) => (),
+			Err(x) => panic!("Wrong error, should have been SmtpdBadClient {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(_) => panic!("This should have failed")
+		};
+	}
+
+	#[test]
+	fn valid_connect() {
+		let s = "Aug  4 00:00:08 yuuai postfix/smtpd[20518]: connect from mx1[129.104.30.34]".to_string();
+		let connect = match parse_smtpd(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::SmtpdConnect{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		assert_eq!(connect.client(), "mx1[129.104.30.34]");
+		assert_eq!(fmt::format(format_args!("{:?}", connect)), "SmtpdConnect { inner: Inner { raw: \"Aug  4 00:00:08 yuuai postfix/smtpd[20518]: connect from mx1[129.104.30.34]\", host_e: 21, queue_s: 22, queue_e: 29, process: Smtpd, pid: 20518, queue_id_s: 0, queue_id_e: 0 }, client_s: 57, client_e: 75 }");
+	}
+
+	#[test]
+	fn valid_disconnect() {
+		let s = "Aug  4 00:00:09 yuuai postfix/smtpd[20518]: disconnect from mx1[129.104.30.34]".to_string();
+		let disconnect = match parse_smtpd(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::SmtpdDisconnect{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		assert_eq!(disconnect.client(), "mx1[129.104.30.34]");
+		assert_eq!(disconnect.stats(), None);
+		let s = "Aug  4 00:00:09 yuuai postfix/smtpd[20518]: disconnect from mx1[129.104.30.34] ehlo=1 mail=1 rcpt=1 data=1 quit=1 commands=5".to_string();
+		let disconnect = match parse_smtpd(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::SmtpdDisconnect{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		assert_eq!(disconnect.client(), "mx1[129.104.30.34]");
+		assert_eq!(disconnect.stats(), Some("ehlo=1 mail=1 rcpt=1 data=1 quit=1 commands=5"));
+		assert_eq!(fmt::format(format_args!("{:?}", disconnect)), "SmtpdDisconnect { inner: Inner { raw: \"Aug  4 00:00:09 yuuai postfix/smtpd[20518]: disconnect from mx1[129.104.30.34] ehlo=1 mail=1 rcpt=1 data=1 quit=1 commands=5\", host_e: 21, queue_s: 22, queue_e: 29, process: Smtpd, pid: 20518, queue_id_s: 0, queue_id_e: 0 }, client_s: 60, client_e: 78, stats_s: 79, stats_e: 124 }");
+	}
+
+	#[test]
+	fn valid_noqueue_reject() {
+		let s = "Aug  4 00:00:09 yuuai postfix/smtpd[20518]: NOQUEUE: reject: RCPT from mx1[129.104.30.34]: 554 5.7.1 <xxx@yyy.zzz>: Relay access denied; from=<aaa@bbb.ccc> to=<xxx@yyy.zzz> proto=ESMTP helo=<mx1.bbb.ccc>".to_string();
+		let reject = match parse_smtpd(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::Reject{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		assert_eq!(reject.queue_id(), None);
+		assert_eq!(reject.message(), "RCPT from mx1[129.104.30.34]: 554 5.7.1 <xxx@yyy.zzz>: Relay access denied");
+		assert_eq!(reject.from(), Some("aaa@bbb.ccc"));
+		assert_eq!(reject.to(), Some("xxx@yyy.zzz"));
+		assert_eq!(reject.helo(), Some("mx1.bbb.ccc"));
+	}
+
+	#[test]
+	fn noqueue_reject_before_mail_from() {
+		let s = "Aug  4 00:00:09 yuuai postfix/smtpd[20518]: NOQUEUE: reject: EHLO from unknown[192.0.2.1]: 504 5.5.2 <localhost>: Helo command rejected: need fully-qualified hostname; proto=ESMTP helo=<localhost>".to_string();
+		let reject = match parse_smtpd(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::Reject{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		assert_eq!(reject.message(), "EHLO from unknown[192.0.2.1]: 504 5.5.2 <localhost>: Helo command rejected: need fully-qualified hostname");
+		assert_eq!(reject.from(), None);
+		assert_eq!(reject.to(), None);
+		assert_eq!(reject.helo(), Some("localhost"));
+
+		let s = "Aug  4 00:00:09 yuuai postfix/smtpd[20518]: NOQUEUE: reject: CONNECT from unknown[192.0.2.1]: 554 5.7.1 Service unavailable; Client host [192.0.2.1] blocked using zen.spamhaus.org; proto=SMTP".to_string();
+		let reject = match parse_smtpd(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::Reject{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		assert_eq!(reject.message(), "CONNECT from unknown[192.0.2.1]: 554 5.7.1 Service unavailable; Client host [192.0.2.1] blocked using zen.spamhaus.org");
+		assert_eq!(reject.from(), None);
+		assert_eq!(reject.helo(), None);
+	}
+
 	#[test]
 	fn bad_orig_queue() {
 		let s ="Aug  4 00:00:08 yuuai postfix/smtpd.local[20039]: 84ED020916: client=localhost[127.0.0.1], orig_queue_id".to_string();
@@ -264,7 +443,7 @@ mod tests {
 			x => panic!("Parsed wrong reason: {}", x)
 		}
 		assert_eq!(smtpd.message(), "DATA from scm.seog.co.kr[61.36.79.99]: <DATA>: Data command Recipient list contains a blacklisted address");
-		assert_eq!(smtpd.from(), "massnewsletter4654654xel@gmail.com");
+		assert_eq!(smtpd.from(), Some("massnewsletter4654654xel@gmail.com"));
 		match smtpd.to() {
 			None => (),
 			Some(s) => panic!("Parsed a non existing to: {}", s)
@@ -273,7 +452,7 @@ mod tests {
 			RejectProto::SMTP => (),
 			x => panic!("Parsed wrong proto: {}", x)
 		}
-		assert_eq!(smtpd.helo(), "gmail.com");
+		assert_eq!(smtpd.helo(), Some("gmail.com"));
 		match smtpd.explanation() {
 			None => (),
 			Some(s) => panic!("Parsed a non existing explanation: {}", s)
@@ -295,7 +474,7 @@ mod tests {
 			x => panic!("Parsed wrong reason: {}", x)
 		}
 		assert_eq!(smtpd.message(), "DATA from 99-46-141-195.lightspeed.sntcca.sbcglobal.net[99.46.141.195]: 421 4.7.1 <DATA>: Data command rejected: Tu (firstname.lastname) as envoye trop de mails recement. Merci de contacter le support s'il s'agit d'une erreur");
-		assert_eq!(smtpd.from(), "firstname.lastname@m4x.org");
+		assert_eq!(smtpd.from(), Some("firstname.lastname@m4x.org"));
 		match smtpd.to() {
 			None => panic!("Failed to parse to"),
 			Some(s) => assert_eq!(s, "firstname.lastname@m4x.org")
@@ -304,7 +483,7 @@ mod tests {
 			RejectProto::ESMTP => (),
 			x => panic!("Parsed wrong proto: {}", x)
 		}
-		assert_eq!(smtpd.helo(), "DiskStation");
+		assert_eq!(smtpd.helo(), Some("DiskStation"));
 		match smtpd.explanation() {
 			None => (),
 			Some(s) => panic!("Parsed a non existing explanation: {}", s)
@@ -326,7 +505,7 @@ mod tests {
 			x => panic!("Parsed wrong reason: {}", x)
 		}
 		assert_eq!(smtpd.message(), "RCPT from unknown[190.62.150.179]: Literal IP in HELO hostnames not allowed here, please check your configuration");
-		assert_eq!(smtpd.from(), "firstname.lastname@m4x.org");
+		assert_eq!(smtpd.from(), Some("firstname.lastname@m4x.org"));
 		match smtpd.to() {
 			None => panic!("Failed to parse to"),
 			Some(s) => assert_eq!(s, "firstname.lastname@m4x.org")
@@ -335,7 +514,7 @@ mod tests {
 			RejectProto::ESMTP => (),
 			x => panic!("Parsed wrong proto: {}", x)
 		}
-		assert_eq!(smtpd.helo(), "[127.0.0.2]");
+		assert_eq!(smtpd.helo(), Some("[127.0.0.2]"));
 		match smtpd.explanation() {
 			None => (),
 			Some(s) => panic!("Parsed a non existing explanation: {}", s)
@@ -0,0 +1,50 @@
+use std::fmt;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SpamAction {
+	Ham,
+	Unsure,
+	Spam,
+	Greylist,
+	SoftReject,
+	Reject,
+}
+
+// Filter independent view of a spam classification, built by the bogofilter,
+// spamd and rspamd parsers. Scores are on each filter's own scale.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpamVerdict {
+	pub action: SpamAction,
+	pub score: Option<f64>,
+	pub threshold: Option<f64>,
+	pub symbols: Vec<String>,
+}
+
+impl fmt::Display for SpamAction {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		let action = match self {
+			&SpamAction::Ham => "Ham",
+			&SpamAction::Unsure => "Unsure",
+			&SpamAction::Spam => "Spam",
+			&SpamAction::Greylist => "Greylist",
+			&SpamAction::SoftReject => "SoftReject",
+			&SpamAction::Reject => "Reject",
+		};
+		write!(fmt, "{}", action)
+	}
+}
+
+impl fmt::Debug for SpamAction {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		fmt::Display::fmt(self, fmt)
+	}
+}
+
+impl SpamVerdict {
+	pub fn is_spam(&self) -> bool {
+		match self.action {
+			SpamAction::Ham | SpamAction::Unsure | SpamAction::Greylist => false,
+			SpamAction::Spam | SpamAction::SoftReject | SpamAction::Reject => true,
+		}
+	}
+}
@@ -0,0 +1,270 @@
+use std::ops::Deref;
+use super::super::ParseError;
+use super::Inner;
+use super::Message;
+use super::MessageParser;
+use super::SpamAction;
+use super::SpamVerdict;
+
+#[derive(Debug)]
+pub struct Spamd {
+	inner: Inner,
+	pub verdict: SpamVerdict,
+	user_s: usize,
+	user_e: usize,
+	message_id_s: usize,
+	message_id_e: usize,
+	pub size: Option<u64>,
+	pub scantime: Option<f64>,
+}
+
+impl Deref for Spamd {
+	type Target = Inner;
+	fn deref(&self) -> &Inner {
+		&self.inner
+	}
+}
+
+impl Spamd {
+	pub fn user <'a>(&'a self) -> Option<&'a str> {
+		if self.user_e != 0 {
+			Some(&self.raw[self.user_s..self.user_e])
+		} else {
+			None
+		}
+	}
+
+	pub fn message_id <'a>(&'a self) -> Option<&'a str> {
+		if self.message_id_e != 0 {
+			Some(&self.raw[self.message_id_s..self.message_id_e])
+		} else {
+			None
+		}
+	}
+}
+
+fn parse_float(s: &str) -> Result<f64, ParseError> {
+	match s.parse::<f64>() {
+		Err(_) => Err(ParseError::SpamdBadScore),
+		Ok(val) => Ok(val)
+	}
+}
+
+type SpamdFields = (SpamVerdict, usize, usize, usize, usize, Option<u64>, Option<f64>);
+
+// Y 12 - BAYES_99,HTML_MESSAGE scantime=1.2,size=3456,user=nobody,uid=65534,required_score=5.0,mid=<id>,...
+fn parse_result(raw: &str, start: usize) -> Result<SpamdFields, ParseError> {
+	let rest = &raw[start..];
+	let action = if rest.starts_with("Y ") {
+		SpamAction::Spam
+	} else if rest.starts_with("N ") {
+		SpamAction::Ham
+	} else {
+		return Err(ParseError::SpamdBadResult);
+	};
+	let score_e = match rest.find(" - ") {
+		None => return Err(ParseError::SpamdBadResult),
+		Some(p) => p
+	};
+	let score = match parse_float(&rest[2..score_e]) {
+		Err(x) => return Err(x),
+		Ok(val) => val
+	};
+	let tests_s = score_e + 3;
+	let tests_e = match rest[tests_s..].find(' ') {
+		None => rest.len(),
+		Some(p) => tests_s + p
+	};
+	let symbols = match &rest[tests_s..tests_e] {
+		"none" => Vec::new(),
+		tests => tests.split(',').map(|t| t.to_string()).collect()
+	};
+	let mut verdict = SpamVerdict { action: action, score: Some(score), threshold: None, symbols: symbols };
+	let (mut user_s, mut user_e, mut message_id_s, mut message_id_e) = (0, 0, 0, 0);
+	let mut size = None;
+	let mut scantime = None;
+	let mut pos = tests_e + 1;
+	while pos < rest.len() {
+		let end = match rest[pos..].find(',') {
+			None => rest.len(),
+			Some(p) => pos + p
+		};
+		let field = &rest[pos..end];
+		if field.starts_with("required_score=") {
+			verdict.threshold = match parse_float(&field[15..]) {
+				Err(x) => return Err(x),
+				Ok(val) => Some(val)
+			};
+		} else if field.starts_with("scantime=") {
+			scantime = field[9..].parse::<f64>().ok();
+		} else if field.starts_with("size=") {
+			size = field[5..].parse::<u64>().ok();
+		} else if field.starts_with("user=") {
+			user_s = start + pos + 5;
+			user_e = start + end;
+		} else if field.starts_with("mid=<") && field.ends_with('>') {
+			message_id_s = start + pos + 5;
+			message_id_e = start + end - 1;
+		}
+		pos = end + 1;
+	}
+	Ok((verdict, user_s, user_e, message_id_s, message_id_e, size, scantime))
+}
+
+// 12.3/5.0) for nobody:65534 in 1.2 seconds, 3456 bytes.
+fn parse_summary(raw: &str, start: usize, action: SpamAction) -> Result<SpamdFields, ParseError> {
+	let rest = &raw[start..];
+	let threshold_s = match rest.find('/') {
+		None => return Err(ParseError::SpamdBadScore),
+		Some(p) => p
+	};
+	let threshold_e = match rest.find(") for ") {
+		Some(p) if p > threshold_s => p,
+		_ => return Err(ParseError::SpamdBadScore),
+	};
+	let score = match parse_float(&rest[..threshold_s]) {
+		Err(x) => return Err(x),
+		Ok(val) => val
+	};
+	let threshold = match parse_float(&rest[threshold_s + 1..threshold_e]) {
+		Err(x) => return Err(x),
+		Ok(val) => val
+	};
+	let user_s = threshold_e + 6;
+	let user_e = match rest[user_s..].find(|c| c == ':' || c == ' ') {
+		None => rest.len(),
+		Some(p) => user_s + p
+	};
+	let mut scantime = None;
+	let mut size = None;
+	if let Some(p) = rest[user_e..].find(" in ") {
+		let time_s = user_e + p + 4;
+		if let Some(l) = rest[time_s..].find(" seconds, ") {
+			scantime = rest[time_s..time_s + l].parse::<f64>().ok();
+			let size_s = time_s + l + 10;
+			if let Some(l) = rest[size_s..].find(" bytes") {
+				size = rest[size_s..size_s + l].parse::<u64>().ok();
+			}
+		}
+	}
+	let verdict = SpamVerdict { action: action, score: Some(score), threshold: Some(threshold), symbols: Vec::new() };
+	Ok((verdict, start + user_s, start + user_e, 0, 0, size, scantime))
+}
+
+impl MessageParser for Spamd {
+	fn parse(inner: Inner, start: usize) -> Result<Option<Message>, ParseError> {
+		let fields = {
+			let rest = &inner.raw[start..];
+			if rest.starts_with(" spamd: result: ") {
+				parse_result(&inner.raw, start + 16)
+			} else if rest.starts_with(" spamd: identified spam (") {
+				parse_summary(&inner.raw, start + 25, SpamAction::Spam)
+			} else if rest.starts_with(" spamd: clean message (") {
+				parse_summary(&inner.raw, start + 23, SpamAction::Ham)
+			} else {
+				return Ok(None);
+			}
+		};
+		let (verdict, user_s, user_e, message_id_s, message_id_e, size, scantime) = match fields {
+			Err(x) => return Err(x),
+			Ok(x) => x
+		};
+		Ok(Some(Message::Spamd { m: Spamd { inner: inner, verdict: verdict, user_s: user_s, user_e: user_e, message_id_s: message_id_s, message_id_e: message_id_e, size: size, scantime: scantime } }))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fmt;
+	use super::*;
+	use super::super::Inner;
+	use super::super::Message;
+	use super::super::MessageParser;
+	use super::super::super::ParserConfig;
+	use super::super::super::ParseError;
+
+	fn parse_spamd(s: String) -> Result<Option<Message>, ParseError> {
+		let conf = ParserConfig::new(vec!["clamsmtpd".to_string()]);
+		let (inner, start) = match Inner::parse(&conf, s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some((x,y))) => (x,y)
+		};
+		Spamd::parse(inner, start)
+	}
+
+	#[test]
+	fn ignored() {
+		let s = "Aug  4 00:00:08 yuuai spamd[2841]: spamd: processing message <abc@bbb.ccc> for nobody:65534".to_string();
+		match parse_spamd(s) {
+			Err(x) => panic!("Failed to parse {}", x),
+			Ok(None) => (),
+			Ok(_) => panic!("This should have been ignored"),
+		};
+		let s = "Aug  4 00:00:08 yuuai spamd[2841]: prefork: child states: II".to_string();
+		match parse_spamd(s) {
+			Err(x) => panic!("Failed to parse {}", x),
+			Ok(None) => (),
+			Ok(_) => panic!("This should have been ignored"),
+		};
+	}
+
+	#[test]
+	fn errors() {
+		let s = "Aug  4 00:00:08 yuuai spamd[2841]: spamd: result: M 12 - BAYES_99 scantime=1.2".to_string();
+		match parse_spamd(s) {
+			Err(ParseError::SpamdBadResult) => (),
+			Err(x) => panic!("Wrong error, should have been SpamdBadResult {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(_) => panic!("This should have failed")
+		};
+		let s = "Aug  4 00:00:08 yuuai spamd[2841]: spamd: identified spam (high/5.0) for nobody:65534 in 1.2 seconds, 3456 bytes.".to_string();
+		match parse_spamd(s) {
+			Err(ParseError::SpamdBadScore) => (),
+			Err(x) => panic!("Wrong error, should have been SpamdBadScore {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(_) => panic!("This should have failed")
+		};
+	}
+
+	#[test]
+	fn valid_result() {
+		let s = "Aug  4 00:00:08 yuuai spamd[2841]: spamd: result: Y 12 - BAYES_99,HTML_MESSAGE scantime=1.2,size=3456,user=nobody,uid=65534,required_score=5.0,rhost=localhost,raddr=127.0.0.1,rport=45678,mid=<abc@bbb.ccc>,bayes=0.999,autolearn=no".to_string();
+		let spamd = match parse_spamd(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::Spamd{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		assert_eq!(spamd.verdict.action, SpamAction::Spam);
+		assert!(spamd.verdict.is_spam());
+		assert_eq!(spamd.verdict.score, Some(12.0));
+		assert_eq!(spamd.verdict.threshold, Some(5.0));
+		assert_eq!(spamd.verdict.symbols, vec!["BAYES_99".to_string(), "HTML_MESSAGE".to_string()]);
+		assert_eq!(spamd.user(), Some("nobody"));
+		assert_eq!(spamd.message_id(), Some("abc@bbb.ccc"));
+		assert_eq!(spamd.size, Some(3456));
+		assert_eq!(spamd.scantime, Some(1.2));
+		assert_eq!(fmt::format(format_args!("{:?}", spamd)), "Spamd { inner: Inner { raw: \"Aug  4 00:00:08 yuuai spamd[2841]: spamd: result: Y 12 - BAYES_99,HTML_MESSAGE scantime=1.2,size=3456,user=nobody,uid=65534,required_score=5.0,rhost=localhost,raddr=127.0.0.1,rport=45678,mid=<abc@bbb.ccc>,bayes=0.999,autolearn=no\", host_e: 21, queue_s: 22, queue_e: 22, process: Spamd, pid: 2841, queue_id_s: 0, queue_id_e: 0 }, verdict: SpamVerdict { action: Spam, score: Some(12.0), threshold: Some(5.0), symbols: [\"BAYES_99\", \"HTML_MESSAGE\"] }, user_s: 107, user_e: 113, message_id_s: 192, message_id_e: 203, size: Some(3456), scantime: Some(1.2) }");
+	}
+
+	#[test]
+	fn valid_summary() {
+		let s = "Aug  4 00:00:08 yuuai spamd[2841]: spamd: clean message (-0.9/5.0) for nobody:65534 in 0.5 seconds, 1234 bytes.".to_string();
+		let spamd = match parse_spamd(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::Spamd{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		assert_eq!(spamd.verdict.action, SpamAction::Ham);
+		assert!(!spamd.verdict.is_spam());
+		assert_eq!(spamd.verdict.score, Some(-0.9));
+		assert_eq!(spamd.verdict.threshold, Some(5.0));
+		assert!(spamd.verdict.symbols.is_empty());
+		assert_eq!(spamd.user(), Some("nobody"));
+		assert_eq!(spamd.message_id(), None);
+		assert_eq!(spamd.size, Some(1234));
+		assert_eq!(spamd.scantime, Some(0.5));
+	}
+}
@@ -0,0 +1,213 @@
+use std::fmt;
+use std::ops::Deref;
+use super::super::ParseError;
+use super::Inner;
+use super::Message;
+use super::MessageParser;
+
+pub enum SubmissionLevel {
+	Warning,
+	Error,
+	Fatal,
+	Panic,
+}
+
+#[derive(Debug)]
+pub struct Submission {
+	inner: Inner,
+	pub level: SubmissionLevel,
+	user_s: usize,
+	user_e: usize,
+	pub uid: Option<u32>,
+	message_s: usize,
+	message_e: usize,
+}
+
+impl Deref for Submission {
+	type Target = Inner;
+	fn deref(&self) -> &Inner {
+		&self.inner
+	}
+}
+
+impl fmt::Display for SubmissionLevel {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		let level = match self {
+			&SubmissionLevel::Warning => "warning",
+			&SubmissionLevel::Error => "error",
+			&SubmissionLevel::Fatal => "fatal",
+			&SubmissionLevel::Panic => "panic",
+		};
+		write!(fmt, "{}", level)
+	}
+}
+
+impl fmt::Debug for SubmissionLevel {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		fmt::Display::fmt(self, fmt)
+	}
+}
+
+impl Submission {
+	pub fn user <'a>(&'a self) -> Option<&'a str> {
+		if self.user_e != 0 {
+			Some(&self.raw[self.user_s..self.user_e])
+		} else {
+			None
+		}
+	}
+
+	pub fn message <'a>(&'a self) -> &'a str {
+		&self.raw[self.message_s..self.message_e]
+	}
+}
+
+impl MessageParser for Submission {
+	fn parse(inner: Inner, start: usize) -> Result<Option<Message>, ParseError> {
+		let (level, user_s, user_e, uid, message_s, message_e) = {
+			let rest = &inner.raw[start..];
+			let (level, offset) = {
+				if rest.starts_with(" warning: ") {
+					(SubmissionLevel::Warning, 10)
+				} else if rest.starts_with(" error: ") {
+					(SubmissionLevel::Error, 8)
+				} else if rest.starts_with(" fatal: ") {
+					(SubmissionLevel::Fatal, 8)
+				} else if rest.starts_with(" panic: ") {
+					(SubmissionLevel::Panic, 8)
+				} else {
+					return Err(ParseError::SubmissionUnknownLevel);
+				}
+			};
+			let rest = &rest[offset..];
+			let start = start + offset;
+			let message_e = inner.raw.len();
+			// postdrop logs "uid=1000: ...", sendmail logs "user(1000): ..."
+			if rest.starts_with("uid=") {
+				let len = match rest.find(": ") {
+					None => return Err(ParseError::SubmissionBadUID),
+					Some(l) => l
+				};
+				let uid = match rest[4..len].parse::<u32>() {
+					Err(_) => return Err(ParseError::SubmissionBadUID),
+					Ok(val) => val
+				};
+				(level, 0, 0, Some(uid), start + len + 2, message_e)
+			} else {
+				let (user_e, uid, message_s) = match rest.find(": ") {
+					None => (0, None, start),
+					Some(len) => {
+						let owner = &rest[..len];
+						if owner.ends_with(')') && !owner.contains(' ') {
+							match owner.find('(') {
+								None => (0, None, start),
+								Some(p) => match owner[p+1..len-1].parse::<u32>() {
+									Err(_) => (0, None, start),
+									Ok(val) => (start + p, Some(val), start + len + 2)
+								}
+							}
+						} else {
+							(0, None, start)
+						}
+					}
+				};
+				let user_s = if user_e != 0 { start } else { 0 };
+				(level, user_s, user_e, uid, message_s, message_e)
+			}
+		};
+		Ok(Some(Message::Submission { m: Submission { inner: inner, level: level, user_s: user_s, user_e: user_e, uid: uid, message_s: message_s, message_e: message_e } }))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fmt;
+	use super::*;
+	use super::super::Inner;
+	use super::super::Message;
+	use super::super::MessageParser;
+	use super::super::Process;
+	use super::super::super::ParserConfig;
+	use super::super::super::ParseError;
+
+	fn parse_submission(s: String) -> Result<Option<Message>, ParseError> {
+		let conf = ParserConfig::new(vec!["clamsmtpd".to_string()]);
+		let (inner, start) = match Inner::parse(&conf, s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some((x,y))) => (x,y)
+		};
+		Submission::parse(inner, start)
+	}
+
+	#[test]
+	fn unknown_level() {
+		let s = "Aug  4 00:00:03 yuuai postfix/sendmail[4242]: something happened".to_string();
+		match parse_submission(s) {
+			Err(ParseError::SubmissionUnknownLevel) => (),
+			Err(x) => panic!("Wrong error, should have been SubmissionUnknownLevel {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(_) => panic!("This should have failed")
+		};
+	}
+
+	#[test]
+	fn bad_uid() {
+		let s = "Aug  4 00:00:03 yuuai postfix/postdrop[4243]: warning: uid=xyz: File too large".to_string();
+		match parse_submission(s) {
+			Err(ParseError::SubmissionBadUID) => (),
+			Err(x) => panic!("Wrong error, should have been SubmissionBadUID {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(_) => panic!("This should have failed")
+		};
+	}
+
+	#[test]
+	fn valid_sendmail() {
+		let s = "Aug  4 00:00:03 yuuai postfix/sendmail[4242]: fatal: root(0): Recipient addresses must be specified on the command line or via the -t option".to_string();
+		let sub = match parse_submission(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::Submission{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		assert_eq!(sub.process, Process::Sendmail);
+		match sub.level {
+			SubmissionLevel::Fatal => (),
+			x => panic!("Parsed wrong level: {}", x)
+		}
+		assert_eq!(sub.user(), Some("root"));
+		assert_eq!(sub.uid, Some(0));
+		assert_eq!(sub.message(), "Recipient addresses must be specified on the command line or via the -t option");
+		assert_eq!(fmt::format(format_args!("{:?}", sub)), "Submission { inner: Inner { raw: \"Aug  4 00:00:03 yuuai postfix/sendmail[4242]: fatal: root(0): Recipient addresses must be specified on the command line or via the -t option\", host_e: 21, queue_s: 22, queue_e: 29, process: Sendmail, pid: 4242, queue_id_s: 0, queue_id_e: 0 }, level: fatal, user_s: 53, user_e: 57, uid: Some(0), message_s: 62, message_e: 140 }");
+	}
+
+	#[test]
+	fn valid_postdrop() {
+		let s = "Aug  4 00:00:03 yuuai postfix/postdrop[4243]: warning: uid=1000: File too large".to_string();
+		let sub = match parse_submission(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::Submission{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		assert_eq!(sub.process, Process::Postdrop);
+		match sub.level {
+			SubmissionLevel::Warning => (),
+			x => panic!("Parsed wrong level: {}", x)
+		}
+		assert_eq!(sub.user(), None);
+		assert_eq!(sub.uid, Some(1000));
+		assert_eq!(sub.message(), "File too large");
+		let s = "Aug  4 00:00:03 yuuai postfix/postdrop[4243]: warning: unable to look up public/pickup: No such file or directory".to_string();
+		let sub = match parse_submission(s) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::Submission{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		assert_eq!(sub.user(), None);
+		assert_eq!(sub.uid, None);
+		assert_eq!(sub.message(), "unable to look up public/pickup: No such file or directory");
+	}
+}
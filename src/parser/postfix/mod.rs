@@ -1,8 +1,47 @@
+mod calendar;
 mod config;
 mod errors;
+mod merge;
 pub mod messages;
+mod noise;
 mod parse;
+mod query;
+mod session;
+#[cfg(feature = "store")]
+mod store;
+mod trace;
+mod transaction;
 
+pub use self::calendar::Calendar;
+pub use self::config::ParseFn;
 pub use self::config::ParserConfig;
+pub use self::config::parse_timezone;
+pub use self::errors::ConfigError;
+pub use self::errors::MergeError;
 pub use self::errors::ParseError;
+#[cfg(feature = "store")]
+pub use self::errors::StoreError;
+pub use self::noise::NoiseRule;
+pub use self::merge::MergeReader;
+pub use self::merge::SourcedMessage;
 pub use self::parse::parse_line;
+pub use self::query::Query;
+pub use self::query::Search;
+pub use self::session::Rejection;
+pub use self::session::SessionBuilder;
+pub use self::session::SmtpSession;
+#[cfg(feature = "store")]
+pub use self::store::Store;
+pub use self::trace::Hop;
+pub use self::trace::HopLink;
+pub use self::trace::MessageTracer;
+pub use self::transaction::Delivery;
+pub use self::transaction::MailTransaction;
+pub use self::transaction::Notification;
+pub use self::transaction::NotificationStatus;
+pub use self::transaction::OrphanReason;
+pub use self::transaction::RecipientOutcome;
+pub use self::transaction::RecipientStatus;
+pub use self::transaction::RelayError;
+pub use self::transaction::TransactionEnd;
+pub use self::transaction::TransactionTracker;
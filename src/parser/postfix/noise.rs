@@ -0,0 +1,175 @@
+use std::fmt;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use regex::Regex;
+
+#[derive(Debug)]
+enum NoiseMatch {
+	// Whole syslog identifier, "clamsmtpd" or "postfix-local/smtpd"
+	Identifier(String),
+	// Process name, whatever the postfix instance logging it
+	Process(String),
+	// Anything logged by a process
+	Any,
+	// Line after the hostname
+	Regex(Regex),
+}
+
+#[derive(Debug)]
+pub struct NoiseRule {
+	matcher: NoiseMatch,
+	message: Option<String>,
+	dropped: AtomicU64,
+}
+
+impl NoiseRule {
+	fn new(matcher: NoiseMatch) -> NoiseRule {
+		NoiseRule { matcher: matcher, message: None, dropped: AtomicU64::new(0) }
+	}
+
+	pub fn identifier(identifier: &str) -> NoiseRule {
+		NoiseRule::new(NoiseMatch::Identifier(identifier.to_string()))
+	}
+
+	pub fn process(process: &str) -> NoiseRule {
+		NoiseRule::new(NoiseMatch::Process(process.to_string()))
+	}
+
+	pub fn any() -> NoiseRule {
+		NoiseRule::new(NoiseMatch::Any)
+	}
+
+	pub fn regex(pattern: &str) -> Result<NoiseRule, ::regex::Error> {
+		match Regex::new(pattern) {
+			Err(x) => Err(x),
+			Ok(regex) => Ok(NoiseRule::new(NoiseMatch::Regex(regex)))
+		}
+	}
+
+	// Restricts the rule to messages starting with the given text, queue ID excluded
+	pub fn message(mut self, prefix: &str) -> NoiseRule {
+		self.message = Some(prefix.to_string());
+		self
+	}
+
+	pub fn dropped(&self) -> u64 {
+		self.dropped.load(Ordering::Relaxed)
+	}
+
+	// rest is the line after the hostname: "postfix/smtp[1234]: 0123ABC: message"
+	pub fn matches(&self, rest: &str) -> bool {
+		self.matches_as(rest, None)
+	}
+
+	// Same, process rules also matching the built-in process the logged one
+	// is an alias of
+	pub fn matches_as(&self, rest: &str, process: Option<&str>) -> bool {
+		let identifier_e = match rest.find(|c| c == '[' || c == ':') {
+			None => return false,
+			Some(p) => p
+		};
+		let identifier = &rest[..identifier_e];
+		let matched = match self.matcher {
+			NoiseMatch::Identifier(ref i) => identifier == i,
+			NoiseMatch::Process(ref p) => process == Some(p.as_str()) || match identifier.rfind('/') {
+				None => identifier == p,
+				Some(pos) => &identifier[pos + 1..] == p
+			},
+			NoiseMatch::Any => true,
+			NoiseMatch::Regex(ref r) => r.is_match(rest),
+		};
+		if !matched {
+			return false;
+		}
+		let prefix = match self.message {
+			None => return true,
+			Some(ref prefix) => prefix
+		};
+		let message = match rest[identifier_e..].find(": ") {
+			None => return false,
+			Some(p) => &rest[identifier_e + p + 2..]
+		};
+		let message = match message.find(": ") {
+			Some(p) if p > 0 && message[..p].bytes().all(|b| (b'0' <= b && b <= b'9') || (b'A' <= b && b <= b'F')) => &message[p + 2..],
+			_ => message
+		};
+		message.starts_with(prefix.as_str())
+	}
+
+	pub fn count(&self) {
+		self.dropped.fetch_add(1, Ordering::Relaxed);
+	}
+}
+
+impl fmt::Display for NoiseRule {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		let res = match self.matcher {
+			NoiseMatch::Identifier(ref i) => write!(fmt, "identifier {}", i),
+			NoiseMatch::Process(ref p) => write!(fmt, "process {}", p),
+			NoiseMatch::Any => write!(fmt, "any process"),
+			NoiseMatch::Regex(ref r) => write!(fmt, "regex {}", r),
+		};
+		if res.is_err() {
+			return res;
+		}
+		match self.message {
+			None => Ok(()),
+			Some(ref m) => write!(fmt, ", message \"{}\"", m)
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn identifier() {
+		let rule = NoiseRule::identifier("clam");
+		assert!(rule.matches("clam[42]: started"));
+		assert!(!rule.matches("clamav-milter[42]: started"));
+		let rule = NoiseRule::identifier("postfix-local/smtpd");
+		assert!(rule.matches("postfix-local/smtpd[42]: connect from localhost[127.0.0.1]"));
+		assert!(!rule.matches("postfix/smtpd[42]: connect from localhost[127.0.0.1]"));
+	}
+
+	#[test]
+	fn process() {
+		let rule = NoiseRule::process("anvil");
+		assert!(rule.matches("postfix/anvil[42]: statistics: max connection rate 1/60s"));
+		assert!(rule.matches("postfix-local/anvil[42]: statistics: max connection rate 1/60s"));
+		assert!(!rule.matches("postfix/smtpd[42]: connect from localhost[127.0.0.1]"));
+	}
+
+	#[test]
+	fn message() {
+		let rule = NoiseRule::process("smtp").message("lost connection with");
+		assert!(rule.matches("postfix/smtp[337]: C06251400063D: lost connection with smtp-in.orange.fr[193.252.22.65] while receiving the initial server greeting"));
+		assert!(rule.matches("postfix/smtp[337]: lost connection with smtp-in.orange.fr[193.252.22.65]"));
+		assert!(!rule.matches("postfix/smtp[337]: C06251400063D: to=<aaa@bbb.ccc>, relay=none, status=deferred (lost connection with smtp-in.orange.fr)"));
+		let rule = NoiseRule::any().message("warning: ");
+		assert!(rule.matches("postlicyd[42]: warning: cannot connect to the database"));
+	}
+
+	#[test]
+	fn alias() {
+		let rule = NoiseRule::process("smtp").message("lost connection with");
+		assert!(!rule.matches("postfix/smtp-amavis[337]: lost connection with 127.0.0.1[127.0.0.1]"));
+		assert!(rule.matches_as("postfix/smtp-amavis[337]: lost connection with 127.0.0.1[127.0.0.1]", Some("smtp")));
+		assert!(rule.matches_as("postfix/smtp[337]: lost connection with 127.0.0.1[127.0.0.1]", Some("smtp")));
+	}
+
+	#[test]
+	fn regex() {
+		let rule = NoiseRule::regex(r"^postfix(-\w+)?/smtpd\[\d+\]: (dis)?connect ").unwrap();
+		assert!(rule.matches("postfix-local/smtpd[42]: disconnect from localhost[127.0.0.1]"));
+		assert!(!rule.matches("postfix/smtpd[42]: 0123ABC: client=localhost[127.0.0.1]"));
+		assert!(NoiseRule::regex("(").is_err());
+	}
+
+	#[test]
+	fn display() {
+		assert_eq!(format!("{}", NoiseRule::process("smtp").message("lost connection with")), "process smtp, message \"lost connection with\"");
+		assert_eq!(format!("{}", NoiseRule::identifier("clamsmtpd")), "identifier clamsmtpd");
+	}
+}
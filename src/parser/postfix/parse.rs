@@ -1,4 +1,5 @@
 use super::ParserConfig;
+use super::config::ParseFn;
 use super::ParseError;
 use super::messages::*;
 
@@ -8,20 +9,43 @@ pub fn parse_line(raw: String, conf: &ParserConfig) -> Result<Option<Message>, P
 		Ok(None) => return Ok(None),
 		Ok(Some((x,y))) => (x,y)
 	};
-	match inner.process {
-		Process::Anvil => Ok(None),
-		Process::Bounce => Bounce::parse(inner, start),
-		Process::Discard => Forward::parse(inner, start),
-		Process::Error => Forward::parse(inner, start),
-		Process::Pickup => Pickup::parse(inner, start),
-		Process::Pipe => Forward::parse(inner, start),
-		Process::Smtp => Forward::parse(inner, start),
-		Process::Local => Forward::parse(inner, start),
-		Process::Smtpd => Smtpd::parse(inner, start),
-		Process::Scache => Ok(None),
-		Process::Cleanup => Cleanup::parse(inner, start),
-		Process::Qmgr => Qmgr::parse(inner, start),
-	}
+	// Built-in parsers and the ones registered on the configuration share
+	// the MessageParser signature
+	let parser: ParseFn = match inner.process {
+		Process::Amavis => Amavis::parse,
+		Process::Anvil => return Ok(None),
+		Process::Bogofilter => Bogofilter::parse,
+		Process::Bounce => Bounce::parse,
+		Process::Clamsmtpd => Clamsmtpd::parse,
+		Process::Discard => Forward::parse,
+		Process::Dovecot => DovecotDelivery::parse,
+		Process::Error => Forward::parse,
+		Process::Opendkim => Opendkim::parse,
+		Process::Opendmarc => Opendmarc::parse,
+		Process::Pickup => Pickup::parse,
+		Process::Pipe => Forward::parse,
+		Process::PolicydSpf => Policy::parse,
+		Process::Postdrop => Submission::parse,
+		Process::Postgrey => Policy::parse,
+		Process::Postlicyd => Policy::parse,
+		Process::Proxymap => LookupError::parse,
+		Process::Smtp => Forward::parse,
+		Process::Local => Forward::parse,
+		Process::Rspamd => Rspamd::parse,
+		Process::Smtpd => Smtpd::parse,
+		Process::Spamd => Spamd::parse,
+		Process::Scache => return Ok(None),
+		Process::Sendmail => Submission::parse,
+		Process::Cleanup => Cleanup::parse,
+		Process::Custom(ref process) => match conf.parser(process) {
+			None => return Ok(None),
+			Some(parser) => parser,
+		},
+		Process::Qmgr => Qmgr::parse,
+		Process::TrivialRewrite => LookupError::parse,
+		Process::Verify => LookupError::parse,
+	};
+	parser(inner, start)
 }
 
 #[cfg(test)]
@@ -32,7 +56,7 @@ mod tests {
 	use super::super::messages::*;
 
 	fn parse(s: String) -> Result<Option<Message>, ParseError> {
-		let conf = ParserConfig { process_noise: vec!["clamsmtpd".to_string()] };
+		let conf = ParserConfig::new(vec!["clamsmtpd".to_string()]);
 		parse_line(s, &conf)
 	}
 
@@ -44,5 +68,43 @@ mod tests {
 			_ => panic!("Should have been ignored")
 		}
 	}
-}
+	#[derive(Debug, PartialEq)]
+	struct Decision {
+		action: String,
+	}
 
+	struct PolicyDaemon;
+
+	impl MessageParser for PolicyDaemon {
+		fn parse(inner: Inner, start: usize) -> Result<Option<Message>, ParseError> {
+			let action = inner.raw[start + 1..].to_string();
+			Ok(Some(Message::Custom { m: Custom::new(inner, Box::new(Decision { action: action })) }))
+		}
+	}
+
+	#[test]
+	fn custom() {
+		let mut conf = ParserConfig::new(vec![]);
+		conf.register::<PolicyDaemon>("policyd");
+		conf.register::<PolicyDaemon>("postgrey");
+		let custom = match parse_line("Sep  3 00:00:03 yuuai policyd[4242]: action=DUNNO".to_string(), &conf) {
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(None) => panic!("This should not have been ignored"),
+			Ok(Some(Message::Custom{m:x})) => x,
+			Ok(Some(x)) => panic!("Wrong message parsed: {:?}", x)
+		};
+		assert_eq!(custom.process, Process::Custom("policyd".to_string()));
+		assert_eq!(custom.pid, 4242);
+		assert_eq!(custom.data.downcast_ref::<Decision>(), Some(&Decision { action: "action=DUNNO".to_string() }));
+		match parse_line("Sep  3 00:00:03 yuuai postgrey[4242]: action=greylist, reason=new".to_string(), &conf) {
+			Ok(Some(Message::Custom{m:_})) => (),
+			Err(x) => panic!("Parser Error: {}", x),
+			Ok(x) => panic!("Built-in parser should have been overridden: {:?}", x)
+		}
+		match parse_line("Sep  3 00:00:03 yuuai policyd[4242]: action=DUNNO".to_string(), &ParserConfig::new(vec![])) {
+			Err(ParseError::NonEndingQueue) => (),
+			Err(x) => panic!("Wrong Error, should have been NonEndingQueue: {}", x),
+			Ok(_) => panic!("Unregistered process should have failed")
+		}
+	}
+}
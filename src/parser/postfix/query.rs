@@ -0,0 +1,266 @@
+use super::messages::Message;
+use super::session::SmtpSession;
+use super::transaction::MailTransaction;
+use super::transaction::TransactionTracker;
+
+// Criteria on MailTransactions, all of them must match. Addresses are
+// compared without case, "@example.com" matches the whole domain.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Query {
+	pub queue_id: Option<String>,
+	pub sender: Option<String>,
+	pub recipient: Option<String>,
+	pub message_id: Option<String>,
+	pub client_ip: Option<String>,
+	pub sasl_username: Option<String>,
+	pub after: Option<i64>,
+	pub before: Option<i64>,
+}
+
+fn address_matches(pattern: &str, address: &str) -> bool {
+	if pattern.starts_with('@') {
+		address.to_lowercase().ends_with(&pattern.to_lowercase())
+	} else {
+		address.eq_ignore_ascii_case(pattern)
+	}
+}
+
+// "host[192.0.2.1]" or "host[192.0.2.1]:25"
+pub fn client_ip(client: &str) -> Option<&str> {
+	let s = match client.find('[') {
+		None => return None,
+		Some(p) => p + 1
+	};
+	match client[s..].find(']') {
+		None => None,
+		Some(p) => Some(&client[s..s + p])
+	}
+}
+
+impl Query {
+	pub fn new() -> Query {
+		Query::default()
+	}
+
+	pub fn queue_id(mut self, queue_id: &str) -> Query {
+		self.queue_id = Some(queue_id.to_string());
+		self
+	}
+
+	pub fn sender(mut self, sender: &str) -> Query {
+		self.sender = Some(sender.to_string());
+		self
+	}
+
+	pub fn recipient(mut self, recipient: &str) -> Query {
+		self.recipient = Some(recipient.to_string());
+		self
+	}
+
+	pub fn message_id(mut self, message_id: &str) -> Query {
+		self.message_id = Some(message_id.trim_matches(|c| c == '<' || c == '>').to_string());
+		self
+	}
+
+	pub fn client_ip(mut self, ip: &str) -> Query {
+		self.client_ip = Some(ip.to_string());
+		self
+	}
+
+	pub fn sasl_username(mut self, username: &str) -> Query {
+		self.sasl_username = Some(username.to_string());
+		self
+	}
+
+	// Transactions with at least one line between after and before, in
+	// seconds since the UNIX epoch
+	pub fn between(mut self, after: Option<i64>, before: Option<i64>) -> Query {
+		self.after = after;
+		self.before = before;
+		self
+	}
+
+	fn in_range(&self, start: Option<i64>, end: Option<i64>) -> bool {
+		match (self.after, end) {
+			(Some(after), Some(end)) if end < after => return false,
+			_ => ()
+		};
+		match (self.before, start) {
+			(Some(before), Some(start)) if start > before => false,
+			_ => true
+		}
+	}
+
+	pub fn matches(&self, transaction: &MailTransaction) -> bool {
+		if let Some(ref queue_id) = self.queue_id {
+			if &transaction.queue_id != queue_id {
+				return false;
+			}
+		}
+		if let Some(ref sender) = self.sender {
+			let from = transaction.from.iter().chain(transaction.rejections.iter().flat_map(|r| r.from.iter()));
+			if !from.into_iter().any(|f| address_matches(sender, f)) {
+				return false;
+			}
+		}
+		if let Some(ref recipient) = self.recipient {
+			let to = transaction.deliveries.iter().flat_map(|d| Some(&d.to).into_iter().chain(d.orig_to.iter()))
+				.chain(transaction.rejections.iter().flat_map(|r| r.to.iter()));
+			if !to.into_iter().any(|t| address_matches(recipient, t)) {
+				return false;
+			}
+		}
+		if let Some(ref message_id) = self.message_id {
+			if transaction.message_id.as_ref() != Some(message_id) {
+				return false;
+			}
+		}
+		if let Some(ref ip) = self.client_ip {
+			if transaction.client.as_ref().and_then(|c| client_ip(c)) != Some(ip.as_str()) {
+				return false;
+			}
+		}
+		if let Some(ref username) = self.sasl_username {
+			if transaction.sasl_username.as_ref() != Some(username) {
+				return false;
+			}
+		}
+		self.in_range(transaction.start, transaction.last)
+	}
+
+	// Sessions only know the envelope of their rejections
+	pub fn matches_session(&self, session: &SmtpSession) -> bool {
+		if let Some(ref queue_id) = self.queue_id {
+			if !session.queue_ids.iter().any(|q| q == queue_id) {
+				return false;
+			}
+		}
+		if self.message_id.is_some() {
+			return false;
+		}
+		if let Some(ref sender) = self.sender {
+			if !session.rejections.iter().filter_map(|r| r.from.as_ref()).any(|f| address_matches(sender, f)) {
+				return false;
+			}
+		}
+		if let Some(ref recipient) = self.recipient {
+			if !session.rejections.iter().filter_map(|r| r.to.as_ref()).any(|t| address_matches(recipient, t)) {
+				return false;
+			}
+		}
+		if let Some(ref ip) = self.client_ip {
+			if session.client.as_ref().and_then(|c| client_ip(c)) != Some(ip.as_str()) {
+				return false;
+			}
+		}
+		if let Some(ref username) = self.sasl_username {
+			if session.sasl_username.as_ref() != Some(username) {
+				return false;
+			}
+		}
+		self.in_range(session.start, session.end)
+	}
+}
+
+// Runs a TransactionTracker over parsed messages and keeps the
+// transactions matching the query
+pub struct Search {
+	query: Query,
+	tracker: TransactionTracker,
+	results: Vec<MailTransaction>,
+}
+
+impl Search {
+	pub fn new(query: Query, tracker: TransactionTracker) -> Search {
+		Search { query: query, tracker: tracker, results: Vec::new() }
+	}
+
+	pub fn add(&mut self, message: &Message) {
+		for transaction in self.tracker.add(message) {
+			if self.query.matches(&transaction) {
+				self.results.push(transaction);
+			}
+		}
+	}
+
+	pub fn finish(mut self) -> Vec<MailTransaction> {
+		for transaction in self.tracker.flush() {
+			if self.query.matches(&transaction) {
+				self.results.push(transaction);
+			}
+		}
+		self.results
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::Calendar;
+	use super::super::ParserConfig;
+	use super::super::SessionBuilder;
+	use super::super::parse_line;
+
+	const LINES: [&'static str; 11] = [
+		"Aug  4 00:00:01 yuuai postfix/smtpd[1234]: 84ED020916: client=mail.example.com[192.0.2.1], sasl_method=PLAIN, sasl_username=aaa",
+		"Aug  4 00:00:01 yuuai postfix/cleanup[1235]: 84ED020916: message-id=<abc@example.com>",
+		"Aug  4 00:00:02 yuuai postfix/qmgr[1236]: 84ED020916: from=<aaa@example.com>, size=1234, nrcpt=1 (queue active)",
+		"Aug  4 00:00:03 yuuai postfix/local[1238]: 84ED020916: to=<bbb@yuuai.example>, orig_to=<postmaster@yuuai.example>, relay=local, delay=2, delays=1/0/0/1, dsn=2.0.0, status=sent (delivered to mailbox)",
+		"Aug  4 00:00:04 yuuai postfix/qmgr[1236]: 84ED020916: removed",
+		"Aug  4 10:00:01 yuuai postfix/pickup[1239]: 0345620AE4: uid=1000 from=<ccc>",
+		"Aug  4 10:00:01 yuuai postfix/cleanup[1235]: 0345620AE4: message-id=<def@yuuai.example>",
+		"Aug  4 10:00:02 yuuai postfix/qmgr[1236]: 0345620AE4: from=<ccc@yuuai.example>, size=1234, nrcpt=1 (queue active)",
+		"Aug  4 10:00:03 yuuai postfix/smtp[1237]: 0345620AE4: to=<xxx@Example.COM>, relay=mail.example.com[192.0.2.1]:25, delay=2, delays=1/0/0.5/0.5, dsn=2.0.0, status=sent (250 2.0.0 Ok: queued as 60F6120AF9)",
+		"Aug  4 10:00:04 yuuai postfix/qmgr[1236]: 0345620AE4: removed",
+		"Aug  4 10:00:05 yuuai postfix/smtpd[1240]: NOQUEUE: reject: RCPT from unknown[198.51.100.7]: 554 5.7.1 <xxx@yuuai.example>: Relay access denied; from=<spam@example.net> to=<xxx@yuuai.example> proto=ESMTP helo=<spam>",
+	];
+
+	fn search(query: Query) -> Vec<String> {
+		let conf = ParserConfig::new(vec![]);
+		let mut search = Search::new(query, TransactionTracker::new(3600, 100, Calendar::new(2015, 0)));
+		for line in LINES.iter() {
+			match parse_line(line.to_string(), &conf) {
+				Err(x) => panic!("Parser Error: {} on {}", x, line),
+				Ok(None) => (),
+				Ok(Some(message)) => search.add(&message),
+			}
+		}
+		search.finish().into_iter().map(|t| t.queue_id).collect()
+	}
+
+	#[test]
+	fn transactions() {
+		assert_eq!(search(Query::new()), vec!["84ED020916", "0345620AE4"]);
+		assert_eq!(search(Query::new().queue_id("0345620AE4")), vec!["0345620AE4"]);
+		assert_eq!(search(Query::new().sender("AAA@example.com")), vec!["84ED020916"]);
+		assert_eq!(search(Query::new().sender("@yuuai.example")), vec!["0345620AE4"]);
+		assert_eq!(search(Query::new().recipient("xxx@example.com")), vec!["0345620AE4"]);
+		assert_eq!(search(Query::new().recipient("postmaster@yuuai.example")), vec!["84ED020916"]);
+		assert_eq!(search(Query::new().message_id("<def@yuuai.example>")), vec!["0345620AE4"]);
+		assert_eq!(search(Query::new().client_ip("192.0.2.1")), vec!["84ED020916"]);
+		assert_eq!(search(Query::new().sasl_username("aaa")), vec!["84ED020916"]);
+		// 2015-08-04T10:00:00Z
+		let ten = 1438682400;
+		assert_eq!(search(Query::new().between(Some(ten), None)), vec!["0345620AE4"]);
+		assert_eq!(search(Query::new().between(None, Some(ten))), vec!["84ED020916"]);
+		assert_eq!(search(Query::new().sender("aaa@example.com").recipient("xxx@example.com")).len(), 0);
+	}
+
+	#[test]
+	fn sessions() {
+		let conf = ParserConfig::new(vec![]);
+		let mut builder = SessionBuilder::new(Calendar::new(2015, 0));
+		for line in LINES.iter() {
+			if let Ok(Some(message)) = parse_line(line.to_string(), &conf) {
+				builder.add(&message);
+			}
+		}
+		let sessions = builder.flush();
+		let query = Query::new().sender("spam@example.net");
+		assert_eq!(sessions.iter().filter(|s| query.matches_session(s)).map(|s| s.pid).collect::<Vec<_>>(), vec![1240]);
+		let query = Query::new().client_ip("198.51.100.7").recipient("@yuuai.example");
+		assert_eq!(sessions.iter().filter(|s| query.matches_session(s)).count(), 1);
+		let query = Query::new().client_ip("192.0.2.1");
+		assert_eq!(sessions.iter().filter(|s| query.matches_session(s)).map(|s| s.pid).collect::<Vec<_>>(), vec![1234]);
+	}
+}
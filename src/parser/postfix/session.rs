@@ -0,0 +1,266 @@
+use std::collections::HashMap;
+use super::calendar::Calendar;
+use super::messages::Message;
+use super::messages::Process;
+use super::messages::Reject;
+use super::messages::RejectReason;
+
+// An access action taken by smtpd during the session
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rejection {
+	pub reason: RejectReason,
+	pub queue_id: Option<String>,
+	pub message: String,
+	// Rejects before MAIL FROM or HELO have no sender or HELO name
+	pub from: Option<String>,
+	pub to: Option<String>,
+	pub helo: Option<String>,
+	// Seconds since the UNIX epoch
+	pub time: Option<i64>,
+}
+
+impl Rejection {
+	pub fn new(reject: &Reject, time: Option<i64>) -> Rejection {
+		Rejection {
+			reason: reject.reason,
+			queue_id: reject.queue_id().map(|q| q.to_string()),
+			message: reject.message().to_string(),
+			from: reject.from().map(|f| f.to_string()),
+			to: reject.to().map(|t| t.to_string()),
+			helo: reject.helo().map(|h| h.to_string()),
+			time: time,
+		}
+	}
+}
+
+// One SMTP connection, from connect to disconnect
+#[derive(Debug)]
+pub struct SmtpSession {
+	pub host: String,
+	pub queue: String,
+	pub pid: u32,
+	pub client: Option<String>,
+	pub helo: Option<String>,
+	pub sasl_username: Option<String>,
+	// Messages accepted during the session, in order
+	pub queue_ids: Vec<String>,
+	pub rejections: Vec<Rejection>,
+	// Command counters logged on disconnect, see SmtpdDisconnect::stats
+	pub stats: Option<String>,
+	// Seconds since the UNIX epoch
+	pub start: Option<i64>,
+	pub end: Option<i64>,
+	// Both the connect and disconnect lines were seen
+	pub complete: bool,
+	pub lines: Vec<String>,
+	connected: bool,
+}
+
+impl SmtpSession {
+	fn new(host: &str, queue: &str, pid: u32) -> SmtpSession {
+		SmtpSession { host: host.to_string(), queue: queue.to_string(), pid: pid, client: None, helo: None,
+		              sasl_username: None, queue_ids: Vec::new(), rejections: Vec::new(), stats: None,
+		              start: None, end: None, complete: false, lines: Vec::new(), connected: false }
+	}
+
+	// Without the connect line, the client is known from its other lines
+	fn add_client(&mut self, client: &str) {
+		if self.client.is_none() {
+			self.client = Some(client.to_string());
+		}
+	}
+
+	fn add_queue_id(&mut self, queue_id: Option<&str>) {
+		if let Some(queue_id) = queue_id {
+			if !self.queue_ids.iter().any(|q| q == queue_id) {
+				self.queue_ids.push(queue_id.to_string());
+			}
+		}
+	}
+}
+
+type Key = (String, String, u32);
+
+// Groups smtpd lines into SmtpSessions: an smtpd process serves a single
+// client at a time, so its pid identifies the session until disconnect.
+// Lines are dated by the builder's Calendar, or by the caller with add_at.
+pub struct SessionBuilder {
+	calendar: Calendar,
+	sessions: HashMap<Key, SmtpSession>,
+}
+
+impl SessionBuilder {
+	pub fn new(calendar: Calendar) -> SessionBuilder {
+		SessionBuilder { calendar: calendar, sessions: HashMap::new() }
+	}
+
+	pub fn pending(&self) -> usize {
+		self.sessions.len()
+	}
+
+	// Returns the sessions ended by this line: on disconnect, or the
+	// previous session of the process if its disconnect was never logged
+	pub fn add(&mut self, message: &Message) -> Option<SmtpSession> {
+		let time = self.calendar.epoch(message.inner());
+		self.add_dated(message, time)
+	}
+
+	// For messages already dated, like SourcedMessage::time
+	pub fn add_at(&mut self, message: &Message, time: i64) -> Option<SmtpSession> {
+		self.add_dated(message, Some(time))
+	}
+
+	fn add_dated(&mut self, message: &Message, time: Option<i64>) -> Option<SmtpSession> {
+		let inner = message.inner();
+		if inner.process != Process::Smtpd {
+			return None;
+		}
+		let key = (inner.host().to_string(), inner.queue().to_string(), inner.pid);
+		let previous = match message {
+			&Message::SmtpdConnect { m: _ } => self.sessions.remove(&key),
+			_ => None,
+		};
+		let ended = {
+			let session = self.sessions.entry(key.clone()).or_insert_with(|| SmtpSession::new(inner.host(), inner.queue(), inner.pid));
+			if session.start.is_none() {
+				session.start = time;
+			}
+			if time.is_some() {
+				session.end = time;
+			}
+			session.lines.push(inner.raw.clone());
+			match message {
+				&Message::SmtpdConnect { ref m } => {
+					session.client = Some(m.client().to_string());
+					session.connected = true;
+					false
+				},
+				&Message::SmtpdDisconnect { ref m } => {
+					session.add_client(m.client());
+					session.stats = m.stats().map(|s| s.to_string());
+					session.complete = session.connected;
+					true
+				},
+				&Message::Smtpd { ref m } => {
+					session.add_client(m.client());
+					session.add_queue_id(m.queue_id());
+					false
+				},
+				&Message::SmtpdForward { ref m } => {
+					session.add_client(m.client());
+					session.add_queue_id(m.queue_id());
+					false
+				},
+				&Message::SmtpdLogin { ref m } => {
+					session.add_client(m.client());
+					session.add_queue_id(m.queue_id());
+					session.sasl_username = Some(m.sasl_username().to_string());
+					false
+				},
+				&Message::Reject { ref m } => {
+					// "RCPT from host[192.0.2.1]: 554 5.7.1 ..."
+					let message = m.message();
+					if let (Some(s), Some(e)) = (message.find(" from "), message.find("]: ")) {
+						if s + 6 < e {
+							session.add_client(&message[s + 6..e + 1]);
+						}
+					}
+					if let Some(helo) = m.helo() {
+						session.helo = Some(helo.to_string());
+					}
+					session.rejections.push(Rejection::new(m, time));
+					false
+				},
+				_ => false,
+			}
+		};
+		if previous.is_some() {
+			return previous;
+		}
+		if !ended {
+			return None;
+		}
+		self.sessions.remove(&key)
+	}
+
+	// Sessions still open at the end of the input
+	pub fn flush(&mut self) -> Vec<SmtpSession> {
+		let mut sessions: Vec<SmtpSession> = self.sessions.drain().map(|(_, s)| s).collect();
+		sessions.sort_by_key(|s| s.start);
+		sessions
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::ParserConfig;
+	use super::super::parse_line;
+
+	fn feed(builder: &mut SessionBuilder, lines: &[&str]) -> Vec<SmtpSession> {
+		let conf = ParserConfig::new(vec![]);
+		let mut done = Vec::new();
+		for line in lines {
+			match parse_line(line.to_string(), &conf) {
+				Err(x) => panic!("Parser Error: {} on {}", x, line),
+				Ok(None) => (),
+				Ok(Some(message)) => done.extend(builder.add(&message)),
+			}
+		}
+		done
+	}
+
+	#[test]
+	fn session() {
+		let mut builder = SessionBuilder::new(Calendar::new(2015, 0));
+		let done = feed(&mut builder, &[
+			"Aug  4 00:00:08 yuuai postfix/smtpd[20518]: connect from mx1[129.104.30.34]",
+			"Aug  4 00:00:08 yuuai postfix/smtpd[20519]: connect from unknown[192.0.2.1]",
+			"Aug  4 00:00:08 yuuai postfix/smtpd[20519]: NOQUEUE: reject: CONNECT from unknown[192.0.2.1]: 554 5.7.1 Service unavailable; Client host [192.0.2.1] blocked using zen.spamhaus.org; proto=SMTP",
+			"Aug  4 00:00:09 yuuai postfix/smtpd[20518]: NOQUEUE: reject: RCPT from mx1[129.104.30.34]: 554 5.7.1 <xxx@yyy.zzz>: Relay access denied; from=<aaa@bbb.ccc> to=<xxx@yyy.zzz> proto=ESMTP helo=<mx1.bbb.ccc>",
+			"Aug  4 00:00:09 yuuai postfix/smtpd[20518]: 84ED020916: client=mx1[129.104.30.34]",
+			"Aug  4 00:00:10 yuuai postfix/cleanup[20520]: 84ED020916: message-id=<abc@bbb.ccc>",
+			"Aug  4 00:00:10 yuuai postfix/smtpd[20518]: 0345620AE4: client=mx1[129.104.30.34], sasl_method=PLAIN, sasl_username=aaa",
+			"Aug  4 00:00:11 yuuai postfix/smtpd[20518]: disconnect from mx1[129.104.30.34] ehlo=1 mail=3 rcpt=2/3 data=2 quit=1 commands=9/10",
+		]);
+		assert_eq!(done.len(), 1);
+		assert_eq!(builder.pending(), 1);
+		let session = &done[0];
+		assert_eq!(session.pid, 20518);
+		assert_eq!(session.client, Some("mx1[129.104.30.34]".to_string()));
+		assert_eq!(session.helo, Some("mx1.bbb.ccc".to_string()));
+		assert_eq!(session.sasl_username, Some("aaa".to_string()));
+		assert_eq!(session.queue_ids, vec!["84ED020916".to_string(), "0345620AE4".to_string()]);
+		assert_eq!(session.rejections.len(), 1);
+		assert!(session.rejections[0].reason == RejectReason::Reject);
+		assert_eq!(session.rejections[0].to, Some("xxx@yyy.zzz".to_string()));
+		assert_eq!(session.stats, Some("ehlo=1 mail=3 rcpt=2/3 data=2 quit=1 commands=9/10".to_string()));
+		assert_eq!(session.end.unwrap() - session.start.unwrap(), 3);
+		assert!(session.complete);
+		assert_eq!(session.lines.len(), 5);
+		let sessions = builder.flush();
+		assert_eq!(sessions.len(), 1);
+		assert_eq!(sessions[0].pid, 20519);
+		assert!(!sessions[0].complete);
+		assert_eq!(sessions[0].helo, None);
+		assert_eq!(sessions[0].rejections.len(), 1);
+		assert_eq!(sessions[0].rejections[0].from, None);
+		assert_eq!(sessions[0].rejections[0].helo, None);
+	}
+
+	#[test]
+	fn missing_lines() {
+		let mut builder = SessionBuilder::new(Calendar::new(2015, 0));
+		let done = feed(&mut builder, &[
+			"Aug  4 00:00:09 yuuai postfix/smtpd[20518]: 84ED020916: client=mx1[129.104.30.34]",
+			"Aug  4 00:00:11 yuuai postfix/smtpd[20518]: disconnect from mx1[129.104.30.34]",
+			"Aug  4 00:00:12 yuuai postfix/smtpd[20518]: connect from mx2[129.104.30.35]",
+			"Aug  4 00:00:13 yuuai postfix/smtpd[20518]: connect from mx1[129.104.30.34]",
+		]);
+		assert_eq!(done.len(), 2);
+		assert_eq!(done[0].queue_ids, vec!["84ED020916".to_string()]);
+		assert!(!done[0].complete);
+		assert_eq!(done[1].client, Some("mx2[129.104.30.35]".to_string()));
+		assert!(!done[1].complete);
+	}
+}
@@ -0,0 +1,551 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::path::Path;
+use rusqlite::Connection;
+use rusqlite::OptionalExtension;
+use rusqlite::params_from_iter;
+use super::calendar::Calendar;
+use super::config::ParserConfig;
+use super::errors::StoreError;
+use super::parse::parse_line;
+use super::query::Query;
+use super::query::client_ip;
+use super::transaction::MailTransaction;
+use super::transaction::TransactionEnd;
+use super::transaction::TransactionTracker;
+
+const SCHEMA: &'static str = "
+CREATE TABLE IF NOT EXISTS transactions (
+	id INTEGER PRIMARY KEY,
+	parent_id INTEGER REFERENCES transactions(id),
+	-- Path of the log, NULL when inserted directly
+	source TEXT,
+	host TEXT NOT NULL,
+	queue TEXT NOT NULL,
+	queue_id TEXT NOT NULL,
+	client_ip TEXT,
+	sasl_username TEXT,
+	message_id TEXT,
+	start INTEGER,
+	last INTEGER,
+	end_reason TEXT
+);
+CREATE INDEX IF NOT EXISTS transactions_parent ON transactions(parent_id);
+CREATE INDEX IF NOT EXISTS transactions_queue_id ON transactions(queue_id);
+CREATE INDEX IF NOT EXISTS transactions_message_id ON transactions(message_id);
+CREATE INDEX IF NOT EXISTS transactions_client_ip ON transactions(client_ip);
+CREATE INDEX IF NOT EXISTS transactions_start ON transactions(start);
+CREATE INDEX IF NOT EXISTS transactions_source ON transactions(source, end_reason);
+CREATE TABLE IF NOT EXISTS addresses (
+	transaction_id INTEGER NOT NULL REFERENCES transactions(id),
+	kind TEXT NOT NULL,
+	address TEXT NOT NULL COLLATE NOCASE
+);
+CREATE INDEX IF NOT EXISTS addresses_address ON addresses(address, kind);
+CREATE INDEX IF NOT EXISTS addresses_transaction ON addresses(transaction_id);
+CREATE TABLE IF NOT EXISTS lines (
+	id INTEGER PRIMARY KEY,
+	transaction_id INTEGER NOT NULL REFERENCES transactions(id),
+	line TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS lines_transaction ON lines(transaction_id);
+CREATE TABLE IF NOT EXISTS sources (
+	path TEXT PRIMARY KEY,
+	position INTEGER NOT NULL,
+	head TEXT NOT NULL,
+	-- Calendar::state after the last line read
+	year INTEGER NOT NULL,
+	previous INTEGER
+);
+";
+
+// A transaction and its notifications, ?1 being the top one
+const TREE: &'static str = "WITH RECURSIVE tree(id) AS (SELECT ?1 UNION ALL SELECT t.id FROM transactions t JOIN tree ON t.parent_id = tree.id)";
+
+// Longer than any log, so that replayed transactions never time out
+const REPLAY_TIMEOUT: u32 = 366 * 24 * 3600;
+
+fn end_from_str(end: &str) -> Option<TransactionEnd> {
+	match end {
+		"removed" => Some(TransactionEnd::Removed),
+		"expired" => Some(TransactionEnd::Expired),
+		"timeout" => Some(TransactionEnd::Timeout),
+		"evicted" => Some(TransactionEnd::Evicted),
+		"flushed" => Some(TransactionEnd::Flushed),
+		_ => None,
+	}
+}
+
+fn restore_end(transaction: &mut MailTransaction, ends: &HashMap<String, TransactionEnd>) {
+	if let Some(end) = ends.get(&transaction.queue_id) {
+		transaction.end = Some(*end);
+	}
+	for notification in transaction.notifications.iter_mut() {
+		if let Some(ref mut child) = notification.transaction {
+			restore_end(child, ends);
+		}
+	}
+}
+
+fn insert(conn: &Connection, transaction: &MailTransaction, parent: Option<i64>, source: Option<&str>) -> Result<i64, ::rusqlite::Error> {
+	let res = conn.execute("INSERT INTO transactions (parent_id, source, host, queue, queue_id, client_ip, sasl_username, message_id, start, last, end_reason) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+	                       (parent, source, &transaction.host, &transaction.queue, &transaction.queue_id,
+	                        transaction.client.as_ref().and_then(|c| client_ip(c)), &transaction.sasl_username,
+	                        &transaction.message_id, transaction.start, transaction.last,
+	                        transaction.end.map(|e| e.to_string())));
+	if let Err(x) = res {
+		return Err(x);
+	}
+	let id = conn.last_insert_rowid();
+	let mut addresses: Vec<(&str, &str)> = transaction.from.iter().chain(transaction.rejections.iter().flat_map(|r| r.from.iter()))
+		.map(|f| ("from", f.as_str()))
+		.chain(transaction.deliveries.iter().flat_map(|d| Some(&d.to).into_iter().chain(d.orig_to.iter()))
+		       .chain(transaction.rejections.iter().flat_map(|r| r.to.iter()))
+		       .map(|t| ("to", t.as_str())))
+		.collect();
+	addresses.sort();
+	addresses.dedup();
+	for (kind, address) in addresses {
+		if let Err(x) = conn.execute("INSERT INTO addresses (transaction_id, kind, address) VALUES (?1, ?2, ?3)", (id, kind, address)) {
+			return Err(x);
+		}
+	}
+	for line in transaction.lines.iter() {
+		if let Err(x) = conn.execute("INSERT INTO lines (transaction_id, line) VALUES (?1, ?2)", (id, line)) {
+			return Err(x);
+		}
+	}
+	for notification in transaction.notifications.iter() {
+		if let Some(ref child) = notification.transaction {
+			if let Err(x) = insert(conn, child, Some(id), source) {
+				return Err(x);
+			}
+		}
+	}
+	Ok(id)
+}
+
+// Lines of the transactions of a log still in progress at the end of its
+// previous index, removed from the store to be tracked again with the new
+// lines. Each line comes with the start of its transaction, to be dated.
+fn take_pending(conn: &Connection, source: &str) -> Result<Vec<(String, Option<i64>)>, ::rusqlite::Error> {
+	let res = conn.execute("
+		CREATE TEMP TABLE pending AS WITH RECURSIVE tree(id, start) AS (
+			SELECT id, start FROM transactions WHERE parent_id IS NULL AND source = ?1 AND end_reason = 'flushed'
+			UNION ALL SELECT t.id, tree.start FROM transactions t JOIN tree ON t.parent_id = tree.id
+		) SELECT id, start FROM tree", (source,));
+	if let Err(x) = res {
+		return Err(x);
+	}
+	let lines = match conn.prepare("SELECT l.line, p.start FROM lines l JOIN pending p ON l.transaction_id = p.id ORDER BY l.id") {
+		Err(x) => Err(x),
+		Ok(mut stmt) => stmt.query_map((), |row| row.get(0).and_then(|l| row.get(1).map(|s| (l, s)))).and_then(|rows| rows.collect())
+	};
+	let res = conn.execute_batch("
+		DELETE FROM lines WHERE transaction_id IN (SELECT id FROM pending);
+		DELETE FROM addresses WHERE transaction_id IN (SELECT id FROM pending);
+		DELETE FROM transactions WHERE id IN (SELECT id FROM pending);
+		DROP TABLE pending;");
+	match res {
+		Err(x) => Err(x),
+		Ok(()) => lines
+	}
+}
+
+// Prefilters on indexed columns, Query::matches has the last word
+fn query_sql(query: &Query) -> (String, Vec<String>) {
+	let mut sql = "SELECT t.id FROM transactions t WHERE t.parent_id IS NULL".to_string();
+	let mut params = Vec::new();
+	for &(column, ref value) in [("queue_id", &query.queue_id), ("message_id", &query.message_id),
+	                             ("client_ip", &query.client_ip), ("sasl_username", &query.sasl_username)].iter() {
+		if let &Some(ref value) = *value {
+			params.push(value.clone());
+			sql.push_str(&format!(" AND t.{} = ?{}", column, params.len()));
+		}
+	}
+	for &(kind, ref value) in [("from", &query.sender), ("to", &query.recipient)].iter() {
+		if let &Some(ref value) = *value {
+			let (op, value) = match value.starts_with('@') {
+				true => ("LIKE", format!("%{}", value)),
+				false => ("=", value.clone()),
+			};
+			params.push(value);
+			sql.push_str(&format!(" AND EXISTS (SELECT 1 FROM addresses a WHERE a.transaction_id = t.id AND a.kind = '{}' AND a.address {} ?{})",
+			                      kind, op, params.len()));
+		}
+	}
+	if let Some(after) = query.after {
+		sql.push_str(&format!(" AND (t.last IS NULL OR t.last >= {})", after));
+	}
+	if let Some(before) = query.before {
+		sql.push_str(&format!(" AND (t.start IS NULL OR t.start <= {})", before));
+	}
+	sql.push_str(" ORDER BY t.id");
+	(sql, params)
+}
+
+// Persistent index of MailTransactions in an SQLite database. Only the
+// searchable fields and the raw lines are stored: transactions are rebuilt
+// by parsing their lines again.
+pub struct Store {
+	conn: Connection,
+}
+
+impl Store {
+	pub fn open(path: &Path) -> Result<Store, StoreError> {
+		match Connection::open(path) {
+			Err(x) => Err(StoreError::Sqlite(x)),
+			Ok(conn) => Store::init(conn)
+		}
+	}
+
+	pub fn memory() -> Result<Store, StoreError> {
+		match Connection::open_in_memory() {
+			Err(x) => Err(StoreError::Sqlite(x)),
+			Ok(conn) => Store::init(conn)
+		}
+	}
+
+	fn init(conn: Connection) -> Result<Store, StoreError> {
+		match conn.execute_batch(SCHEMA) {
+			Err(x) => Err(StoreError::Sqlite(x)),
+			Ok(()) => Ok(Store { conn: conn })
+		}
+	}
+
+	pub fn insert(&mut self, transaction: &MailTransaction) -> Result<i64, StoreError> {
+		let tx = match self.conn.transaction() {
+			Err(x) => return Err(StoreError::Sqlite(x)),
+			Ok(tx) => tx
+		};
+		let id = match insert(&tx, transaction, None, None) {
+			Err(x) => return Err(StoreError::Sqlite(x)),
+			Ok(id) => id
+		};
+		match tx.commit() {
+			Err(x) => Err(StoreError::Sqlite(x)),
+			Ok(()) => Ok(id)
+		}
+	}
+
+	// Reads the lines appended to the log since its last index, a file with
+	// another first line is assumed rotated and read again. Transactions
+	// still in progress are stored as flushed and resumed on the next call.
+	// Lines are dated with the timezone of the ParserConfig, and on the
+	// first index of a log with its year, see Calendar::from_file; then from
+	// where the previous index left off.
+	// Returns the number of transactions stored.
+	pub fn index(&mut self, path: &Path, conf: &ParserConfig, mut tracker: TransactionTracker) -> Result<usize, StoreError> {
+		let source = path.to_string_lossy().into_owned();
+		let mut reader = match File::open(path) {
+			Err(x) => return Err(StoreError::Io(x)),
+			Ok(f) => BufReader::new(f)
+		};
+		let len = match reader.get_ref().metadata() {
+			Err(x) => return Err(StoreError::Io(x)),
+			Ok(m) => m.len()
+		};
+		let mut buffer: Vec<u8> = Vec::new();
+		if let Err(x) = reader.read_until(b'\n', &mut buffer) {
+			return Err(StoreError::Io(x));
+		}
+		let head = String::from_utf8_lossy(&buffer).into_owned();
+		let tx = match self.conn.transaction() {
+			Err(x) => return Err(StoreError::Sqlite(x)),
+			Ok(tx) => tx
+		};
+		let sql = "SELECT position, head, year, previous FROM sources WHERE path = ?1";
+		let state = tx.query_row(sql, (&source,), |row| row.get::<_, i64>(0).and_then(|p| row.get::<_, String>(1).and_then(|h| {
+			row.get::<_, i32>(2).and_then(|y| row.get::<_, Option<u32>>(3).map(|c| (p as u64, h, y, c)))
+		}))).optional();
+		let timezone = conf.timezone.unwrap_or(0);
+		let (position, mut calendar) = match state {
+			Err(x) => return Err(StoreError::Sqlite(x)),
+			Ok(Some((p, ref h, year, previous))) if p <= len && h == &head => (p, Calendar::resume(year, timezone, previous)),
+			// Rotated, the new file follows the old one
+			Ok(Some((_, _, year, previous))) => (0, Calendar::resume(year, timezone, previous)),
+			Ok(None) => match Calendar::from_file(reader.get_mut(), conf, timezone) {
+				Err(x) => return Err(StoreError::Io(x)),
+				Ok(calendar) => (0, calendar)
+			},
+		};
+		if let Err(x) = reader.seek(SeekFrom::Start(position)) {
+			return Err(StoreError::Io(x));
+		}
+		let pending = match take_pending(&tx, &source) {
+			Err(x) => return Err(StoreError::Sqlite(x)),
+			Ok(lines) => lines
+		};
+		let mut done = Vec::new();
+		for (line, start) in pending {
+			if let Ok(Some(message)) = parse_line(line, conf) {
+				let time = match start {
+					None => calendar.epoch(message.inner()),
+					Some(start) => message.inner().epoch_near(start, timezone)
+				};
+				match time {
+					// Undated
+					None => done.extend(tracker.add(&message)),
+					Some(time) => done.extend(tracker.add_at(&message, time)),
+				}
+			}
+		}
+		let mut position = position;
+		loop {
+			buffer.clear();
+			let len = match reader.read_until(b'\n', &mut buffer) {
+				Err(x) => return Err(StoreError::Io(x)),
+				Ok(len) => len
+			};
+			// Incomplete last line, still being written
+			if len == 0 || buffer[len - 1] != b'\n' {
+				break;
+			}
+			position += len as u64;
+			let line = String::from_utf8_lossy(&buffer[..len - 1]).into_owned();
+			match parse_line(line.clone(), conf) {
+				Err(x) => if conf.strict {
+					return Err(StoreError::Parse(x, line));
+				},
+				Ok(None) => (),
+				Ok(Some(message)) => match calendar.epoch(message.inner()) {
+					// Undated
+					None => done.extend(tracker.add(&message)),
+					Some(time) => done.extend(tracker.add_at(&message, time)),
+				},
+			}
+		}
+		done.extend(tracker.flush());
+		for transaction in done.iter() {
+			if let Err(x) = insert(&tx, transaction, None, Some(&source)) {
+				return Err(StoreError::Sqlite(x));
+			}
+		}
+		let (year, previous) = calendar.state();
+		if let Err(x) = tx.execute("INSERT OR REPLACE INTO sources (path, position, head, year, previous) VALUES (?1, ?2, ?3, ?4, ?5)",
+		                           (&source, position as i64, &head, year, previous)) {
+			return Err(StoreError::Sqlite(x));
+		}
+		match tx.commit() {
+			Err(x) => Err(StoreError::Sqlite(x)),
+			Ok(()) => Ok(done.len())
+		}
+	}
+
+	// Rebuilds a stored transaction and its notifications from their lines
+	pub fn load(&self, id: i64, conf: &ParserConfig) -> Result<Vec<MailTransaction>, StoreError> {
+		let lines: Result<Vec<String>, _> = match self.conn.prepare(&format!("{} SELECT line FROM lines WHERE transaction_id IN tree ORDER BY id", TREE)) {
+			Err(x) => Err(x),
+			Ok(mut stmt) => stmt.query_map((id,), |row| row.get(0)).and_then(|rows| rows.collect())
+		};
+		let ends: Result<Vec<(String, Option<String>)>, _> = match self.conn.prepare(&format!("{} SELECT queue_id, end_reason FROM transactions WHERE id IN tree", TREE)) {
+			Err(x) => Err(x),
+			Ok(mut stmt) => stmt.query_map((id,), |row| row.get(0).and_then(|q| row.get(1).map(|e| (q, e)))).and_then(|rows| rows.collect())
+		};
+		let start = self.conn.query_row("SELECT start FROM transactions WHERE id = ?1", (id,), |row| row.get::<_, Option<i64>>(0));
+		let (lines, ends, start) = match (lines, ends, start) {
+			(Err(x), _, _) | (_, Err(x), _) | (_, _, Err(x)) => return Err(StoreError::Sqlite(x)),
+			(Ok(lines), Ok(ends), Ok(start)) => (lines, ends, start)
+		};
+		// Timeouts and evictions depend on the lines around the transaction
+		let ends: HashMap<String, TransactionEnd> = ends.into_iter()
+			.filter_map(|(queue_id, end)| end.and_then(|e| end_from_str(&e)).map(|e| (queue_id, e)))
+			.collect();
+		// The lines are dated in the year closest to the start, see index
+		let timezone = conf.timezone.unwrap_or(0);
+		let mut tracker = TransactionTracker::new(REPLAY_TIMEOUT, usize::max_value(), Calendar::from_config(conf));
+		let mut transactions = Vec::new();
+		for line in lines {
+			match parse_line(line.clone(), conf) {
+				Err(x) => return Err(StoreError::Parse(x, line)),
+				Ok(None) => (),
+				Ok(Some(message)) => match start.and_then(|start| message.inner().epoch_near(start, timezone)) {
+					None => transactions.extend(tracker.add(&message)),
+					Some(time) => transactions.extend(tracker.add_at(&message, time)),
+				},
+			}
+		}
+		transactions.extend(tracker.flush());
+		for transaction in transactions.iter_mut() {
+			restore_end(transaction, &ends);
+		}
+		Ok(transactions)
+	}
+
+	pub fn search(&self, query: &Query, conf: &ParserConfig) -> Result<Vec<MailTransaction>, StoreError> {
+		let (sql, params) = query_sql(query);
+		let ids: Result<Vec<i64>, _> = match self.conn.prepare(&sql) {
+			Err(x) => Err(x),
+			Ok(mut stmt) => stmt.query_map(params_from_iter(params.iter()), |row| row.get(0)).and_then(|rows| rows.collect())
+		};
+		let ids = match ids {
+			Err(x) => return Err(StoreError::Sqlite(x)),
+			Ok(ids) => ids
+		};
+		let mut found = Vec::new();
+		for id in ids {
+			match self.load(id, conf) {
+				Err(x) => return Err(x),
+				Ok(transactions) => found.extend(transactions.into_iter().filter(|t| query.matches(t))),
+			}
+		}
+		Ok(found)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::env;
+	use std::fs;
+	use std::fs::OpenOptions;
+	use std::io::Write;
+	use std::path::PathBuf;
+	use std::time::Duration;
+	use std::time::UNIX_EPOCH;
+
+	const BOUNCE: [&'static str; 11] = [
+		"Aug  4 00:03:10 yuuai postfix/qmgr[1236]: 7C091208A3: from=<aaa@example.com>, size=1234, nrcpt=1 (queue active)",
+		"Aug  4 00:03:14 yuuai postfix/smtp[1237]: 7C091208A3: to=<xxx@yyy.zzz>, relay=mx.yyy.zzz[198.51.100.1]:25, delay=4, delays=1/0/2/1, dsn=5.1.1, status=bounced (host mx.yyy.zzz[198.51.100.1] said: 550 5.1.1 User unknown (in reply to RCPT TO command))",
+		"Aug  4 00:03:15 yuuai postfix/cleanup[1235]: A270E20915: message-id=<20150804000315.A270E20915@yuuai.example>",
+		"Aug  4 00:03:15 yuuai postfix/bounce[24350]: 7C091208A3: sender non-delivery notification: A270E20915",
+		"Aug  4 00:03:15 yuuai postfix/qmgr[1236]: 7C091208A3: removed",
+		"Aug  4 00:03:15 yuuai postfix/qmgr[1236]: A270E20915: from=<>, size=3456, nrcpt=1 (queue active)",
+		"Aug  4 00:03:16 yuuai postfix/smtp[1237]: A270E20915: to=<aaa@example.com>, relay=mx.example.com[192.0.2.2]:25, delay=1, delays=0/0/0.5/0.5, dsn=2.0.0, status=sent (250 2.0.0 Ok: queued as 60F6120AF9)",
+		"Aug  4 00:03:16 yuuai postfix/qmgr[1236]: A270E20915: removed",
+		"Aug  4 10:00:01 yuuai postfix/smtpd[1234]: 0345620AE4: client=mail.example.com[192.0.2.1], sasl_method=PLAIN, sasl_username=aaa",
+		"Aug  4 10:00:01 yuuai postfix/cleanup[1235]: 0345620AE4: message-id=<def@example.com>",
+		"Aug  4 10:00:02 yuuai postfix/qmgr[1236]: 0345620AE4: from=<aaa@example.com>, size=1234, nrcpt=1 (queue active)",
+	];
+
+	fn log(name: &str, lines: &[&str], append: bool) -> PathBuf {
+		let path = env::temp_dir().join(format!("maillog-store-{}-{}.log", name, ::std::process::id()));
+		let mut file = OpenOptions::new().create(true).write(true).append(append).truncate(!append).open(&path).unwrap();
+		for line in lines {
+			writeln!(file, "{}", line).unwrap();
+		}
+		path
+	}
+
+	fn queue_ids(transactions: &[MailTransaction]) -> Vec<&str> {
+		transactions.iter().map(|t| t.queue_id.as_str()).collect()
+	}
+
+	#[test]
+	fn search() {
+		let mut conf = ParserConfig::new(vec![]);
+		conf.year = Some(2015);
+		let mut store = Store::memory().unwrap();
+		let path = log("search", &BOUNCE, false);
+		assert_eq!(store.index(&path, &conf, TransactionTracker::new(3600, 100, Calendar::from_config(&conf))).unwrap(), 2);
+		fs::remove_file(&path).unwrap();
+		let found = store.search(&Query::new(), &conf).unwrap();
+		assert_eq!(queue_ids(&found), vec!["7C091208A3", "0345620AE4"]);
+		assert_eq!(found[0].end, Some(TransactionEnd::Removed));
+		assert_eq!(found[0].lines.len(), 4);
+		let ndr = found[0].notifications[0].transaction.as_ref().unwrap();
+		assert_eq!(ndr.queue_id, "A270E20915");
+		assert_eq!(ndr.deliveries[0].to, "aaa@example.com");
+		assert_eq!(found[1].end, Some(TransactionEnd::Flushed));
+		assert_eq!(queue_ids(&store.search(&Query::new().sender("AAA@example.com"), &conf).unwrap()), vec!["7C091208A3", "0345620AE4"]);
+		assert_eq!(queue_ids(&store.search(&Query::new().recipient("@YYY.zzz"), &conf).unwrap()), vec!["7C091208A3"]);
+		assert_eq!(queue_ids(&store.search(&Query::new().queue_id("0345620AE4"), &conf).unwrap()), vec!["0345620AE4"]);
+		assert_eq!(queue_ids(&store.search(&Query::new().message_id("<def@example.com>"), &conf).unwrap()), vec!["0345620AE4"]);
+		assert_eq!(queue_ids(&store.search(&Query::new().client_ip("192.0.2.1"), &conf).unwrap()), vec!["0345620AE4"]);
+		assert_eq!(queue_ids(&store.search(&Query::new().sasl_username("aaa"), &conf).unwrap()), vec!["0345620AE4"]);
+		// 2015-08-04T10:00:00Z
+		let ten = 1438682400;
+		assert_eq!(queue_ids(&store.search(&Query::new().between(None, Some(ten)), &conf).unwrap()), vec!["7C091208A3"]);
+		// Notifications are only found through the transaction they belong to
+		assert_eq!(store.search(&Query::new().queue_id("A270E20915"), &conf).unwrap().len(), 0);
+	}
+
+	#[test]
+	fn incremental() {
+		let conf = ParserConfig::new(vec![]);
+		let mut store = Store::memory().unwrap();
+		let path = log("incremental", &BOUNCE[8..], false);
+		assert_eq!(store.index(&path, &conf, TransactionTracker::new(3600, 100, Calendar::from_config(&conf))).unwrap(), 1);
+		log("incremental", &[
+			"Aug  4 10:00:03 yuuai postfix/smtp[1237]: 0345620AE4: to=<xxx@yyy.zzz>, relay=mx.yyy.zzz[198.51.100.1]:25, delay=2, delays=1/0/0.5/0.5, dsn=2.0.0, status=sent (250 2.0.0 Ok: queued as 60F6120AF9)",
+			"Aug  4 10:00:04 yuuai postfix/qmgr[1236]: 0345620AE4: removed",
+		], true);
+		// Partial line, left for the next index
+		OpenOptions::new().append(true).open(&path).unwrap().write_all(b"Aug  4 10:00:05 yuuai postfix/qmgr").unwrap();
+		assert_eq!(store.index(&path, &conf, TransactionTracker::new(3600, 100, Calendar::from_config(&conf))).unwrap(), 1);
+		let found = store.search(&Query::new(), &conf).unwrap();
+		assert_eq!(queue_ids(&found), vec!["0345620AE4"]);
+		assert_eq!(found[0].end, Some(TransactionEnd::Removed));
+		assert_eq!(found[0].lines.len(), 5);
+		assert_eq!(store.index(&path, &conf, TransactionTracker::new(3600, 100, Calendar::from_config(&conf))).unwrap(), 0);
+		// Rotated
+		log("incremental", &BOUNCE[..5], false);
+		assert_eq!(store.index(&path, &conf, TransactionTracker::new(3600, 100, Calendar::from_config(&conf))).unwrap(), 1);
+		fs::remove_file(&path).unwrap();
+		assert_eq!(queue_ids(&store.search(&Query::new(), &conf).unwrap()), vec!["0345620AE4", "7C091208A3"]);
+	}
+
+	#[test]
+	fn new_year() {
+		let mut conf = ParserConfig::new(vec![]);
+		conf.year = Some(2015);
+		let mut store = Store::memory().unwrap();
+		let path = log("new-year", &[
+			"Dec 31 23:59:58 yuuai postfix/smtpd[1234]: 0345620AE4: client=mail.example.com[192.0.2.1]",
+			"Dec 31 23:59:59 yuuai postfix/qmgr[1236]: 0345620AE4: from=<aaa@example.com>, size=1234, nrcpt=1 (queue active)",
+		], false);
+		assert_eq!(store.index(&path, &conf, TransactionTracker::new(3600, 100, Calendar::from_config(&conf))).unwrap(), 1);
+		log("new-year", &[
+			"Jan  1 00:00:01 yuuai postfix/smtp[1237]: 0345620AE4: to=<xxx@yyy.zzz>, relay=mx.yyy.zzz[198.51.100.1]:25, delay=3, delays=1/0/1/1, dsn=2.0.0, status=sent (250 2.0.0 Ok: queued as 60F6120AF9)",
+			"Jan  1 00:00:01 yuuai postfix/qmgr[1236]: 0345620AE4: removed",
+			"Jan  1 00:00:02 yuuai postfix/qmgr[1236]: 7C091208A3: from=<bbb@example.com>, size=1234, nrcpt=1 (queue active)",
+		], true);
+		// The second index goes on in 2016
+		assert_eq!(store.index(&path, &conf, TransactionTracker::new(3600, 100, Calendar::from_config(&conf))).unwrap(), 2);
+		fs::remove_file(&path).unwrap();
+		let found = store.search(&Query::new(), &conf).unwrap();
+		assert_eq!(queue_ids(&found), vec!["0345620AE4", "7C091208A3"]);
+		assert_eq!(found[0].end, Some(TransactionEnd::Removed));
+		// 2015-12-31T23:59:58Z
+		assert_eq!(found[0].start, Some(1451606398));
+		assert_eq!(found[0].last, Some(1451606401));
+		assert_eq!(queue_ids(&store.search(&Query::new().between(None, Some(1451606399)), &conf).unwrap()), vec!["0345620AE4"]);
+		assert_eq!(queue_ids(&store.search(&Query::new().between(Some(1451606402), None), &conf).unwrap()), vec!["7C091208A3"]);
+	}
+
+	#[test]
+	fn modified() {
+		let conf = ParserConfig::new(vec![]);
+		let mut store = Store::memory().unwrap();
+		let path = log("modified", &[
+			"Dec 31 23:59:59 yuuai postfix/qmgr[1236]: 0345620AE4: from=<aaa@example.com>, size=1234, nrcpt=1 (queue active)",
+			"Jan  1 00:00:02 yuuai postfix/qmgr[1236]: 7C091208A3: from=<bbb@example.com>, size=1234, nrcpt=1 (queue active)",
+		], false);
+		// No year configured: the log was last written 2016-01-01T00:10:00Z
+		OpenOptions::new().write(true).open(&path).unwrap().set_modified(UNIX_EPOCH + Duration::from_secs(1451607000)).unwrap();
+		assert_eq!(store.index(&path, &conf, TransactionTracker::new(3600, 100, Calendar::from_config(&conf))).unwrap(), 2);
+		fs::remove_file(&path).unwrap();
+		assert_eq!(queue_ids(&store.search(&Query::new().between(Some(1451606400), None), &conf).unwrap()), vec!["7C091208A3"]);
+		assert_eq!(queue_ids(&store.search(&Query::new().between(Some(1420070400), Some(1451606399)), &conf).unwrap()), vec!["0345620AE4"]);
+	}
+
+	#[test]
+	fn sources() {
+		let conf = ParserConfig::new(vec![]);
+		let mut store = Store::memory().unwrap();
+		let mx1 = log("mx1", &BOUNCE[8..], false);
+		let mx2 = log("mx2", &BOUNCE[..8], false);
+		assert_eq!(store.index(&mx1, &conf, TransactionTracker::new(3600, 100, Calendar::from_config(&conf))).unwrap(), 1);
+		// The transaction in progress in mx1 is left for the next index of mx1
+		assert_eq!(store.index(&mx2, &conf, TransactionTracker::new(3600, 100, Calendar::from_config(&conf))).unwrap(), 1);
+		fs::remove_file(&mx1).unwrap();
+		fs::remove_file(&mx2).unwrap();
+		let found = store.search(&Query::new(), &conf).unwrap();
+		assert_eq!(queue_ids(&found), vec!["0345620AE4", "7C091208A3"]);
+		assert_eq!(found[0].end, Some(TransactionEnd::Flushed));
+	}
+}
@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fmt;
+use super::calendar::Calendar;
+use super::messages::Message;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HopLink {
+	// Relayed by smtp/lmtp, "queued as" in the remote reply
+	Relay,
+	// Received by smtpd with orig_queue_id, after a content filter or
+	// another instance
+	Reinjection,
+	// Passed by amavisd-new, Queue-ID and queued_as
+	ContentFilter,
+}
+
+impl fmt::Display for HopLink {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		let link = match self {
+			&HopLink::Relay => "relay",
+			&HopLink::Reinjection => "reinjection",
+			&HopLink::ContentFilter => "content filter",
+		};
+		write!(fmt, "{}", link)
+	}
+}
+
+// A queue ID the message went through. Host and queue are unknown for
+// queue IDs only seen in the logs of another hop.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hop {
+	pub queue_id: String,
+	pub host: Option<String>,
+	pub queue: Option<String>,
+	pub message_id: Option<String>,
+	// Seconds since the UNIX epoch of the first line, see Calendar
+	pub time: Option<i64>,
+	// How this hop was reached from the previous one
+	pub via: Option<HopLink>,
+}
+
+impl Hop {
+	fn new(queue_id: &str) -> Hop {
+		Hop { queue_id: queue_id.to_string(), host: None, queue: None, message_id: None, time: None, via: None }
+	}
+}
+
+// Follows a message across postfix instances, hosts and content filters.
+// Queue IDs are assumed unique across all the traced logs, which are added
+// in time order so that the Calendar follows year changes.
+pub struct MessageTracer {
+	calendar: Calendar,
+	hops: HashMap<String, Hop>,
+	parent: HashMap<String, String>,
+	children: HashMap<String, Vec<String>>,
+	message_ids: HashMap<String, Vec<String>>,
+}
+
+impl MessageTracer {
+	pub fn new(calendar: Calendar) -> MessageTracer {
+		MessageTracer { calendar: calendar, hops: HashMap::new(), parent: HashMap::new(), children: HashMap::new(), message_ids: HashMap::new() }
+	}
+
+	pub fn add(&mut self, message: &Message) {
+		let time = self.calendar.epoch(message.inner());
+		// amavisd-new lines carry no postfix queue ID of their own
+		if let &Message::Amavis { ref m } = message {
+			if let (Some(parent), Some(child)) = (m.orig_queue_id(), m.queued_as()) {
+				self.link(parent, child, HopLink::ContentFilter);
+				if let Some(message_id) = m.message_id() {
+					self.message_id(parent, message_id);
+				}
+			}
+			return;
+		}
+		let inner = message.inner();
+		let queue_id = match inner.queue_id() {
+			None => return,
+			Some(q) => q
+		};
+		{
+			let hop = self.hops.entry(queue_id.to_string()).or_insert_with(|| Hop::new(queue_id));
+			if hop.host.is_none() {
+				hop.host = Some(inner.host().to_string());
+				hop.queue = Some(inner.queue().to_string());
+			}
+			if hop.time.is_none() {
+				hop.time = time;
+			}
+		}
+		match message {
+			&Message::Cleanup { ref m } => self.message_id(queue_id, m.message_id()),
+			&Message::Forward { ref m } => if let Some(child) = m.child_queue() {
+				self.link(queue_id, child, HopLink::Relay);
+			},
+			&Message::SmtpdForward { ref m } => self.link(m.orig_queue_id(), queue_id, HopLink::Reinjection),
+			_ => (),
+		}
+	}
+
+	fn message_id(&mut self, queue_id: &str, message_id: &str) {
+		if let Some(hop) = self.hops.get_mut(queue_id) {
+			hop.message_id = Some(message_id.to_string());
+		}
+		let queue_ids = self.message_ids.entry(message_id.to_string()).or_insert_with(Vec::new);
+		if !queue_ids.iter().any(|q| q == queue_id) {
+			queue_ids.push(queue_id.to_string());
+		}
+	}
+
+	fn link(&mut self, parent: &str, child: &str, via: HopLink) {
+		if parent == child || self.parent.contains_key(child) {
+			return;
+		}
+		self.hops.entry(parent.to_string()).or_insert_with(|| Hop::new(parent));
+		self.hops.entry(child.to_string()).or_insert_with(|| Hop::new(child)).via = Some(via);
+		self.parent.insert(child.to_string(), parent.to_string());
+		self.children.entry(parent.to_string()).or_insert_with(Vec::new).push(child.to_string());
+	}
+
+	fn root<'a>(&'a self, mut queue_id: &'a str) -> &'a str {
+		let mut seen = HashSet::new();
+		while let Some(parent) = self.parent.get(queue_id) {
+			if !seen.insert(queue_id) {
+				break;
+			}
+			queue_id = parent;
+		}
+		queue_id
+	}
+
+	fn walk<'a>(&'a self, queue_id: &'a str, seen: &mut HashSet<&'a str>, hops: &mut Vec<&'a Hop>) {
+		if !seen.insert(queue_id) {
+			return;
+		}
+		if let Some(hop) = self.hops.get(queue_id) {
+			hops.push(hop);
+		}
+		if let Some(children) = self.children.get(queue_id) {
+			for child in children {
+				self.walk(child, seen, hops);
+			}
+		}
+	}
+
+	// Every hop of the message with this queue ID or Message-ID, from the
+	// first submission to the last relay
+	pub fn trace<'a>(&'a self, id: &str) -> Vec<&'a Hop> {
+		let mut roots: Vec<&str> = match self.hops.get_key_value(id) {
+			Some((queue_id, _)) => vec![self.root(queue_id)],
+			None => match self.message_ids.get(id.trim_matches(|c| c == '<' || c == '>')) {
+				None => return Vec::new(),
+				Some(queue_ids) => queue_ids.iter().map(|q| self.root(q)).collect(),
+			}
+		};
+		roots.sort_by_key(|q| self.hops.get(*q).and_then(|h| h.time));
+		let mut seen = HashSet::new();
+		let mut hops = Vec::new();
+		for root in roots {
+			self.walk(root, &mut seen, &mut hops);
+		}
+		hops
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::Calendar;
+	use super::super::ParserConfig;
+	use super::super::parse_line;
+
+	fn tracer(lines: &[&str]) -> MessageTracer {
+		let conf = ParserConfig::new(vec![]);
+		let mut tracer = MessageTracer::new(Calendar::new(2015, 0));
+		for line in lines {
+			match parse_line(line.to_string(), &conf) {
+				Err(x) => panic!("Parser Error: {} on {}", x, line),
+				Ok(None) => (),
+				Ok(Some(message)) => tracer.add(&message),
+			}
+		}
+		tracer
+	}
+
+	#[test]
+	fn instances() {
+		let tracer = tracer(&[
+			"Aug  4 00:00:01 yuuai postfix-in/smtpd[1234]: 84ED020916: client=mail.example.com[192.0.2.1]",
+			"Aug  4 00:00:01 yuuai postfix-in/cleanup[1235]: 84ED020916: message-id=<abc@example.com>",
+			"Aug  4 00:00:02 yuuai postfix-in/smtp[1237]: 84ED020916: to=<xxx@yyy.zzz>, relay=127.0.0.1[127.0.0.1]:10025, delay=1, delays=0.5/0/0.2/0.3, dsn=2.0.0, status=sent (250 2.0.0 Ok: queued as 0345620AE4)",
+			"Aug  4 00:00:02 yuuai postfix-out/smtpd[1238]: 0345620AE4: client=localhost[127.0.0.1], orig_queue_id=84ED020916, orig_client=mail.example.com[192.0.2.1]",
+			"Aug  4 00:00:02 yuuai postfix-out/cleanup[1239]: 0345620AE4: message-id=<abc@example.com>",
+			"Aug  4 00:00:03 yuuai postfix-out/smtp[1240]: 0345620AE4: to=<xxx@yyy.zzz>, relay=mx.yyy.zzz[198.51.100.1]:25, delay=1, delays=0.5/0/0.2/0.3, dsn=2.0.0, status=sent (250 2.0.0 Ok: queued as C217620B0B)",
+		]);
+		let hops = tracer.trace("0345620AE4");
+		assert_eq!(hops.iter().map(|h| h.queue_id.as_str()).collect::<Vec<_>>(), vec!["84ED020916", "0345620AE4", "C217620B0B"]);
+		assert_eq!(hops[0].queue, Some("postfix-in".to_string()));
+		assert_eq!(hops[0].via, None);
+		assert_eq!(hops[1].queue, Some("postfix-out".to_string()));
+		assert_eq!(hops[1].via, Some(HopLink::Relay));
+		assert_eq!(hops[1].message_id, Some("abc@example.com".to_string()));
+		assert_eq!(hops[2].host, None);
+		assert_eq!(hops[2].via, Some(HopLink::Relay));
+		assert_eq!(tracer.trace("<abc@example.com>"), hops);
+		assert_eq!(tracer.trace("C217620B0B"), hops);
+		assert_eq!(tracer.trace("FFFFFFFFFF").len(), 0);
+	}
+
+	#[test]
+	fn reinjection() {
+		let tracer = tracer(&[
+			"Aug  4 00:00:02 yuuai postfix/smtpd[1238]: 0345620AE4: client=localhost[127.0.0.1], orig_queue_id=84ED020916, orig_client=mail.example.com[192.0.2.1]",
+		]);
+		let hops = tracer.trace("84ED020916");
+		assert_eq!(hops.len(), 2);
+		assert_eq!(hops[1].queue_id, "0345620AE4");
+		assert_eq!(hops[1].via, Some(HopLink::Reinjection));
+	}
+
+	#[test]
+	fn content_filter() {
+		let tracer = tracer(&[
+			"Aug  4 00:00:01 yuuai postfix/cleanup[1235]: 84ED020916: message-id=<abc@bbb.ccc>",
+			"Aug  4 00:00:08 yuuai amavis[2841]: (02841-01) Passed CLEAN {RelayedInbound}, [1.2.3.4]:5678 <aaa@bbb.ccc> -> <xxx@yyy.zzz>, Queue-ID: 84ED020916, Message-ID: <abc@bbb.ccc>, mail_id: Xc2Ja1Bc, Hits: -1.2, size: 1234, queued_as: 67D8720887, 1234 ms",
+			"Aug  4 00:00:08 yuuai postfix/cleanup[1235]: 67D8720887: message-id=<abc@bbb.ccc>",
+		]);
+		let hops = tracer.trace("abc@bbb.ccc");
+		assert_eq!(hops.iter().map(|h| h.queue_id.as_str()).collect::<Vec<_>>(), vec!["84ED020916", "67D8720887"]);
+		assert_eq!(hops[1].via, Some(HopLink::ContentFilter));
+		assert_eq!(hops[1].time, Some(1438646408));
+	}
+}
@@ -0,0 +1,851 @@
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::fmt;
+use super::calendar::Calendar;
+use super::messages::BounceKind;
+use super::messages::Message;
+use super::messages::Process;
+use super::session::Rejection;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransactionEnd {
+	// qmgr removed the message from the queue
+	Removed,
+	// qmgr returned the message to its sender
+	Expired,
+	// No line for this queue ID since the tracker timeout
+	Timeout,
+	// Pushed out by newer transactions once the tracker was full
+	Evicted,
+	// Still pending when the input ended
+	Flushed,
+}
+
+impl fmt::Display for TransactionEnd {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		let end = match self {
+			&TransactionEnd::Removed => "removed",
+			&TransactionEnd::Expired => "expired",
+			&TransactionEnd::Timeout => "timeout",
+			&TransactionEnd::Evicted => "evicted",
+			&TransactionEnd::Flushed => "flushed",
+		};
+		write!(fmt, "{}", end)
+	}
+}
+
+// Why the logs of a transaction look incomplete
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OrphanReason {
+	// No smtpd, pickup or cleanup line: the logs start after the message
+	// was received
+	MissingStart,
+	// Never removed from the queue: still pending when the input ended,
+	// or evicted
+	MissingEnd,
+	// No line for longer than the tracker timeout
+	Timeout,
+	// Returned to its sender by qmgr
+	Expired,
+}
+
+impl fmt::Display for OrphanReason {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		let reason = match self {
+			&OrphanReason::MissingStart => "missing start",
+			&OrphanReason::MissingEnd => "missing end",
+			&OrphanReason::Timeout => "timeout",
+			&OrphanReason::Expired => "expired",
+		};
+		write!(fmt, "{}", reason)
+	}
+}
+
+// Failure of one of the hosts tried before the relay of a Delivery, from a
+// ForwardError line
+#[derive(Debug, Clone, PartialEq)]
+pub struct RelayError {
+	pub host: String,
+	pub message: String,
+	pub time: Option<i64>,
+}
+
+// One delivery attempt, from a Forward line
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delivery {
+	pub to: String,
+	pub orig_to: Option<String>,
+	pub relay: String,
+	pub dsn: [u8; 3],
+	pub status: String,
+	pub child_queue_id: Option<String>,
+	// Seconds since the UNIX epoch, see Calendar
+	pub time: Option<i64>,
+	// Logged by the same smtp process just before this attempt
+	pub relay_errors: Vec<RelayError>,
+}
+
+impl Delivery {
+	// "sent", "deferred", "bounced"... without the remote reply
+	pub fn state(&self) -> &str {
+		match self.status.find(' ') {
+			None => &self.status,
+			Some(p) => &self.status[..p]
+		}
+	}
+
+	// Remote reply or local reason, in parentheses after the state
+	pub fn reply(&self) -> Option<&str> {
+		match (self.status.find(" ("), self.status.ends_with(')')) {
+			(Some(p), true) => Some(&self.status[p + 2..self.status.len() - 1]),
+			_ => None
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecipientStatus {
+	Sent,
+	// Still to be retried when the logs end
+	Deferred,
+	Bounced,
+	// Deferred until qmgr returned the message to its sender
+	Expired,
+}
+
+impl fmt::Display for RecipientStatus {
+	fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+		let status = match self {
+			&RecipientStatus::Sent => "sent",
+			&RecipientStatus::Deferred => "deferred",
+			&RecipientStatus::Bounced => "bounced",
+			&RecipientStatus::Expired => "expired",
+		};
+		write!(fmt, "{}", status)
+	}
+}
+
+// All the delivery attempts to one recipient of a transaction
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecipientOutcome {
+	pub to: String,
+	pub orig_to: Option<String>,
+	pub attempts: u32,
+	pub sent: u32,
+	pub deferred: u32,
+	pub bounced: u32,
+	// After the last attempt
+	pub status: RecipientStatus,
+	// Seconds from the first line of the transaction to the last attempt,
+	// unless still deferred
+	pub delay: Option<i64>,
+	// Every attempt, oldest first
+	pub history: Vec<Delivery>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NotificationStatus {
+	// Not delivered yet, or its transaction was not seen
+	Pending,
+	Delivered,
+	// The notification itself bounced: double bounce
+	Bounced,
+}
+
+// Bounce, delay or DSN message generated by postfix for a transaction
+#[derive(Debug)]
+pub struct Notification {
+	pub kind: BounceKind,
+	pub queue_id: String,
+	// The notification's own delivery, once complete
+	pub transaction: Option<Box<MailTransaction>>,
+}
+
+impl Notification {
+	pub fn status(&self) -> NotificationStatus {
+		let transaction = match self.transaction {
+			None => return NotificationStatus::Pending,
+			Some(ref t) => t
+		};
+		if transaction.end == Some(TransactionEnd::Expired) {
+			return NotificationStatus::Bounced;
+		}
+		match transaction.deliveries.last().map(|d| d.state()) {
+			Some("sent") => NotificationStatus::Delivered,
+			Some("bounced") => NotificationStatus::Bounced,
+			_ => NotificationStatus::Pending,
+		}
+	}
+}
+
+// Everything logged about one queue ID of one postfix instance
+#[derive(Debug)]
+pub struct MailTransaction {
+	pub host: String,
+	pub queue: String,
+	pub queue_id: String,
+	pub client: Option<String>,
+	pub sasl_username: Option<String>,
+	pub message_id: Option<String>,
+	pub from: Option<String>,
+	pub size: Option<u64>,
+	pub nrcpt: Option<u32>,
+	pub deliveries: Vec<Delivery>,
+	// smtpd and cleanup actions on this queue ID
+	pub rejections: Vec<Rejection>,
+	// Times of the first and last lines, in seconds since the UNIX epoch
+	pub start: Option<i64>,
+	pub last: Option<i64>,
+	pub end: Option<TransactionEnd>,
+	// Queue ID of the transaction this one notifies about
+	pub parent: Option<String>,
+	pub notifications: Vec<Notification>,
+	pub lines: Vec<String>,
+	started: bool,
+	removed: bool,
+	// ForwardErrors by smtp pid, and whether a Forward line already used them
+	relay_errors: Vec<(u32, RelayError, bool)>,
+}
+
+impl MailTransaction {
+	fn new(host: &str, queue: &str, queue_id: &str) -> MailTransaction {
+		MailTransaction { host: host.to_string(), queue: queue.to_string(), queue_id: queue_id.to_string(),
+		                  client: None, sasl_username: None, message_id: None, from: None, size: None, nrcpt: None,
+		                  deliveries: Vec::new(), rejections: Vec::new(), start: None, last: None, end: None,
+		                  parent: None, notifications: Vec::new(), lines: Vec::new(), started: false, removed: false,
+		                  relay_errors: Vec::new() }
+	}
+
+	fn add(&mut self, message: &Message, time: Option<i64>) {
+		let inner = message.inner();
+		if self.start.is_none() {
+			self.start = time;
+		}
+		if time.is_some() {
+			self.last = time;
+		}
+		self.lines.push(inner.raw.clone());
+		match inner.process {
+			Process::Smtpd | Process::Pickup | Process::Cleanup => self.started = true,
+			_ => (),
+		}
+		match message {
+			&Message::Smtpd { ref m } => self.client = Some(m.client().to_string()),
+			&Message::SmtpdForward { ref m } => self.client = Some(m.client().to_string()),
+			&Message::SmtpdLogin { ref m } => {
+				self.client = Some(m.client().to_string());
+				self.sasl_username = Some(m.sasl_username().to_string());
+			},
+			&Message::Reject { ref m } => {
+				if self.from.is_none() {
+					self.from = m.from().map(|f| f.to_string());
+				}
+				self.rejections.push(Rejection::new(m, time));
+			},
+			&Message::Cleanup { ref m } => self.message_id = Some(m.message_id().to_string()),
+			&Message::Pickup { ref m } => if self.from.is_none() {
+				self.from = Some(m.from().to_string());
+			},
+			// On retries, nrcpt only counts the recipients left
+			&Message::Qmgr { ref m } => {
+				self.from = m.from().map(|f| f.to_string());
+				self.size = Some(m.size);
+				if self.nrcpt.is_none() {
+					self.nrcpt = Some(m.nrcpt);
+				}
+			},
+			// The errors of a connection apply to all the recipients it
+			// then delivers
+			&Message::ForwardError { ref m } => {
+				self.relay_errors.retain(|&(pid, _, used)| pid != inner.pid || !used);
+				self.relay_errors.push((inner.pid, RelayError {
+					host: m.host().to_string(),
+					message: m.message().to_string(),
+					time: time,
+				}, false));
+			},
+			&Message::Forward { ref m } => {
+				let mut relay_errors = Vec::new();
+				for &mut (pid, ref error, ref mut used) in self.relay_errors.iter_mut() {
+					if pid == inner.pid {
+						relay_errors.push(error.clone());
+						*used = true;
+					}
+				}
+				self.deliveries.push(Delivery {
+					to: m.to().to_string(),
+					orig_to: m.orig_to().map(|t| t.to_string()),
+					relay: m.relay().to_string(),
+					dsn: m.dsn,
+					status: m.status().to_string(),
+					child_queue_id: m.child_queue().map(|q| q.to_string()),
+					time: time,
+					relay_errors: relay_errors,
+				});
+			},
+			&Message::Bounce { ref m } => self.notifications.push(Notification {
+				kind: m.kind,
+				queue_id: m.child_queue_id().to_string(),
+				transaction: None,
+			}),
+			// qmgr still logs "removed" once the message is returned
+			&Message::QmgrRemoved { m: _ } => {
+				self.removed = true;
+				if self.end.is_none() {
+					self.end = Some(TransactionEnd::Removed);
+				}
+			},
+			&Message::QmgrExpired { m: _ } => self.end = Some(TransactionEnd::Expired),
+			_ => (),
+		}
+	}
+
+	// Recipients in the order of their first attempt
+	pub fn recipients(&self) -> Vec<RecipientOutcome> {
+		let mut recipients: Vec<RecipientOutcome> = Vec::new();
+		for delivery in self.deliveries.iter() {
+			let pos = match recipients.iter().position(|r| r.to == delivery.to && r.orig_to == delivery.orig_to) {
+				Some(pos) => pos,
+				None => {
+					recipients.push(RecipientOutcome { to: delivery.to.clone(), orig_to: delivery.orig_to.clone(),
+					                                   attempts: 0, sent: 0, deferred: 0, bounced: 0,
+					                                   status: RecipientStatus::Deferred, delay: None, history: Vec::new() });
+					recipients.len() - 1
+				}
+			};
+			let recipient = &mut recipients[pos];
+			recipient.attempts += 1;
+			recipient.history.push(delivery.clone());
+			recipient.status = match delivery.state() {
+				"sent" => {
+					recipient.sent += 1;
+					RecipientStatus::Sent
+				},
+				"bounced" => {
+					recipient.bounced += 1;
+					RecipientStatus::Bounced
+				},
+				_ => {
+					recipient.deferred += 1;
+					RecipientStatus::Deferred
+				},
+			};
+			recipient.delay = match (self.start, delivery.time) {
+				(Some(start), Some(time)) => Some(time - start),
+				_ => None,
+			};
+		}
+		for recipient in recipients.iter_mut().filter(|r| r.status == RecipientStatus::Deferred) {
+			if self.end == Some(TransactionEnd::Expired) {
+				recipient.status = RecipientStatus::Expired;
+			} else {
+				recipient.delay = None;
+			}
+		}
+		recipients
+	}
+
+	// Recipients of the envelope with at least one attempt: local alias
+	// expansions share their orig_to
+	pub fn envelope_recipients(&self) -> usize {
+		let mut recipients: Vec<&str> = self.deliveries.iter().map(|d| d.orig_to.as_ref().unwrap_or(&d.to).as_str()).collect();
+		recipients.sort();
+		recipients.dedup();
+		recipients.len()
+	}
+
+	// The transaction ended without an attempt for each of the nrcpt
+	// recipients announced by qmgr, or with more recipients
+	pub fn nrcpt_mismatch(&self) -> bool {
+		match (self.end, self.nrcpt) {
+			(Some(TransactionEnd::Removed), Some(nrcpt)) | (Some(TransactionEnd::Expired), Some(nrcpt)) => nrcpt as usize != self.envelope_recipients(),
+			_ => false,
+		}
+	}
+
+	// Empty for a transaction logged from reception to removal
+	pub fn orphan_reasons(&self) -> Vec<OrphanReason> {
+		let mut reasons = Vec::new();
+		if !self.started {
+			reasons.push(OrphanReason::MissingStart);
+		}
+		match self.end {
+			Some(TransactionEnd::Timeout) => reasons.push(OrphanReason::Timeout),
+			Some(TransactionEnd::Expired) => reasons.push(OrphanReason::Expired),
+			_ if !self.removed => reasons.push(OrphanReason::MissingEnd),
+			_ => (),
+		}
+		reasons
+	}
+
+	// Removed from the queue, and all its notifications delivered or failed
+	fn complete(&self) -> bool {
+		self.removed && self.notifications.iter().all(|n| n.transaction.is_some())
+	}
+}
+
+type Key = (String, String, String);
+
+// Joins the lines sharing a queue ID into MailTransactions. Transactions are
+// returned once removed or expired, when no line was seen for timeout
+// seconds, or when more than max_pending are in progress. Lines are dated by
+// the tracker's Calendar, or by the caller with add_at.
+pub struct TransactionTracker {
+	timeout: u32,
+	max_pending: usize,
+	calendar: Calendar,
+	now: Option<i64>,
+	pending: HashMap<Key, MailTransaction>,
+	// Notifications still in progress, with the transaction they notify about
+	parents: HashMap<Key, Key>,
+	// Oldest first, with the last activity when queued
+	order: VecDeque<(Key, Option<i64>)>,
+	orphans: HashMap<OrphanReason, u64>,
+}
+
+impl TransactionTracker {
+	pub fn new(timeout: u32, max_pending: usize, calendar: Calendar) -> TransactionTracker {
+		TransactionTracker { timeout: timeout, max_pending: max_pending, calendar: calendar, now: None,
+		                     pending: HashMap::new(), parents: HashMap::new(), order: VecDeque::new(),
+		                     orphans: HashMap::new() }
+	}
+
+	pub fn pending(&self) -> usize {
+		self.pending.len()
+	}
+
+	// Transactions returned so far with this reason, notifications included
+	pub fn orphans(&self, reason: OrphanReason) -> u64 {
+		self.orphans.get(&reason).map_or(0, |c| *c)
+	}
+
+	pub fn add(&mut self, message: &Message) -> Vec<MailTransaction> {
+		let time = self.calendar.epoch(message.inner());
+		self.add_dated(message, time)
+	}
+
+	// For messages already dated, like SourcedMessage::time
+	pub fn add_at(&mut self, message: &Message, time: i64) -> Vec<MailTransaction> {
+		self.add_dated(message, Some(time))
+	}
+
+	fn add_dated(&mut self, message: &Message, time: Option<i64>) -> Vec<MailTransaction> {
+		let mut done = Vec::new();
+		let inner = message.inner();
+		if let Some(time) = time {
+			if self.now.map_or(true, |now| time > now) {
+				self.now = Some(time);
+				self.expire(&mut done);
+			}
+		}
+		let queue_id = match inner.queue_id() {
+			None => return done,
+			Some(q) => q
+		};
+		let key = (inner.host().to_string(), inner.queue().to_string(), queue_id.to_string());
+		let complete = {
+			let order = &mut self.order;
+			let transaction = self.pending.entry(key.clone()).or_insert_with(|| {
+				order.push_back((key.clone(), time));
+				MailTransaction::new(inner.host(), inner.queue(), queue_id)
+			});
+			transaction.add(message, time);
+			transaction.complete()
+		};
+		if let &Message::Bounce { ref m } = message {
+			let child = (key.0.clone(), key.1.clone(), m.child_queue_id().to_string());
+			if let Some(transaction) = self.pending.get_mut(&child) {
+				transaction.parent = Some(queue_id.to_string());
+			}
+			self.parents.insert(child, key.clone());
+		}
+		if complete {
+			self.finish(&key, &mut done);
+		}
+		while self.pending.len() > self.max_pending {
+			match self.order.pop_front() {
+				None => break,
+				Some((key, _)) => if self.pending.contains_key(&key) {
+					self.end(&key, TransactionEnd::Evicted, &mut done);
+				}
+			}
+		}
+		done
+	}
+
+	// Ends an incomplete transaction, unless qmgr already removed or expired it
+	fn end(&mut self, key: &Key, end: TransactionEnd, done: &mut Vec<MailTransaction>) {
+		if let Some(transaction) = self.pending.get_mut(key) {
+			if transaction.end.is_none() {
+				transaction.end = Some(end);
+			}
+		}
+		self.finish(key, done);
+	}
+
+	// Notifications are attached to the transaction they notify about, which
+	// is only returned once they are all finished
+	fn finish(&mut self, key: &Key, done: &mut Vec<MailTransaction>) {
+		let mut transaction = match self.pending.remove(key) {
+			None => return,
+			Some(t) => t
+		};
+		for reason in transaction.orphan_reasons() {
+			*self.orphans.entry(reason).or_insert(0) += 1;
+		}
+		for notification in transaction.notifications.iter().filter(|n| n.transaction.is_none()) {
+			self.parents.remove(&(key.0.clone(), key.1.clone(), notification.queue_id.clone()));
+		}
+		let parent_key = match self.parents.remove(key) {
+			None => {
+				done.push(transaction);
+				return;
+			},
+			Some(p) => p
+		};
+		let complete = match self.pending.get_mut(&parent_key) {
+			None => {
+				done.push(transaction);
+				return;
+			},
+			Some(parent) => {
+				transaction.parent = Some(parent.queue_id.clone());
+				match parent.notifications.iter_mut().find(|n| n.queue_id == key.2 && n.transaction.is_none()) {
+					None => done.push(transaction),
+					Some(notification) => notification.transaction = Some(Box::new(transaction)),
+				}
+				parent.complete()
+			}
+		};
+		if complete {
+			self.finish(&parent_key, done);
+		}
+	}
+
+	// Returns every transaction still in progress, at the end of the input
+	// Newest first, so that notifications reach their parent
+	pub fn flush(&mut self) -> Vec<MailTransaction> {
+		let mut done = Vec::new();
+		while let Some((key, _)) = self.order.pop_back() {
+			self.end(&key, TransactionEnd::Flushed, &mut done);
+		}
+		done.reverse();
+		done
+	}
+
+	// The order queue is only refreshed lazily: a transaction active since
+	// it was queued goes back at the end instead of timing out
+	fn expire(&mut self, done: &mut Vec<MailTransaction>) {
+		loop {
+			let last = match self.order.front() {
+				None => return,
+				Some(&(ref key, queued)) => match self.pending.get(key) {
+					None => None,
+					Some(transaction) if transaction.last != queued => Some((transaction.last, true)),
+					Some(transaction) => Some((transaction.last, false)),
+				}
+			};
+			let (key, _) = self.order.pop_front().unwrap();
+			match last {
+				// Already completed
+				None => (),
+				Some((last, true)) => self.order.push_back((key, last)),
+				Some((Some(last), false)) if self.now.map_or(true, |now| last + self.timeout as i64 >= now) => {
+					self.order.push_front((key, Some(last)));
+					return;
+				},
+				Some((_, false)) => self.end(&key, TransactionEnd::Timeout, done),
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use super::super::ParserConfig;
+	use super::super::parse_line;
+
+	fn feed(tracker: &mut TransactionTracker, lines: &[&str]) -> Vec<MailTransaction> {
+		let conf = ParserConfig::new(vec![]);
+		let mut done = Vec::new();
+		for line in lines {
+			match parse_line(line.to_string(), &conf) {
+				Err(x) => panic!("Parser Error: {} on {}", x, line),
+				Ok(None) => (),
+				Ok(Some(message)) => done.extend(tracker.add(&message)),
+			}
+		}
+		done
+	}
+
+	#[test]
+	fn complete() {
+		let mut tracker = TransactionTracker::new(3600, 100, Calendar::new(2015, 0));
+		let done = feed(&mut tracker, &[
+			"Aug  4 00:00:01 yuuai postfix/smtpd[1234]: 84ED020916: client=mail.example.com[192.0.2.1]",
+			"Aug  4 00:00:01 yuuai postfix/cleanup[1235]: 84ED020916: message-id=<abc@example.com>",
+			"Aug  4 00:00:02 yuuai postfix/qmgr[1236]: 84ED020916: from=<aaa@example.com>, size=1234, nrcpt=2 (queue active)",
+			"Aug  4 00:00:03 yuuai postfix/smtp[1237]: 84ED020916: to=<xxx@yyy.zzz>, relay=mx.yyy.zzz[198.51.100.1]:25, delay=2, delays=1/0/0.5/0.5, dsn=2.0.0, status=sent (250 2.0.0 Ok: queued as 60F6120AF9)",
+			"Aug  4 00:00:03 yuuai postfix/local[1238]: 84ED020916: to=<bbb@yuuai.example>, relay=local, delay=2, delays=1/0/0/1, dsn=2.0.0, status=sent (delivered to mailbox)",
+		]);
+		assert_eq!(done.len(), 0);
+		assert_eq!(tracker.pending(), 1);
+		let done = feed(&mut tracker, &["Aug  4 00:00:04 yuuai postfix/qmgr[1236]: 84ED020916: removed"]);
+		assert_eq!(tracker.pending(), 0);
+		assert_eq!(done.len(), 1);
+		let transaction = &done[0];
+		assert_eq!(transaction.host, "yuuai");
+		assert_eq!(transaction.queue, "postfix");
+		assert_eq!(transaction.queue_id, "84ED020916");
+		assert_eq!(transaction.client, Some("mail.example.com[192.0.2.1]".to_string()));
+		assert_eq!(transaction.message_id, Some("abc@example.com".to_string()));
+		assert_eq!(transaction.from, Some("aaa@example.com".to_string()));
+		assert_eq!(transaction.size, Some(1234));
+		assert_eq!(transaction.nrcpt, Some(2));
+		assert_eq!(transaction.deliveries.len(), 2);
+		assert_eq!(transaction.deliveries[0].to, "xxx@yyy.zzz");
+		assert_eq!(transaction.deliveries[0].state(), "sent");
+		assert_eq!(transaction.deliveries[0].child_queue_id, Some("60F6120AF9".to_string()));
+		assert_eq!(transaction.deliveries[1].relay, "local");
+		// 2015-08-04T00:00:01Z
+		assert_eq!(transaction.start, Some(1438646401));
+		assert_eq!(transaction.last, Some(1438646404));
+		assert_eq!(transaction.end, Some(TransactionEnd::Removed));
+		assert_eq!(transaction.lines.len(), 6);
+		assert!(!transaction.nrcpt_mismatch());
+		assert_eq!(transaction.orphan_reasons(), vec![]);
+	}
+
+	#[test]
+	fn recipients() {
+		let mut tracker = TransactionTracker::new(24 * 3600, 100, Calendar::new(2015, 0));
+		let done = feed(&mut tracker, &[
+			"Aug  4 00:00:02 yuuai postfix/qmgr[1236]: 84ED020916: from=<aaa@example.com>, size=1234, nrcpt=3 (queue active)",
+			"Aug  4 00:00:03 yuuai postfix/smtp[1237]: 84ED020916: to=<xxx@yyy.zzz>, relay=mx.yyy.zzz[198.51.100.1]:25, delay=1, delays=0.5/0/0.2/0.3, dsn=4.2.0, status=deferred (host mx.yyy.zzz[198.51.100.1] said: 450 4.2.0 Greylisted (in reply to RCPT TO command))",
+			"Aug  4 00:00:03 yuuai postfix/local[1238]: 84ED020916: to=<bbb@yuuai.example>, orig_to=<staff@yuuai.example>, relay=local, delay=1, delays=1/0/0/0, dsn=2.0.0, status=sent (delivered to mailbox)",
+			"Aug  4 00:00:03 yuuai postfix/local[1238]: 84ED020916: to=<ccc@yuuai.example>, orig_to=<staff@yuuai.example>, relay=local, delay=1, delays=1/0/0/0, dsn=2.0.0, status=sent (delivered to mailbox)",
+			"Aug  4 00:00:04 yuuai postfix/smtp[1237]: 84ED020916: to=<ddd@yyy.zzz>, relay=mx.yyy.zzz[198.51.100.1]:25, delay=2, delays=1/0/0.5/0.5, dsn=5.1.1, status=bounced (host mx.yyy.zzz[198.51.100.1] said: 550 5.1.1 User unknown (in reply to RCPT TO command))",
+			"Aug  4 00:10:02 yuuai postfix/qmgr[1236]: 84ED020916: from=<aaa@example.com>, size=1234, nrcpt=1 (queue active)",
+			"Aug  4 00:10:03 yuuai postfix/smtp[1237]: 84ED020916: to=<xxx@yyy.zzz>, relay=mx.yyy.zzz[198.51.100.1]:25, delay=601, delays=600/0/0.5/0.5, dsn=2.0.0, status=sent (250 2.0.0 Ok: queued as 60F6120AF9)",
+			"Aug  4 00:10:04 yuuai postfix/qmgr[1236]: 84ED020916: removed",
+		]);
+		let transaction = &done[0];
+		assert_eq!(transaction.nrcpt, Some(3));
+		let recipients = transaction.recipients();
+		assert_eq!(recipients.len(), 4);
+		assert_eq!(recipients[0].to, "xxx@yyy.zzz");
+		assert_eq!((recipients[0].attempts, recipients[0].sent, recipients[0].deferred), (2, 1, 1));
+		assert_eq!(recipients[0].status, RecipientStatus::Sent);
+		assert_eq!(recipients[0].delay, Some(601));
+		assert_eq!(recipients[0].history.len(), 2);
+		assert_eq!(recipients[0].history[0].dsn, [4, 2, 0]);
+		assert_eq!(recipients[0].history[0].reply(), Some("host mx.yyy.zzz[198.51.100.1] said: 450 4.2.0 Greylisted (in reply to RCPT TO command)"));
+		assert_eq!(recipients[0].history[1].reply(), Some("250 2.0.0 Ok: queued as 60F6120AF9"));
+		assert_eq!(recipients[2].orig_to, Some("staff@yuuai.example".to_string()));
+		assert_eq!(recipients[3].status, RecipientStatus::Bounced);
+		assert_eq!(transaction.envelope_recipients(), 3);
+		assert!(!transaction.nrcpt_mismatch());
+	}
+
+	#[test]
+	fn retries() {
+		let mut tracker = TransactionTracker::new(7 * 24 * 3600, 100, Calendar::new(2015, 0));
+		let done = feed(&mut tracker, &[
+			"Aug  4 00:00:02 yuuai postfix/qmgr[1236]: C217620B0B: from=<aaa@example.com>, size=1234, nrcpt=2 (queue active)",
+			"Aug  4 00:01:08 yuuai postfix/smtp[10627]: C217620B0B: host gmail-smtp-in.l.google.com[64.233.167.26] said: 421 4.7.0 Try again later (in reply to end of DATA command)",
+			"Aug  4 00:01:09 yuuai postfix/smtp[10627]: C217620B0B: host alt1.gmail-smtp-in.l.google.com[64.233.168.27] said: 421 4.7.0 Try again later (in reply to end of DATA command)",
+			"Aug  4 00:01:10 yuuai postfix/smtp[10627]: C217620B0B: to=<xxx@gmail.com>, relay=alt2.gmail-smtp-in.l.google.com[64.233.169.28]:25, delay=68, delays=0/0/60/8, dsn=4.7.0, status=deferred (host alt2.gmail-smtp-in.l.google.com[64.233.169.28] said: 421 4.7.0 Try again later (in reply to end of DATA command))",
+			"Aug  4 00:01:10 yuuai postfix/smtp[10627]: C217620B0B: to=<yyy@gmail.com>, relay=alt2.gmail-smtp-in.l.google.com[64.233.169.28]:25, delay=68, delays=0/0/60/8, dsn=4.7.0, status=deferred (host alt2.gmail-smtp-in.l.google.com[64.233.169.28] said: 421 4.7.0 Try again later (in reply to end of DATA command))",
+			"Aug  4 01:01:10 yuuai postfix/smtp[10628]: C217620B0B: to=<xxx@gmail.com>, relay=none, delay=3668, delays=3600/0/60/0, dsn=4.4.1, status=deferred (connect to gmail-smtp-in.l.google.com[64.233.167.26]:25: Connection timed out)",
+			"Aug  4 01:01:10 yuuai postfix/smtp[10629]: C217620B0B: host gmail-smtp-in.l.google.com[64.233.167.26] said: 421 4.7.0 Try again later (in reply to end of DATA command)",
+			"Aug  4 01:01:11 yuuai postfix/smtp[10629]: C217620B0B: to=<yyy@gmail.com>, relay=alt1.gmail-smtp-in.l.google.com[64.233.168.27]:25, delay=3669, delays=3600/0/60/9, dsn=2.0.0, status=sent (250 2.0.0 OK 1438646471 md4si16637671wic.106 - gsmtp)",
+			"Aug  6 00:01:12 yuuai postfix/qmgr[1236]: C217620B0B: from=<aaa@example.com>, status=expired, returned to sender",
+			"Aug  6 00:01:12 yuuai postfix/qmgr[1236]: C217620B0B: removed",
+		]);
+		let recipients = done[0].recipients();
+		assert_eq!(recipients[0].status, RecipientStatus::Expired);
+		let history = &recipients[0].history;
+		assert_eq!(history.iter().map(|d| d.relay.as_str()).collect::<Vec<_>>(),
+		           vec!["64.233.169.28", "none"]);
+		assert_eq!(history[0].relay_errors.iter().map(|e| e.host.as_str()).collect::<Vec<_>>(),
+		           vec!["gmail-smtp-in.l.google.com[64.233.167.26]", "alt1.gmail-smtp-in.l.google.com[64.233.168.27]"]);
+		assert_eq!(history[0].relay_errors[0].message, "421 4.7.0 Try again later (in reply to end of DATA command)");
+		assert_eq!(history[1].time.unwrap() - history[0].time.unwrap(), 3600);
+		assert_eq!(history[1].dsn, [4, 4, 1]);
+		assert_eq!(history[1].reply(), Some("connect to gmail-smtp-in.l.google.com[64.233.167.26]:25: Connection timed out"));
+		assert_eq!(history[1].relay_errors.len(), 0);
+		assert_eq!(recipients[1].status, RecipientStatus::Sent);
+		assert_eq!(recipients[1].history[0].relay_errors.len(), 2);
+		assert_eq!(recipients[1].history[1].relay_errors.len(), 1);
+	}
+
+	#[test]
+	fn recipients_missing() {
+		let mut tracker = TransactionTracker::new(7 * 24 * 3600, 100, Calendar::new(2015, 0));
+		let done = feed(&mut tracker, &[
+			"Aug  4 00:00:02 yuuai postfix/qmgr[1236]: 84ED020916: from=<aaa@example.com>, size=1234, nrcpt=2 (queue active)",
+			"Aug  4 00:00:03 yuuai postfix/smtp[1237]: 84ED020916: to=<xxx@yyy.zzz>, relay=mx.yyy.zzz[198.51.100.1]:25, delay=1, delays=0.5/0/0.2/0.3, dsn=4.2.0, status=deferred (host mx.yyy.zzz[198.51.100.1] said: 450 4.2.0 Greylisted (in reply to RCPT TO command))",
+			"Aug  6 00:00:03 yuuai postfix/qmgr[1236]: 84ED020916: from=<aaa@example.com>, status=expired, returned to sender",
+			"Aug  6 00:00:03 yuuai postfix/qmgr[1236]: 84ED020916: removed",
+		]);
+		let recipients = done[0].recipients();
+		assert_eq!(recipients[0].status, RecipientStatus::Expired);
+		assert_eq!(recipients[0].delay, Some(1));
+		assert!(done[0].nrcpt_mismatch());
+		feed(&mut tracker, &["Aug  6 00:00:05 yuuai postfix/qmgr[1236]: 0345620AE4: from=<aaa@example.com>, size=1234, nrcpt=2 (queue active)"]);
+		assert!(!tracker.flush()[0].nrcpt_mismatch());
+	}
+
+	#[test]
+	fn timeout() {
+		let mut tracker = TransactionTracker::new(600, 100, Calendar::new(2015, 0));
+		let done = feed(&mut tracker, &[
+			"Aug  4 00:00:01 yuuai postfix/smtpd[1234]: 84ED020916: client=mail.example.com[192.0.2.1]",
+			"Aug  4 00:00:01 yuuai postfix/smtpd[1234]: 0345620AE4: client=mail.example.com[192.0.2.1]",
+			"Aug  4 00:09:00 yuuai postfix/cleanup[1235]: 84ED020916: message-id=<abc@example.com>",
+			"Aug  4 00:10:02 yuuai postfix/cleanup[1235]: C217620B0B: message-id=<def@example.com>",
+		]);
+		assert_eq!(done.len(), 1);
+		assert_eq!(done[0].queue_id, "0345620AE4");
+		assert_eq!(done[0].end, Some(TransactionEnd::Timeout));
+		let done = feed(&mut tracker, &["Aug  4 00:19:01 yuuai postfix/cleanup[1235]: C217620B0B: message-id=<def@example.com>"]);
+		assert_eq!(done.len(), 1);
+		assert_eq!(done[0].queue_id, "84ED020916");
+		let mut done = tracker.flush();
+		assert_eq!(done.len(), 1);
+		let transaction = done.pop().unwrap();
+		assert_eq!(transaction.queue_id, "C217620B0B");
+		assert_eq!(transaction.end, Some(TransactionEnd::Flushed));
+	}
+
+	#[test]
+	fn new_year() {
+		let mut tracker = TransactionTracker::new(600, 100, Calendar::new(2015, 0));
+		let done = feed(&mut tracker, &[
+			"Dec 31 23:50:00 yuuai postfix/smtpd[1234]: 0345620AE4: client=mail.example.com[192.0.2.1]",
+			"Dec 31 23:59:59 yuuai postfix/smtpd[1234]: 84ED020916: client=mail.example.com[192.0.2.1]",
+			"Dec 31 23:59:59 yuuai postfix/qmgr[1236]: 84ED020916: from=<aaa@example.com>, size=1234, nrcpt=1 (queue active)",
+			"Jan  1 00:00:01 yuuai postfix/smtp[1237]: 84ED020916: to=<xxx@yyy.zzz>, relay=mx.yyy.zzz[198.51.100.1]:25, delay=2, delays=1/0/0.5/0.5, dsn=2.0.0, status=sent (250 2.0.0 Ok: queued as 60F6120AF9)",
+			"Jan  1 00:00:02 yuuai postfix/qmgr[1236]: 84ED020916: removed",
+		]);
+		assert_eq!(done.len(), 2);
+		assert_eq!(done[0].queue_id, "0345620AE4");
+		assert_eq!(done[0].end, Some(TransactionEnd::Timeout));
+		assert_eq!(done[1].queue_id, "84ED020916");
+		// 2015-12-31T23:59:59Z
+		assert_eq!(done[1].start, Some(1451606399));
+		assert_eq!(done[1].recipients()[0].delay, Some(2));
+	}
+
+	#[test]
+	fn dated() {
+		let conf = ParserConfig::new(vec![]);
+		let mut tracker = TransactionTracker::new(600, 100, Calendar::new(2015, 0));
+		let lines = [
+			("Aug  4 00:00:01 yuuai postfix/smtpd[1234]: 84ED020916: client=mail.example.com[192.0.2.1]", 1438646401),
+			("Aug  4 00:00:02 yuuai postfix/qmgr[1236]: 84ED020916: removed", 1438646402),
+		];
+		let mut done = Vec::new();
+		for &(line, time) in lines.iter() {
+			match parse_line(line.to_string(), &conf) {
+				Ok(Some(message)) => done.extend(tracker.add_at(&message, time)),
+				_ => panic!("Failed to parse {}", line),
+			}
+		}
+		assert_eq!(done.len(), 1);
+		assert_eq!((done[0].start, done[0].last), (Some(1438646401), Some(1438646402)));
+	}
+
+	#[test]
+	fn bounce() {
+		let mut tracker = TransactionTracker::new(3600, 100, Calendar::new(2015, 0));
+		let done = feed(&mut tracker, &[
+			"Aug  4 00:03:10 yuuai postfix/qmgr[1236]: 7C091208A3: from=<aaa@example.com>, size=1234, nrcpt=1 (queue active)",
+			"Aug  4 00:03:14 yuuai postfix/smtp[1237]: 7C091208A3: to=<xxx@yyy.zzz>, relay=mx.yyy.zzz[198.51.100.1]:25, delay=4, delays=1/0/2/1, dsn=5.1.1, status=bounced (host mx.yyy.zzz[198.51.100.1] said: 550 5.1.1 User unknown (in reply to RCPT TO command))",
+			"Aug  4 00:03:15 yuuai postfix/cleanup[1235]: A270E20915: message-id=<20150804000315.A270E20915@yuuai.example>",
+			"Aug  4 00:03:15 yuuai postfix/bounce[24350]: 7C091208A3: sender non-delivery notification: A270E20915",
+			"Aug  4 00:03:15 yuuai postfix/qmgr[1236]: 7C091208A3: removed",
+			"Aug  4 00:03:15 yuuai postfix/qmgr[1236]: A270E20915: from=<>, size=3456, nrcpt=1 (queue active)",
+		]);
+		assert_eq!(done.len(), 0);
+		let done = feed(&mut tracker, &[
+			"Aug  4 00:03:16 yuuai postfix/smtp[1237]: A270E20915: to=<aaa@example.com>, relay=mx.example.com[192.0.2.2]:25, delay=1, delays=0/0/0.5/0.5, dsn=5.1.1, status=bounced (host mx.example.com[192.0.2.2] said: 550 5.1.1 User unknown (in reply to RCPT TO command))",
+			"Aug  4 00:03:16 yuuai postfix/cleanup[1235]: B381F31A26: message-id=<20150804000316.B381F31A26@yuuai.example>",
+			"Aug  4 00:03:16 yuuai postfix/bounce[24350]: A270E20915: postmaster non-delivery notification: B381F31A26",
+			"Aug  4 00:03:16 yuuai postfix/qmgr[1236]: A270E20915: removed",
+			"Aug  4 00:03:17 yuuai postfix/local[1238]: B381F31A26: to=<root@yuuai.example>, orig_to=<postmaster>, relay=local, delay=1, delays=0/0/0/1, dsn=2.0.0, status=sent (delivered to mailbox)",
+		]);
+		assert_eq!(done.len(), 0);
+		let done = feed(&mut tracker, &["Aug  4 00:03:17 yuuai postfix/qmgr[1236]: B381F31A26: removed"]);
+		assert_eq!(done.len(), 1);
+		assert_eq!(tracker.pending(), 0);
+		let transaction = &done[0];
+		assert_eq!(transaction.queue_id, "7C091208A3");
+		assert_eq!(transaction.notifications.len(), 1);
+		let notification = &transaction.notifications[0];
+		assert!(notification.kind == BounceKind::SenderNonDelivery);
+		assert_eq!(notification.status(), NotificationStatus::Bounced);
+		let ndr = notification.transaction.as_ref().unwrap();
+		assert_eq!(ndr.queue_id, "A270E20915");
+		assert_eq!(ndr.parent, Some("7C091208A3".to_string()));
+		assert_eq!(ndr.from, Some("".to_string()));
+		assert_eq!(ndr.notifications[0].status(), NotificationStatus::Delivered);
+		assert_eq!(ndr.notifications[0].transaction.as_ref().unwrap().deliveries[0].to, "root@yuuai.example");
+	}
+
+	#[test]
+	fn expired() {
+		let mut tracker = TransactionTracker::new(3600, 100, Calendar::new(2015, 0));
+		feed(&mut tracker, &[
+			"Aug  4 00:03:10 yuuai postfix/qmgr[1236]: 7C091208A3: from=<aaa@example.com>, status=expired, returned to sender",
+			"Aug  4 00:03:15 yuuai postfix/bounce[24350]: 7C091208A3: sender non-delivery notification: A270E20915",
+			"Aug  4 00:03:15 yuuai postfix/qmgr[1236]: 7C091208A3: removed",
+		]);
+		let done = tracker.flush();
+		assert_eq!(done.len(), 1);
+		assert_eq!(done[0].end, Some(TransactionEnd::Expired));
+		assert_eq!(done[0].notifications[0].status(), NotificationStatus::Pending);
+		assert!(done[0].notifications[0].transaction.is_none());
+	}
+
+	#[test]
+	fn orphans() {
+		let mut tracker = TransactionTracker::new(600, 100, Calendar::new(2015, 0));
+		let done = feed(&mut tracker, &[
+			"Aug  4 00:00:01 yuuai postfix/smtp[1237]: 84ED020916: to=<xxx@yyy.zzz>, relay=mx.yyy.zzz[198.51.100.1]:25, delay=2, delays=1/0/0.5/0.5, dsn=2.0.0, status=sent (250 2.0.0 Ok: queued as 60F6120AF9)",
+			"Aug  4 00:00:02 yuuai postfix/qmgr[1236]: 84ED020916: removed",
+			"Aug  4 00:00:03 yuuai postfix/cleanup[1235]: 0345620AE4: message-id=<abc@example.com>",
+			"Aug  4 00:00:04 yuuai postfix/qmgr[1236]: C217620B0B: from=<aaa@example.com>, status=expired, returned to sender",
+			"Aug  4 00:00:04 yuuai postfix/qmgr[1236]: C217620B0B: removed",
+			"Aug  4 00:20:00 yuuai postfix/smtpd[1234]: 67D8720887: client=mail.example.com[192.0.2.1]",
+		]);
+		assert_eq!(done.len(), 3);
+		assert_eq!(done[0].queue_id, "84ED020916");
+		assert_eq!(done[0].orphan_reasons(), vec![OrphanReason::MissingStart]);
+		assert_eq!(done[1].queue_id, "C217620B0B");
+		assert_eq!(done[1].orphan_reasons(), vec![OrphanReason::MissingStart, OrphanReason::Expired]);
+		assert_eq!(done[2].queue_id, "0345620AE4");
+		assert_eq!(done[2].orphan_reasons(), vec![OrphanReason::Timeout]);
+		let done = tracker.flush();
+		assert_eq!(done[0].orphan_reasons(), vec![OrphanReason::MissingEnd]);
+		assert_eq!(tracker.orphans(OrphanReason::MissingStart), 2);
+		assert_eq!(tracker.orphans(OrphanReason::MissingEnd), 1);
+		assert_eq!(tracker.orphans(OrphanReason::Timeout), 1);
+		assert_eq!(tracker.orphans(OrphanReason::Expired), 1);
+		assert_eq!(format!("{}", OrphanReason::MissingStart), "missing start");
+	}
+
+	#[test]
+	fn bounded() {
+		let mut tracker = TransactionTracker::new(3600, 1, Calendar::new(2015, 0));
+		let done = feed(&mut tracker, &[
+			"Aug  4 00:00:01 yuuai postfix/smtpd[1234]: 84ED020916: client=mail.example.com[192.0.2.1]",
+			"Aug  4 00:00:01 ozgurluk postfix/smtpd[1234]: 84ED020916: client=mail.example.com[192.0.2.1]",
+		]);
+		assert_eq!(done.len(), 1);
+		assert_eq!(done[0].host, "yuuai");
+		assert_eq!(done[0].end, Some(TransactionEnd::Evicted));
+		assert_eq!(tracker.pending(), 1);
+	}
+}